@@ -0,0 +1,59 @@
+//! Authorization of the admin endpoints, which are only routed when an
+//! admin token is configured
+
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use subtle::ConstantTimeEq;
+
+use crate::AppError;
+
+/// The bearer token admin requests must carry
+#[derive(Clone)]
+pub struct AdminToken(pub String);
+
+/// Check the bearer token of an admin request
+pub fn authorize(headers: &HeaderMap, token: &AdminToken) -> Result<(), AppError> {
+    let authorized = headers
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        // in constant time, so the token can't be guessed byte by byte
+        .is_some_and(|t| bool::from(t.as_bytes().ct_eq(token.0.as_bytes())));
+
+    match authorized {
+        true => Ok(()),
+        false => Err(AppError::Unauthorized("invalid admin token".into())),
+    }
+}
+
+/// Whether the server runs with `DEBUG` set
+#[derive(Clone, Copy)]
+pub struct DebugMode(pub bool);
+
+/// Check a request may see debugging internals, which it may in debug
+/// mode or with the admin token
+pub fn authorize_debug(
+    headers: &HeaderMap,
+    debug: DebugMode,
+    token: Option<&AdminToken>,
+) -> Result<(), AppError> {
+    match (debug, token) {
+        (DebugMode(true), _) => Ok(()),
+        (_, Some(token)) => authorize(headers, token),
+        _ => Err(AppError::Unauthorized(
+            "debugging output is disabled".into(),
+        )),
+    }
+}
+
+#[test]
+fn test_authorize_debug() {
+    let token = AdminToken("secret".into());
+    let mut headers = HeaderMap::new();
+    assert!(authorize_debug(&headers, DebugMode(true), None).is_ok());
+    assert!(authorize_debug(&headers, DebugMode(false), None).is_err());
+    assert!(authorize_debug(&headers, DebugMode(false), Some(&token)).is_err());
+    headers.insert(AUTHORIZATION, "Bearer secreT".parse().unwrap());
+    assert!(authorize_debug(&headers, DebugMode(false), Some(&token)).is_err());
+    headers.insert(AUTHORIZATION, "Bearer secret".parse().unwrap());
+    assert!(authorize_debug(&headers, DebugMode(false), Some(&token)).is_ok());
+}
@@ -0,0 +1,177 @@
+//! Annotating a block of Japanese text with the kanji it uses.
+
+use axum::{Extension, Json};
+use futures::TryStreamExt;
+use model::{kana::is_kanji, kanji::Kanji};
+use mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+
+use crate::{normalize::normalize, AppError, Database, Store};
+
+/// The longest text accepted, in characters
+pub const MAX_TEXT_LEN: usize = 10_000;
+
+#[derive(Deserialize)]
+pub struct AnalyzeRequest {
+    pub text: String,
+}
+
+/// A kanji used in the text
+#[derive(Debug, PartialEq, Serialize)]
+pub struct KanjiUse {
+    pub literal: char,
+    /// How many times the kanji appears
+    pub count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grade: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jlptn: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub freq: Option<u32>,
+    pub meanings: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    /// Characters in the text, not counting whitespace
+    pub characters: usize,
+    /// Kanji in the text, counting each use
+    pub kanji: usize,
+    pub unique_kanji: usize,
+    /// Distinct kanji which are jouyou, i.e. have a grade up to 8
+    pub jouyou: usize,
+    /// Distinct kanji only approved for use in names
+    pub jinmeiyou: usize,
+    /// The share of distinct kanji which are jouyou, in percent
+    pub jouyou_percent: f64,
+    /// Kanji without an entry in the dictionary
+    pub unknown: Vec<char>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Analysis {
+    /// The kanji with an entry, in the order they first appear
+    pub kanji: Vec<KanjiUse>,
+    pub summary: Summary,
+}
+
+/// The kanji of a text with how often each appears, in the order they
+/// first appear. Iteration marks such as 々 aren't counted.
+fn extract(text: &str) -> Vec<(char, usize)> {
+    let mut counts: Vec<(char, usize)> = vec![];
+    for c in text
+        .chars()
+        .filter(|c| is_kanji(*c) && !matches!(c, '々' | '〆'))
+    {
+        match counts.iter_mut().find(|(k, _)| *k == c) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((c, 1)),
+        }
+    }
+    counts
+}
+
+/// Annotate the kanji of a text with the entries found for them
+fn analyze(text: &str, found: &[Kanji]) -> Analysis {
+    let counts = extract(text);
+    let mut kanji = vec![];
+    let mut summary = Summary {
+        characters: text.chars().filter(|c| !c.is_whitespace()).count(),
+        kanji: counts.iter().map(|(_, n)| n).sum(),
+        unique_kanji: counts.len(),
+        ..Default::default()
+    };
+
+    for (literal, count) in counts {
+        let Some(k) = found.iter().find(|k| k.literal == literal) else {
+            summary.unknown.push(literal);
+            continue;
+        };
+        match k.info.grade {
+            Some(1..=8) => summary.jouyou += 1,
+            Some(9 | 10) => summary.jinmeiyou += 1,
+            _ => (),
+        }
+        kanji.push(KanjiUse {
+            literal,
+            count,
+            grade: k.info.grade,
+            jlptn: k.info.jlptn,
+            freq: k.info.freq,
+            meanings: k.meanings.clone(),
+        });
+    }
+    if summary.unique_kanji > 0 {
+        summary.jouyou_percent = 100.0 * summary.jouyou as f64 / summary.unique_kanji as f64;
+    }
+
+    Analysis { kanji, summary }
+}
+
+pub async fn post_analyze(
+    db: Extension<Database>,
+    store: Extension<Store>,
+    Json(request): Json<AnalyzeRequest>,
+) -> Result<Json<Analysis>, AppError> {
+    if request.text.chars().count() > MAX_TEXT_LEN {
+        return Err(AppError::BadRequest(format!(
+            "text is longer than {} characters",
+            MAX_TEXT_LEN
+        )));
+    }
+    let text = normalize(&request.text);
+    let literals: Vec<char> = extract(&text).into_iter().map(|(c, _)| c).collect();
+
+    let found: Vec<Kanji> = match store.as_ref() {
+        Some(store) => literals
+            .iter()
+            .filter_map(|c| store.get(*c).transpose())
+            .collect::<Result<_, _>>()?,
+        None => {
+            let keys: Vec<String> = literals.iter().map(|c| c.to_string()).collect();
+            db.collection::<Kanji>("kanjidic")
+                .find(doc! { "literal": { "$in": keys } }, None)
+                .await?
+                .try_collect()
+                .await?
+        }
+    };
+
+    Ok(Json(analyze(&text, &found)))
+}
+
+#[test]
+fn test_analyze() {
+    use model::kanji::{Info, References};
+
+    let kanji = |literal, grade| {
+        let info = Info::builder(1, 1).grade(grade).build();
+        Kanji::builder(literal, info, References::builder("0").build())
+            .meanings(vec![literal.to_string()])
+            .build()
+    };
+    let found = [kanji('日', 1), kanji('本', 1), kanji('亘', 9)];
+
+    let analysis = analyze("日本の日々、亘と\u{20B9F}。", &found);
+    let literals: Vec<(char, usize)> = analysis
+        .kanji
+        .iter()
+        .map(|k| (k.literal, k.count))
+        .collect();
+    assert_eq!(literals, [('日', 2), ('本', 1), ('亘', 1)]);
+    assert_eq!(analysis.kanji[0].meanings, ["日"]);
+    assert_eq!(
+        analysis.summary,
+        Summary {
+            characters: 10,
+            kanji: 5,
+            unique_kanji: 4,
+            jouyou: 2,
+            jinmeiyou: 1,
+            jouyou_percent: 50.0,
+            unknown: vec!['\u{20B9F}'],
+        }
+    );
+
+    assert_eq!(analyze(" ", &[]).summary, Summary::default());
+}
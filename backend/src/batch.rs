@@ -0,0 +1,47 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::AppError;
+
+/// The maximum number of keys accepted by a single batch request
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// Check a batch request is non-empty and within the size limit
+pub fn validate<T>(keys: &[T]) -> Result<(), AppError> {
+    if keys.is_empty() {
+        return Err(AppError::BadRequest("empty batch".into()));
+    }
+    if keys.len() > MAX_BATCH_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "batch size {} exceeds the limit of {}",
+            keys.len(),
+            MAX_BATCH_SIZE
+        )));
+    }
+    Ok(())
+}
+
+/// Arrange the documents found for a batch in the order of the requested
+/// keys, with `None` wherever a key had no matching document.
+pub fn in_order<K, T, F>(keys: &[K], found: Vec<T>, key: F) -> Vec<Option<T>>
+where
+    K: Eq + Hash,
+    T: Clone,
+    F: Fn(&T) -> K,
+{
+    let found: HashMap<K, T> = found.into_iter().map(|t| (key(&t), t)).collect();
+    keys.iter().map(|k| found.get(k).cloned()).collect()
+}
+
+#[test]
+fn test_in_order() {
+    let found = vec![(3, 'c'), (1, 'a')];
+    let out = in_order(&[1, 2, 3, 1], found, |t| t.0);
+    assert_eq!(out, [Some((1, 'a')), None, Some((3, 'c')), Some((1, 'a'))]);
+}
+
+#[test]
+fn test_validate() {
+    assert!(validate::<u32>(&[]).is_err());
+    assert!(validate(&[0; MAX_BATCH_SIZE]).is_ok());
+    assert!(validate(&[0; MAX_BATCH_SIZE + 1]).is_err());
+}
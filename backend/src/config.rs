@@ -0,0 +1,261 @@
+//! The server configuration, read from environment variables and an
+//! optional `.env` file. Every missing or invalid variable is reported at
+//! once rather than failing on the first.
+
+use std::{collections::HashMap, fmt, time::Duration};
+
+use model::namespace;
+
+use crate::{cache::Cache, errors::Envelope, jobs, ratelimit::RateLimit, tenant};
+
+/// The port served on when SERVER_PORT is unset
+const DEFAULT_PORT: u16 = 8080;
+
+/// How long cached responses are kept when CACHE_TTL is unset
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+#[derive(Clone)]
+pub struct Config {
+    /// A Redis server caching kanji lookups and lists, which are served
+    /// uncached if unset
+    pub redis_url: Option<String>,
+    /// How long cached responses are kept
+    pub cache_ttl: Duration,
+    pub mongo_url: String,
+    #[cfg_attr(feature = "lambda", allow(dead_code))]
+    pub server_port: u16,
+    /// Enables debugging endpoints such as search explain, and indents
+    /// every JSON response with debug headers added
+    pub debug: bool,
+    /// A read-only kanji store written by populate, used for lookups
+    /// instead of the database when set
+    pub store_path: Option<String>,
+    /// How error responses are rendered
+    pub error_envelope: Envelope,
+    /// The namespace served by default, or the production data if unset
+    pub namespace: Option<String>,
+    /// Further namespaces clients can select with a header
+    pub namespaces: Vec<String>,
+    /// A JSON file of search relevance weights
+    pub search_weights: Option<String>,
+    /// The bearer token of the admin endpoints, which are disabled if unset
+    pub admin_token: Option<String>,
+    /// The directory dumps are served from, which are disabled if unset
+    pub dump_dir: Option<String>,
+    /// Count downloads of each dump version
+    pub count_downloads: bool,
+    /// A shell command refreshing the data, run by the job scheduler
+    pub repopulate_command: Option<String>,
+    /// How often to run the repopulate command, e.g. 1d. It is only run
+    /// through the admin endpoint if unset.
+    pub repopulate_interval: Option<Duration>,
+    /// How many requests each client may make, unlimited if unset
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Every problem found with the configuration
+#[derive(Debug, PartialEq)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for problem in &self.0 {
+            writeln!(f, "  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the lines of a `.env` file, `KEY=value` with optional quotes
+/// around the value. Blank lines and `#` comments are skipped.
+pub fn parse_env_file(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.strip_prefix("export ").unwrap_or(l).split_once('='))
+        .map(|(k, v)| {
+            let v = v.trim();
+            let unquoted = ['"', '\'']
+                .iter()
+                .find_map(|q| v.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)));
+            (k.trim().to_owned(), unquoted.unwrap_or(v).to_owned())
+        })
+        .collect()
+}
+
+/// Reads variables, recording the problems with them
+struct Reader<F> {
+    var: F,
+    problems: Vec<String>,
+}
+
+impl<F: Fn(&str) -> Option<String>> Reader<F> {
+    /// A variable which is unset when empty
+    fn optional(&self, name: &str) -> Option<String> {
+        (self.var)(name).filter(|v| !v.is_empty())
+    }
+
+    fn required(&mut self, name: &str) -> String {
+        self.optional(name).unwrap_or_else(|| {
+            self.problems.push(format!("{} is not set", name));
+            String::new()
+        })
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.optional(name).is_some_and(|v| v == "1" || v == "true")
+    }
+
+    /// Parse a variable if it is set, recording it as invalid if it can't be
+    fn parse<T>(&mut self, name: &str, parse: impl FnOnce(&str) -> Option<T>) -> Option<T> {
+        let value = self.optional(name)?;
+        let parsed = parse(&value);
+        if parsed.is_none() {
+            self.problems
+                .push(format!("{} is invalid: {:?}", name, value));
+        }
+        parsed
+    }
+}
+
+impl Config {
+    /// Read the configuration from the environment, falling back to a
+    /// `.env` file in the working directory for unset variables
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let file = std::fs::read_to_string(".env")
+            .map(|t| parse_env_file(&t))
+            .unwrap_or_default();
+        Config::from_vars(|name| std::env::var(name).ok().or_else(|| file.get(name).cloned()))
+    }
+
+    /// Read the configuration from any source of variables
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut r = Reader {
+            var,
+            problems: vec![],
+        };
+
+        let cache_ttl = r.parse("CACHE_TTL", jobs::parse_interval);
+        let redis_url = r.optional("REDIS_URL");
+        if let Some(url) = redis_url.as_ref() {
+            if let Err(reason) = Cache::new(url, DEFAULT_CACHE_TTL) {
+                r.problems
+                    .push(format!("REDIS_URL is invalid: {:?}, {}", url, reason));
+            }
+        }
+        let error_envelope = r.parse("ERROR_ENVELOPE", |e| {
+            matches!(e, "text" | "json").then(|| Envelope::parse(e))
+        });
+        let positive = |n: &str| n.parse().ok().filter(|n| *n > 0);
+        let rate_limit = r.parse("RATE_LIMIT", positive);
+        let burst = r.parse("RATE_LIMIT_BURST", positive);
+        let rate_limit = rate_limit.map(|per_minute| RateLimit {
+            per_minute,
+            burst: burst.unwrap_or(per_minute),
+            trust_forwarded: r.flag("TRUST_FORWARDED_FOR"),
+        });
+        let namespace = r.optional("NAMESPACE");
+        if let Some(ns) = namespace.as_ref().filter(|ns| !namespace::is_valid(ns)) {
+            r.problems.push(format!("NAMESPACE is invalid: {:?}", ns));
+        }
+
+        let config = Config {
+            redis_url,
+            cache_ttl: cache_ttl.unwrap_or(DEFAULT_CACHE_TTL),
+            mongo_url: r.required("MONGODB_URL"),
+            server_port: r
+                .parse("SERVER_PORT", |p| p.parse().ok())
+                .unwrap_or(DEFAULT_PORT),
+            debug: r.flag("DEBUG"),
+            store_path: r.optional("KANJIDIC_STORE"),
+            error_envelope: error_envelope.unwrap_or(Envelope::Text),
+            namespace,
+            namespaces: r
+                .parse("NAMESPACES", |n| tenant::parse_list(n).ok())
+                .unwrap_or_default(),
+            search_weights: r.optional("SEARCH_WEIGHTS"),
+            admin_token: r.optional("ADMIN_TOKEN"),
+            dump_dir: r.optional("DUMP_DIR"),
+            count_downloads: r.flag("COUNT_DOWNLOADS"),
+            repopulate_command: r.optional("REPOPULATE_COMMAND"),
+            repopulate_interval: r.parse("REPOPULATE_INTERVAL", jobs::parse_interval),
+            rate_limit,
+        };
+
+        match r.problems.is_empty() {
+            true => Ok(config),
+            false => Err(ConfigError(r.problems)),
+        }
+    }
+}
+
+#[test]
+fn test_parse_env_file() {
+    let vars = parse_env_file(
+        "# local settings\n\nMONGODB_URL=mongodb://localhost\nexport DEBUG=1\nADMIN_TOKEN = \"a b\"\nbroken\n",
+    );
+    assert_eq!(vars.len(), 3);
+    assert_eq!(vars["MONGODB_URL"], "mongodb://localhost");
+    assert_eq!(vars["DEBUG"], "1");
+    assert_eq!(vars["ADMIN_TOKEN"], "a b");
+}
+
+#[test]
+fn test_from_vars() {
+    let vars = |pairs: &[(&str, &str)]| {
+        let m: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name: &str| m.get(name).cloned()
+    };
+
+    let config = Config::from_vars(vars(&[("MONGODB_URL", "mongodb://localhost")]))
+        .ok()
+        .unwrap();
+    assert_eq!(config.server_port, DEFAULT_PORT);
+    assert_eq!(config.cache_ttl, DEFAULT_CACHE_TTL);
+    assert_eq!(config.redis_url, None);
+    assert!(config.error_envelope == Envelope::Text);
+    assert_eq!(config.rate_limit, None);
+
+    let config = Config::from_vars(vars(&[
+        ("MONGODB_URL", "mongodb://localhost"),
+        ("RATE_LIMIT", "120"),
+        ("TRUST_FORWARDED_FOR", "1"),
+    ]))
+    .ok()
+    .unwrap();
+    assert_eq!(
+        config.rate_limit,
+        Some(RateLimit {
+            per_minute: 120,
+            burst: 120,
+            trust_forwarded: true
+        })
+    );
+
+    let error = Config::from_vars(vars(&[
+        ("SERVER_PORT", "eighty"),
+        ("REPOPULATE_INTERVAL", "daily"),
+        ("REDIS_URL", "localhost"),
+        ("ERROR_ENVELOPE", "xml"),
+        ("NAMESPACES", "a.b"),
+        ("RATE_LIMIT", "0"),
+    ]))
+    .err()
+    .unwrap();
+    assert_eq!(
+        error.0,
+        [
+            "REDIS_URL is invalid: \"localhost\", not a redis:// URL",
+            "ERROR_ENVELOPE is invalid: \"xml\"",
+            "RATE_LIMIT is invalid: \"0\"",
+            "MONGODB_URL is not set",
+            "SERVER_PORT is invalid: \"eighty\"",
+            "NAMESPACES is invalid: \"a.b\"",
+            "REPOPULATE_INTERVAL is invalid: \"daily\"",
+        ]
+    );
+}
@@ -0,0 +1,127 @@
+//! Paging by cursor, resuming after the sort key of the last result
+//! returned. Unlike an offset this stays consistent while entries are
+//! added or removed between requests, e.g. by populate.
+//!
+//! A cursor is the hex encoded JSON of the key. Clients should treat it
+//! as opaque, since the key differs between listings.
+
+use mongodb::bson::Bson;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::AppError;
+
+/// Where a page starts
+#[derive(Debug, PartialEq)]
+pub enum Paging<K> {
+    /// At the `from` offset, returning a plain array
+    Offset,
+    /// At the first result, returning a [`CursorPage`]
+    Start,
+    /// After the result with this key, returning a [`CursorPage`]
+    After(K),
+}
+
+impl<K: DeserializeOwned> Paging<K> {
+    /// Read the `cursor` parameter, which is empty for the first page.
+    /// Without it the `from` offset is used.
+    pub fn new(cursor: Option<&str>, from: Option<i64>) -> Result<Self, AppError> {
+        match cursor {
+            None => Ok(Paging::Offset),
+            Some(_) if from.is_some() => Err(AppError::BadRequest(
+                "from can't be combined with cursor".into(),
+            )),
+            Some("") => Ok(Paging::Start),
+            Some(cursor) => decode(cursor).map(Paging::After),
+        }
+    }
+
+    pub fn after(&self) -> Option<&K> {
+        match self {
+            Paging::After(key) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+pub fn encode<K: Serialize>(key: &K) -> String {
+    serde_json::to_vec(key)
+        .expect("failed to encode cursor")
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn decode<K: DeserializeOwned>(cursor: &str) -> Result<K, AppError> {
+    let invalid = || AppError::BadRequest(format!("invalid cursor {:?}", cursor));
+    if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid())?;
+    serde_json::from_slice(&bytes).map_err(|_| invalid())
+}
+
+/// A numeric sort key of a document, whichever number type it is stored
+/// as
+pub fn number(value: Option<&Bson>) -> Option<f64> {
+    match value? {
+        Bson::Double(n) => Some(*n),
+        Bson::Int32(n) => Some(*n as f64),
+        Bson::Int64(n) => Some(*n as f64),
+        _ => None,
+    }
+}
+
+/// One page of results, with the cursor of the next if there is one
+#[derive(Serialize)]
+pub struct CursorPage<T> {
+    pub results: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+impl<T> CursorPage<T> {
+    /// A page of `count` results with their keys, from up to `count + 1`
+    /// read to tell whether there are more
+    pub fn new<K: Serialize>(mut results: Vec<(K, T)>, count: i64) -> Self {
+        let more = results.len() > count as usize;
+        results.truncate(count as usize);
+        let next = match more {
+            true => results.last().map(|(key, _)| encode(key)),
+            false => None,
+        };
+        CursorPage {
+            results: results.into_iter().map(|(_, r)| r).collect(),
+            next,
+        }
+    }
+}
+
+#[test]
+fn test_cursor() {
+    let cursor = encode(&(1.5, '水'));
+    assert_eq!(decode::<(f64, char)>(&cursor).ok(), Some((1.5, '水')));
+    for bad in ["x", "abc", "zz", "7b", "ｆｆ"] {
+        assert!(decode::<(f64, char)>(bad).is_err(), "{}", bad);
+    }
+
+    assert_eq!(
+        Paging::<u32>::new(None, Some(10)).ok(),
+        Some(Paging::Offset)
+    );
+    assert_eq!(Paging::<u32>::new(Some(""), None).ok(), Some(Paging::Start));
+    assert_eq!(
+        Paging::<u32>::new(Some(&encode(&7)), None).ok(),
+        Some(Paging::After(7))
+    );
+    assert!(Paging::<u32>::new(Some(""), Some(10)).is_err());
+
+    let page = CursorPage::new(vec![(1, 'a'), (2, 'b'), (3, 'c')], 2);
+    assert_eq!(page.results, ['a', 'b']);
+    assert_eq!(page.next, Some(encode(&2)));
+    let page = CursorPage::new(vec![(1, 'a'), (2, 'b')], 2);
+    assert_eq!(page.next, None);
+}
@@ -0,0 +1,162 @@
+//! Output for exploring the API by hand. `?pretty=true` indents JSON
+//! responses, and `X-Debug-*` headers report how the request was served.
+//! Both are always on when the server runs with `DEBUG` set.
+
+use std::time::Instant;
+
+use axum::{
+    body::{boxed, BoxBody, Bytes, Full, HttpBody},
+    http::{header::CONTENT_TYPE, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+
+/// Middleware indenting JSON bodies and adding the debug headers, when
+/// asked for or when `debug` is set
+pub async fn layer<B>(debug: bool, req: Request<B>, next: Next<B>) -> Response {
+    let wanted = debug || req.uri().query().is_some_and(wants_pretty);
+    if !wanted {
+        return next.run(req).await;
+    }
+
+    let start = Instant::now();
+    let res = next.run(req).await;
+    let elapsed = start.elapsed();
+
+    let is_json = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|t| t.to_str().ok())
+        .is_some_and(|t| t.starts_with("application/json"));
+
+    let (mut parts, body) = res.into_parts();
+    let (body, size) = match is_json {
+        true => match collect(body).await {
+            Ok(bytes) => {
+                let size = bytes.len();
+                (boxed(Full::from(pretty(&bytes))), Some(size))
+            }
+            Err(e) => {
+                tracing::error!("failed reading response to indent: {}", e);
+                (boxed(Full::from(Bytes::new())), None)
+            }
+        },
+        false => (body, None),
+    };
+
+    let h = &mut parts.headers;
+    h.remove(axum::http::header::CONTENT_LENGTH);
+    h.insert(
+        "x-debug-elapsed-ms",
+        HeaderValue::from_str(&format!("{:.3}", elapsed.as_secs_f64() * 1000.0)).unwrap(),
+    );
+    h.insert(
+        "x-debug-version",
+        HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
+    );
+    if let Some(size) = size {
+        h.insert("x-debug-compact-bytes", HeaderValue::from(size));
+    }
+    Response::from_parts(parts, body)
+}
+
+fn wants_pretty(query: &str) -> bool {
+    query
+        .split('&')
+        .any(|p| matches!(p, "pretty" | "pretty=true" | "pretty=1"))
+}
+
+pub async fn collect(mut body: BoxBody) -> Result<Vec<u8>, axum::Error> {
+    let mut out = vec![];
+    while let Some(chunk) = body.data().await {
+        out.extend_from_slice(&chunk?);
+    }
+    Ok(out)
+}
+
+/// Indent compact JSON like `serde_json::to_string_pretty`, keeping the
+/// order of object keys. Anything which is not valid JSON is returned
+/// unchanged.
+pub fn pretty(json: &[u8]) -> Vec<u8> {
+    if serde_json::from_slice::<serde::de::IgnoredAny>(json).is_err() {
+        return json.to_vec();
+    }
+
+    let newline = |out: &mut Vec<u8>, depth: usize| {
+        out.push(b'\n');
+        out.extend(std::iter::repeat_n(b' ', depth * 2));
+    };
+
+    let mut out = Vec::with_capacity(json.len() * 2);
+    let mut depth = 0;
+    let (mut in_string, mut escaped) = (false, false);
+    let mut bytes = json.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        if in_string {
+            out.push(b);
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+
+        match b {
+            b'"' => {
+                in_string = true;
+                out.push(b);
+            }
+            b'{' | b'[' => {
+                out.push(b);
+                // empty containers stay on one line
+                match bytes.next_if(|c| *c == b'}' || *c == b']') {
+                    Some(close) => out.push(close),
+                    None => {
+                        depth += 1;
+                        newline(&mut out, depth);
+                    }
+                }
+            }
+            b'}' | b']' => {
+                depth -= 1;
+                newline(&mut out, depth);
+                out.push(b);
+            }
+            b',' => {
+                out.push(b);
+                newline(&mut out, depth);
+            }
+            b':' => out.extend(b": "),
+            b' ' | b'\n' | b'\r' | b'\t' => (),
+            _ => out.push(b),
+        }
+    }
+    out
+}
+
+#[test]
+fn test_pretty() {
+    let pretty = |s: &str| String::from_utf8(pretty(s.as_bytes())).unwrap();
+
+    let value = serde_json::json!({
+        "literal": "亜",
+        "meanings": ["Asia", "a \"quoted\", {odd} [string]:"],
+        "empty": [],
+        "nested": { "a": {}, "b": [1, 2.5, null, true] },
+    });
+    let compact = serde_json::to_string(&value).unwrap();
+    assert_eq!(
+        pretty(&compact),
+        serde_json::to_string_pretty(&value).unwrap()
+    );
+
+    // keys keep their order rather than being sorted
+    assert_eq!(pretty(r#"{"z":1,"a":2}"#), "{\n  \"z\": 1,\n  \"a\": 2\n}");
+    assert_eq!(pretty("not json"), "not json");
+    assert_eq!(pretty("[1,"), "[1,");
+
+    assert!(wants_pretty("search=a&pretty=true"));
+    assert!(!wants_pretty("pretty=false"));
+}
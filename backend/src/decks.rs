@@ -0,0 +1,201 @@
+//! Study decks: kanji in the order of a course or by grade or frequency,
+//! split into lessons of a fixed size.
+
+use axum::{extract::Query, Extension, Json};
+use futures::TryStreamExt;
+use model::kanji::Kanji;
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    params::{Page, MAX_COUNT},
+    AppError, Database,
+};
+
+/// The most kanji in a lesson
+pub const MAX_BATCH: i64 = 100;
+
+/// The order kanji are studied in
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeckOrder {
+    /// The Kodansha Kanji Learner's Course
+    Klc,
+    /// Remembering the Kanji, 6th edition
+    Heisig,
+    /// By school grade, most frequent first within a grade
+    Grade,
+    /// Most frequent first
+    Freq,
+}
+
+impl DeckOrder {
+    /// The field kanji are ordered by. Kanji without it aren't in the deck.
+    fn field(self) -> &'static str {
+        match self {
+            DeckOrder::Klc => "dict_index.klc",
+            DeckOrder::Heisig => "dict_index.rtk",
+            DeckOrder::Grade => "info.grade",
+            DeckOrder::Freq => "info.freq",
+        }
+    }
+
+    fn sort(self) -> Document {
+        match self {
+            // missing fields sort first, so rank unranked kanji after the rest
+            DeckOrder::Grade => doc! { "info.grade": 1, "rank": 1, "literal": 1 },
+            _ => doc! { self.field(): 1, "literal": 1 },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DeckParams {
+    pub order: DeckOrder,
+    /// The kanji in each lesson, 20 by default
+    pub batch: Option<i64>,
+    /// The first lesson, counting from 0
+    pub from: Option<i64>,
+    /// How many lessons, 5 by default
+    pub count: Option<i64>,
+}
+
+impl DeckParams {
+    /// The lesson size and the lessons of the deck
+    pub fn validate(&self) -> Result<(i64, Page), AppError> {
+        let batch = self.batch.unwrap_or(20);
+        if !(1..=MAX_BATCH).contains(&batch) {
+            return Err(AppError::BadRequest(format!(
+                "batch must be between 1 and {}",
+                MAX_BATCH
+            )));
+        }
+        let page = Page::new(self.from, self.count, 5)?;
+        if page.count * batch > MAX_COUNT {
+            return Err(AppError::BadRequest(format!(
+                "a deck has at most {} kanji",
+                MAX_COUNT
+            )));
+        }
+        Ok((batch, page))
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Lesson {
+    /// The number of the lesson, counting from 1
+    pub lesson: u64,
+    pub kanji: Vec<Kanji>,
+}
+
+#[derive(Serialize)]
+pub struct Deck {
+    pub order: DeckOrder,
+    pub batch: i64,
+    pub lessons: Vec<Lesson>,
+}
+
+/// Split kanji in study order into lessons of `batch` kanji, the first
+/// numbered `first`. The last lesson may be short.
+fn lessons(kanji: Vec<Kanji>, batch: usize, first: u64) -> Vec<Lesson> {
+    let mut out: Vec<Lesson> = vec![];
+    for k in kanji {
+        match out.last_mut() {
+            Some(last) if last.kanji.len() < batch => last.kanji.push(k),
+            _ => out.push(Lesson {
+                lesson: first + out.len() as u64,
+                kanji: vec![k],
+            }),
+        }
+    }
+    out
+}
+
+/// Generate a study deck, with the full entry of each kanji
+pub async fn get_generate(
+    params: Query<DeckParams>,
+    db: Extension<Database>,
+) -> Result<Json<Deck>, AppError> {
+    let (batch, page) = params.validate()?;
+    let order = params.order;
+
+    let pipeline = [
+        doc! { "$match": { order.field(): { "$exists": true } } },
+        doc! { "$addFields": { "rank": { "$ifNull": ["$info.freq", i32::MAX] } } },
+        doc! { "$sort": order.sort() },
+        doc! { "$skip": page.from as i64 * batch },
+        doc! { "$limit": page.count * batch },
+        doc! { "$project": { "rank": 0 } },
+    ];
+    let kanji: Vec<Kanji> = db
+        .collection::<Kanji>("kanjidic")
+        .aggregate(pipeline, None)
+        .await?
+        .with_type::<Kanji>()
+        .try_collect()
+        .await?;
+
+    Ok(Json(Deck {
+        order,
+        batch,
+        lessons: lessons(kanji, batch as usize, page.from + 1),
+    }))
+}
+
+#[test]
+fn test_lessons() {
+    use model::kanji::{Info, References};
+
+    let kanji: Vec<Kanji> = "一二三四五"
+        .chars()
+        .map(|c| {
+            Kanji::builder(
+                c,
+                Info::builder(1, 1).build(),
+                References::builder("0").build(),
+            )
+            .build()
+        })
+        .collect();
+    let literals = |lessons: &[Lesson]| {
+        lessons
+            .iter()
+            .map(|l| {
+                (
+                    l.lesson,
+                    l.kanji.iter().map(|k| k.literal).collect::<String>(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        literals(&lessons(kanji.clone(), 2, 3)),
+        [(3, "一二".into()), (4, "三四".into()), (5, "五".into())]
+    );
+    assert_eq!(literals(&lessons(kanji, 5, 1)), [(1, "一二三四五".into())]);
+    assert!(lessons(vec![], 5, 1).is_empty());
+}
+
+#[test]
+fn test_validate() {
+    let params = |batch, count| DeckParams {
+        order: DeckOrder::Klc,
+        batch,
+        from: Some(2),
+        count,
+    };
+
+    assert_eq!(
+        params(None, None).validate().ok(),
+        Some((20, Page { from: 2, count: 5 }))
+    );
+    assert_eq!(
+        params(Some(100), Some(10)).validate().ok().map(|v| v.0),
+        Some(100)
+    );
+    assert!(params(Some(0), None).validate().is_err());
+    assert!(params(Some(101), None).validate().is_err());
+    assert!(params(Some(100), Some(11)).validate().is_err());
+    assert!(params(None, Some(0)).validate().is_err());
+}
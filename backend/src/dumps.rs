@@ -0,0 +1,312 @@
+//! Downloads of the dataset dumps written by populate, such as
+//! kanjidic.json. Downloads can be resumed with Range requests and are
+//! tagged with the dataset version so mirrors can tell when to refetch.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, SeekFrom},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::UNIX_EPOCH,
+};
+
+use axum::{
+    body::StreamBody,
+    extract::Path,
+    http::{
+        header::{
+            ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_NONE_MATCH,
+            IF_RANGE, RANGE,
+        },
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Serialize;
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+
+use crate::AppError;
+
+/// The dumps which can be downloaded, with their content type
+const DUMPS: &[(&str, &str)] = &[
+    ("kanjidic.json", "application/json"),
+    ("provenance.json", "application/json"),
+    ("kanjidic2.json", "application/json"),
+    ("jmdict.json", "application/json"),
+    ("kanjidic.bin", "application/octet-stream"),
+    ("kanjidic.shards", "application/octet-stream"),
+    ("kanjidic.shards.json", "application/json"),
+];
+
+pub struct Dumps {
+    dir: PathBuf,
+    /// Downloads per dump and version, if counting is enabled. Only the
+    /// totals are kept, nothing about who downloaded.
+    counts: Option<Mutex<BTreeMap<(String, String), u64>>>,
+}
+
+impl Dumps {
+    pub fn new(dir: PathBuf, count: bool) -> Self {
+        Dumps {
+            dir,
+            counts: count.then(Default::default),
+        }
+    }
+
+    fn count(&self, name: &str, version: &str) {
+        if let Some(counts) = &self.counts {
+            *counts
+                .lock()
+                .unwrap()
+                .entry((name.to_owned(), version.to_owned()))
+                .or_default() += 1;
+        }
+    }
+}
+
+/// What is known about a dump file
+#[derive(Serialize)]
+pub struct DumpInfo {
+    pub name: String,
+    /// The dataset version, written by populate next to the dump
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub etag: String,
+    pub size: u64,
+    /// Downloads of the current version, if counting is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downloads: Option<u64>,
+}
+
+/// A failure reading a dump. Missing files are not found, anything else
+/// leaves the dump unavailable.
+fn dump_error(name: &str, e: io::Error) -> AppError {
+    if e.kind() == io::ErrorKind::NotFound {
+        return AppError::EntryNotFound(format!("no dump {}", name));
+    }
+    // don't leak file paths to clients
+    tracing::error!("dump {}: {}", name, e);
+    AppError::DumpUnavailable(format!("dump {} unavailable", name))
+}
+
+/// Open a dump along with what is known about it. The size and tag are
+/// those of the opened file, even if populate replaces the dump meanwhile.
+async fn open(dumps: &Dumps, name: &str) -> Result<Option<(File, DumpInfo)>, AppError> {
+    let file = match File::open(dumps.dir.join(name)).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(dump_error(name, e)),
+    };
+    let meta = file.metadata().await.map_err(|e| dump_error(name, e))?;
+
+    let version = tokio::fs::read_to_string(dumps.dir.join(format!("{}.version", name)))
+        .await
+        .ok()
+        .map(|v| v.trim().to_owned());
+    let modified = meta
+        .modified()
+        .map_err(|e| dump_error(name, e))?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let etag = match &version {
+        Some(v) => format!("\"{}-{:x}-{:x}\"", v, meta.len(), modified),
+        None => format!("\"{:x}-{:x}\"", meta.len(), modified),
+    };
+    let downloads = dumps.counts.as_ref().map(|counts| {
+        let key = (name.to_owned(), version.clone().unwrap_or_default());
+        counts.lock().unwrap().get(&key).copied().unwrap_or(0)
+    });
+
+    let info = DumpInfo {
+        name: name.to_owned(),
+        version,
+        etag,
+        size: meta.len(),
+        downloads,
+    };
+    Ok(Some((file, info)))
+}
+
+#[derive(Serialize)]
+pub struct Meta {
+    pub dumps: Vec<DumpInfo>,
+}
+
+pub async fn get_meta(dumps: Extension<Arc<Dumps>>) -> Result<Json<Meta>, AppError> {
+    let mut out = vec![];
+    for (name, _) in DUMPS {
+        if let Some((_, info)) = open(&dumps, name).await? {
+            out.push(info);
+        }
+    }
+    Ok(Json(Meta { dumps: out }))
+}
+
+/// Whether an If-None-Match header matches the tag of a dump, comparing
+/// weakly as RFC 9110 asks
+fn none_match(tags: &str, etag: &str) -> bool {
+    let weak = |t: &str| t.trim().trim_start_matches("W/").to_owned();
+    tags.trim() == "*" || tags.split(',').any(|t| weak(t) == weak(etag))
+}
+
+/// A byte range of a file, end exclusive
+#[derive(Debug, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Parse a Range header against a file of `len` bytes. Only single
+/// ranges are supported. Returns `Err` for unsatisfiable ranges and
+/// `Ok(None)` for headers which should be ignored.
+pub fn parse_range(header: &str, len: u64) -> Result<Option<ByteRange>, ()> {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return Ok(None);
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=-500 is the last 500 bytes
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return Err(());
+            }
+            ByteRange {
+                start: len.saturating_sub(suffix),
+                end: len,
+            }
+        }
+        (Ok(start), Err(_)) if end.is_empty() => ByteRange { start, end: len },
+        (Ok(start), Ok(end)) if start <= end => ByteRange {
+            start,
+            end: end.saturating_add(1).min(len),
+        },
+        _ => return Ok(None),
+    };
+
+    match range.start < len {
+        true => Ok(Some(range)),
+        false => Err(()),
+    }
+}
+
+pub async fn get_dump(
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    dumps: Extension<Arc<Dumps>>,
+) -> Result<Response, AppError> {
+    let not_found = || AppError::EntryNotFound(format!("no dump {}", name));
+    let (_, content_type) = DUMPS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .ok_or_else(not_found)?;
+    let (mut file, info) = open(&dumps, &name).await?.ok_or_else(not_found)?;
+
+    let etag = HeaderValue::from_str(&info.etag).unwrap();
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|h: &HeaderValue| h.to_str().ok())
+    };
+
+    if header(IF_NONE_MATCH).is_some_and(|tags| none_match(tags, &info.etag)) {
+        return Ok((StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response());
+    }
+
+    // a range only applies to the version the client already has part of
+    let range = match header(IF_RANGE) {
+        Some(tag) if tag != info.etag => None,
+        _ => header(RANGE),
+    };
+    let range = match range.map(|r| parse_range(r, info.size)).transpose() {
+        Ok(range) => range.flatten(),
+        Err(()) => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(CONTENT_RANGE, format!("bytes */{}", info.size))],
+            )
+                .into_response())
+        }
+    };
+
+    // count each download once, not every resumed part of it
+    if range.as_ref().is_none_or(|r| r.start == 0) {
+        dumps.count(&name, info.version.as_deref().unwrap_or_default());
+    }
+
+    let (status, start, end) = match &range {
+        Some(r) => (StatusCode::PARTIAL_CONTENT, r.start, r.end),
+        None => (StatusCode::OK, 0, info.size),
+    };
+    file.seek(SeekFrom::Start(start))
+        .await
+        .map_err(|e| dump_error(&name, e))?;
+    let body = StreamBody::new(ReaderStream::new(file.take(end - start)));
+
+    let mut res = (status, body).into_response();
+    let h = res.headers_mut();
+    h.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    h.insert(CONTENT_LENGTH, HeaderValue::from(end - start));
+    h.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    h.insert(ETAG, etag);
+    if range.is_some() {
+        let content_range = format!("bytes {}-{}/{}", start, end - 1, info.size);
+        h.insert(
+            CONTENT_RANGE,
+            HeaderValue::from_str(&content_range).unwrap(),
+        );
+    }
+    Ok(res)
+}
+
+#[test]
+fn test_parse_range() {
+    let range = |start, end| Ok(Some(ByteRange { start, end }));
+
+    assert_eq!(parse_range("bytes=0-99", 1000), range(0, 100));
+    assert_eq!(parse_range("bytes=900-", 1000), range(900, 1000));
+    assert_eq!(parse_range("bytes=-100", 1000), range(900, 1000));
+    assert_eq!(parse_range("bytes=-2000", 1000), range(0, 1000));
+    assert_eq!(parse_range("bytes=990-2000", 1000), range(990, 1000));
+    assert_eq!(parse_range("bytes=1000-", 1000), Err(()));
+    assert_eq!(parse_range("bytes=-0", 1000), Err(()));
+    assert_eq!(parse_range("bytes=5-1", 1000), Ok(None));
+    assert_eq!(parse_range("bytes=0-1,5-9", 1000), Ok(None));
+    assert_eq!(parse_range("items=0-1", 1000), Ok(None));
+}
+
+#[test]
+fn test_none_match() {
+    let etag = "\"2024-01-10-4-0\"";
+    assert!(none_match(etag, etag));
+    assert!(none_match("\"a\", W/\"2024-01-10-4-0\"", etag));
+    assert!(none_match("*", etag));
+    assert!(!none_match("\"2024-01-09-4-0\"", etag));
+}
+
+#[test]
+fn test_dump_error() {
+    use crate::errors::ErrorCode;
+
+    let e = io::Error::from(io::ErrorKind::NotFound);
+    assert_eq!(
+        dump_error("kanjidic.json", e).code(),
+        ErrorCode::EntryNotFound
+    );
+    let e = io::Error::from(io::ErrorKind::PermissionDenied);
+    assert_eq!(
+        dump_error("kanjidic.json", e).code(),
+        ErrorCode::DumpUnavailable
+    );
+}
@@ -0,0 +1,229 @@
+//! The error codes returned by the API and how error responses are
+//! rendered.
+//!
+//! Every error response carries its code in the `x-error-code` header.
+//! Depending on the configured [`Envelope`] the body is either the plain
+//! message or a JSON object like:
+//!
+//! ```json
+//! { "code": "KANJI_NOT_FOUND", "message": "no kanji loaded" }
+//! ```
+
+use axum::{
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        Request, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+/// A stable code identifying the kind of error, for clients to branch on
+/// instead of parsing messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// A path or query parameter is missing or out of range
+    InvalidQuery,
+    /// The request lacks valid credentials for an admin endpoint
+    Unauthorized,
+    /// No kanji matches the request
+    KanjiNotFound,
+    /// No dictionary entry matches the request
+    EntryNotFound,
+    /// The request conflicts with work in progress, e.g. a running job
+    Conflict,
+    /// The client made too many requests and should retry later
+    RateLimited,
+    /// The read-only kanji store could not be read
+    StoreUnavailable,
+    /// The configured search weights are missing or invalid
+    InvalidWeights,
+    /// A dump file exists but could not be read
+    DumpUnavailable,
+    /// A populate job could not be run
+    JobFailed,
+    /// The database failed
+    DatabaseError,
+    /// A service the API depends on, such as the database, could not be
+    /// reached
+    UpstreamError,
+    /// Data could not be converted to or from JSON
+    SerializationError,
+    /// Anything else
+    InternalError,
+}
+
+/// The status and default message of every error code
+const REGISTRY: &[(ErrorCode, &str, StatusCode, &str)] = &[
+    (
+        ErrorCode::InvalidQuery,
+        "INVALID_QUERY",
+        StatusCode::BAD_REQUEST,
+        "invalid request",
+    ),
+    (
+        ErrorCode::Unauthorized,
+        "UNAUTHORIZED",
+        StatusCode::UNAUTHORIZED,
+        "unauthorized",
+    ),
+    (
+        ErrorCode::KanjiNotFound,
+        "KANJI_NOT_FOUND",
+        StatusCode::NOT_FOUND,
+        "kanji not found",
+    ),
+    (
+        ErrorCode::EntryNotFound,
+        "ENTRY_NOT_FOUND",
+        StatusCode::NOT_FOUND,
+        "entry not found",
+    ),
+    (
+        ErrorCode::Conflict,
+        "CONFLICT",
+        StatusCode::CONFLICT,
+        "conflict",
+    ),
+    (
+        ErrorCode::RateLimited,
+        "RATE_LIMITED",
+        StatusCode::TOO_MANY_REQUESTS,
+        "too many requests",
+    ),
+    (
+        ErrorCode::StoreUnavailable,
+        "STORE_UNAVAILABLE",
+        StatusCode::SERVICE_UNAVAILABLE,
+        "kanji store unavailable",
+    ),
+    (
+        ErrorCode::InvalidWeights,
+        "INVALID_WEIGHTS",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "invalid search weights",
+    ),
+    (
+        ErrorCode::DumpUnavailable,
+        "DUMP_UNAVAILABLE",
+        StatusCode::SERVICE_UNAVAILABLE,
+        "dump unavailable",
+    ),
+    (
+        ErrorCode::JobFailed,
+        "JOB_FAILED",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "populate job failed",
+    ),
+    (
+        ErrorCode::DatabaseError,
+        "DATABASE_ERROR",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "database error",
+    ),
+    (
+        ErrorCode::UpstreamError,
+        "UPSTREAM_ERROR",
+        StatusCode::BAD_GATEWAY,
+        "upstream service unavailable",
+    ),
+    (
+        ErrorCode::SerializationError,
+        "SERIALIZATION_ERROR",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "serialization error",
+    ),
+    (
+        ErrorCode::InternalError,
+        "INTERNAL_ERROR",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "internal error",
+    ),
+];
+
+impl ErrorCode {
+    fn entry(self) -> &'static (ErrorCode, &'static str, StatusCode, &'static str) {
+        REGISTRY
+            .iter()
+            .find(|(c, ..)| *c == self)
+            .expect("error code missing from registry")
+    }
+
+    /// The code as sent to clients
+    pub fn as_str(self) -> &'static str {
+        self.entry().1
+    }
+
+    pub fn status(self) -> StatusCode {
+        self.entry().2
+    }
+
+    /// The message used when there is no more specific one
+    pub fn default_message(self) -> &'static str {
+        self.entry().3
+    }
+}
+
+/// The code and message of an error response, attached as a response
+/// extension so later layers can localize or re-render it
+#[derive(Clone, Debug, Serialize)]
+pub struct ErrorInfo {
+    pub code: ErrorCode,
+    pub message: String,
+    /// More for clients to act on, such as suggested kanji, rendered
+    /// next to the code and message in the JSON envelope
+    #[serde(flatten)]
+    pub data: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// How the body of an error response is rendered
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Envelope {
+    /// Just the message as plain text
+    Text,
+    /// A JSON object with the code and message
+    Json,
+}
+
+impl Envelope {
+    /// Parse the `ERROR_ENVELOPE` setting, defaulting to plain text
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "json" => Envelope::Json,
+            _ => Envelope::Text,
+        }
+    }
+}
+
+/// Middleware rendering error responses in the configured envelope
+pub async fn envelope<B>(envelope: Envelope, req: Request<B>, next: Next<B>) -> Response {
+    let mut res = next.run(req).await;
+    if envelope == Envelope::Text {
+        return res;
+    }
+
+    let info = match res.extensions().get::<ErrorInfo>() {
+        Some(info) => info.clone(),
+        None => return res,
+    };
+
+    let mut headers = std::mem::take(res.headers_mut());
+    headers.remove(CONTENT_LENGTH);
+    headers.remove(CONTENT_TYPE);
+    let mut out = (res.status(), headers, Json(&info)).into_response();
+    out.extensions_mut().insert(info);
+    out
+}
+
+#[test]
+fn test_registry() {
+    for (code, name, ..) in REGISTRY {
+        assert_eq!(serde_json::to_value(code).unwrap(), *name);
+        assert!(code.status().is_client_error() || code.status().is_server_error());
+    }
+    assert_eq!(ErrorCode::KanjiNotFound.status(), StatusCode::NOT_FOUND);
+    assert_eq!(ErrorCode::InvalidQuery.as_str(), "INVALID_QUERY");
+}
@@ -0,0 +1,99 @@
+//! Example sentences from the Tanaka Corpus, as populated by `populate
+//! examples`, looked up by a kanji they use or a word they are indexed by.
+
+use axum::{extract::Query, Extension};
+use model::{example::Example, kana::is_kanji};
+use mongodb::{
+    bson::{doc, Document},
+    options::FindOptions,
+    Cursor,
+};
+use serde::Deserialize;
+
+use crate::{
+    normalize::normalize,
+    params::{self, Page},
+    stream::JsonArray,
+    AppError, Database,
+};
+
+#[derive(Deserialize)]
+pub struct ExamplesParams {
+    pub kanji: Option<String>,
+    pub word: Option<String>,
+    pub from: Option<i64>,
+    pub count: Option<i64>,
+}
+
+impl ExamplesParams {
+    /// The filter for the sentences using the kanji or indexed by the
+    /// word, one of which must be given
+    pub fn filter(&self) -> Result<Document, AppError> {
+        match (&self.kanji, &self.word) {
+            (Some(kanji), None) => {
+                let kanji = normalize(kanji);
+                let mut chars = kanji.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if is_kanji(c) => Ok(doc! { "kanji": c.to_string() }),
+                    _ => Err(AppError::BadRequest(format!("{:?} is not a kanji", kanji))),
+                }
+            }
+            (None, Some(word)) => Ok(doc! { "words.headword": params::search(word)? }),
+            _ => Err(AppError::BadRequest(
+                "exactly one of kanji and word is required".to_owned(),
+            )),
+        }
+    }
+}
+
+pub async fn get_examples(
+    params: Query<ExamplesParams>,
+    db: Extension<Database>,
+) -> Result<JsonArray<Cursor<Example>>, AppError> {
+    let filter = params.filter()?;
+    let page = Page::new(params.from, params.count, 10)?;
+
+    let options = FindOptions::builder()
+        .sort(doc! { "id": 1 })
+        .skip(page.from)
+        .limit(page.count)
+        .build();
+    let cursor = db
+        .collection::<Example>("examples")
+        .find(filter, options)
+        .await?;
+    Ok(JsonArray(cursor))
+}
+
+#[test]
+fn test_filter() {
+    let params = |kanji: Option<&str>, word: Option<&str>| ExamplesParams {
+        kanji: kanji.map(str::to_owned),
+        word: word.map(str::to_owned),
+        from: None,
+        count: None,
+    };
+
+    assert_eq!(
+        params(Some("漢"), None).filter().ok(),
+        Some(doc! { "kanji": "漢" })
+    );
+    assert_eq!(
+        params(None, Some("勉強")).filter().ok(),
+        Some(doc! { "words.headword": "勉強" })
+    );
+    for (kanji, word) in [
+        (None, None),
+        (Some("漢"), Some("勉強")),
+        (Some("漢字"), None),
+        (Some("か"), None),
+        (None, Some(" ")),
+    ] {
+        assert!(
+            params(kanji, word).filter().is_err(),
+            "{:?} {:?}",
+            kanji,
+            word
+        );
+    }
+}
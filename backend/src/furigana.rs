@@ -0,0 +1,250 @@
+//! Furigana for a phrase, from the readings of the JMdict words found in
+//! it. Words are matched greedily, longest first, so conjugated forms
+//! are only read as far as their stem matches a word.
+
+use std::collections::HashMap;
+
+use axum::{extract::Query, Extension, Json};
+use futures::TryStreamExt;
+use model::{
+    entry::{priority_rank, Entry},
+    kana::{self, is_kanji},
+};
+use mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+
+use crate::{params, AppError, Database};
+
+/// The longest word looked up, in characters
+const MAX_WORD_LEN: usize = 12;
+
+#[derive(Deserialize)]
+pub struct FuriganaParams {
+    pub text: String,
+}
+
+/// A part of the phrase, with a reading if it is written with kanji
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Segment {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reading: Option<String>,
+}
+
+impl Segment {
+    fn new(text: &str, reading: Option<&str>) -> Self {
+        Segment {
+            text: text.to_owned(),
+            reading: reading.map(str::to_owned),
+        }
+    }
+}
+
+/// Every part of the text which could be a word written with kanji
+fn candidates(chars: &[char]) -> Vec<String> {
+    let mut out = vec![];
+    for start in 0..chars.len() {
+        for end in start + 1..=chars.len().min(start + MAX_WORD_LEN) {
+            let word = &chars[start..end];
+            if word.iter().any(|c| is_kanji(*c)) {
+                out.push(word.iter().collect());
+            }
+        }
+    }
+    out
+}
+
+/// The reading of each written form of the entries, from the most common
+/// entry writing it
+fn readings(entries: &[Entry]) -> HashMap<String, String> {
+    let mut best = HashMap::<&str, (u32, &str)>::new();
+    for e in entries {
+        for k in e.kanji() {
+            let Some(reading) = e.reading_of(&k.text) else {
+                continue;
+            };
+            let rank = priority_rank(&k.priority).unwrap_or(u32::MAX);
+            match best.get(k.text.as_str()) {
+                Some((best, _)) if *best <= rank => (),
+                _ => {
+                    best.insert(&k.text, (rank, &reading.text));
+                }
+            }
+        }
+    }
+    best.into_iter()
+        .map(|(text, (_, reading))| (text.to_owned(), reading.to_owned()))
+        .collect()
+}
+
+/// Add a segment, joining consecutive segments without readings
+fn push(out: &mut Vec<Segment>, segment: Segment) {
+    match out.last_mut() {
+        Some(last) if last.reading.is_none() && segment.reading.is_none() => {
+            last.text.push_str(&segment.text)
+        }
+        _ => out.push(segment),
+    }
+}
+
+/// Split the kana a word starts or ends with, such as the okurigana of
+/// 食べる, from its reading so the reading only covers the kanji
+fn split_okurigana(out: &mut Vec<Segment>, word: &str, reading: &str) {
+    let word: Vec<char> = word.chars().collect();
+    let reading: Vec<char> = reading.chars().collect();
+    let same = |a: &char, b: &char| {
+        kana::is_kana(*a) && kana::to_hiragana(&a.to_string()) == kana::to_hiragana(&b.to_string())
+    };
+
+    let prefix = word
+        .iter()
+        .zip(&reading)
+        .take_while(|(a, b)| same(a, b))
+        .count();
+    let suffix = word[prefix..]
+        .iter()
+        .rev()
+        .zip(reading[prefix..].iter().rev())
+        .take_while(|(a, b)| same(a, b))
+        .count();
+
+    let text = |chars: &[char]| chars.iter().collect::<String>();
+    let core = &word[prefix..word.len() - suffix];
+    let core_reading = &reading[prefix..reading.len() - suffix];
+    if core.is_empty() || core_reading.is_empty() {
+        return push(out, Segment::new(&text(&word), Some(&text(&reading))));
+    }
+
+    if prefix > 0 {
+        push(out, Segment::new(&text(&word[..prefix]), None));
+    }
+    push(out, Segment::new(&text(core), Some(&text(core_reading))));
+    if suffix > 0 {
+        push(out, Segment::new(&text(&word[word.len() - suffix..]), None));
+    }
+}
+
+/// Split text into words with their readings, taking the longest word
+/// at each position. Kanji outside any known word have no reading.
+fn segment(text: &str, readings: &HashMap<String, String>) -> Vec<Segment> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let longest = (1..=(chars.len() - i).min(MAX_WORD_LEN))
+            .rev()
+            .map(|len| (len, chars[i..i + len].iter().collect::<String>()))
+            .find_map(|(len, word)| Some((len, readings.get(&word)?, word)));
+        match longest {
+            Some((len, reading, word)) => {
+                split_okurigana(&mut out, &word, reading);
+                i += len;
+            }
+            None => {
+                push(&mut out, Segment::new(&chars[i].to_string(), None));
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+pub async fn get_furigana(
+    params: Query<FuriganaParams>,
+    db: Extension<Database>,
+) -> Result<Json<Vec<Segment>>, AppError> {
+    let text = params::search(&params.text)?;
+    let chars: Vec<char> = text.chars().collect();
+
+    let entries: Vec<Entry> = db
+        .collection::<Entry>("jmdict")
+        .find(doc! { "kanji.text": { "$in": candidates(&chars) } }, None)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(Json(segment(&text, &readings(&entries))))
+}
+
+#[test]
+fn test_segment() {
+    let readings: HashMap<String, String> = [
+        ("日本", "にほん"),
+        ("日本語", "にほんご"),
+        ("勉強", "べんきょう"),
+        ("食べる", "たべる"),
+        ("お茶", "おちゃ"),
+    ]
+    .iter()
+    .map(|(k, r)| (k.to_string(), r.to_string()))
+    .collect();
+
+    let segments = |text| {
+        segment(text, &readings)
+            .into_iter()
+            .map(|s| (s.text, s.reading.unwrap_or_default()))
+            .collect::<Vec<_>>()
+    };
+    let pairs = |pairs: &[(&str, &str)]| {
+        pairs
+            .iter()
+            .map(|(t, r)| (t.to_string(), r.to_string()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        segments("日本語を勉強する"),
+        pairs(&[
+            ("日本語", "にほんご"),
+            ("を", ""),
+            ("勉強", "べんきょう"),
+            ("する", "")
+        ])
+    );
+    assert_eq!(
+        segments("お茶を食べる"),
+        pairs(&[
+            ("お", ""),
+            ("茶", "ちゃ"),
+            ("を", ""),
+            ("食", "た"),
+            ("べる", "")
+        ])
+    );
+    // unknown kanji are left without a reading
+    assert_eq!(
+        segments("𠮟る日本"),
+        pairs(&[("𠮟る", ""), ("日本", "にほん")])
+    );
+}
+
+#[test]
+fn test_readings() {
+    use model::entry::{Kanji, Reading};
+
+    let entry = |seq, text: &str, reading: &str, priority: &[&str]| {
+        Entry::builder(seq)
+            .kanji(vec![Kanji {
+                text: text.into(),
+                info: vec![],
+                priority: priority.iter().map(|p| p.to_string()).collect(),
+            }])
+            .readings(vec![Reading {
+                text: reading.into(),
+                no_kanji: false,
+                restrictions: vec![],
+                info: vec![],
+                priority: vec![],
+            }])
+            .build()
+    };
+    let entries = [
+        entry(1, "日", "か", &[]),
+        entry(2, "日", "ひ", &["ichi1", "nf02"]),
+        entry(3, "日", "にち", &["news1"]),
+    ];
+    assert_eq!(readings(&entries)["日"], "ひ");
+
+    let chars: Vec<char> = "お茶a".chars().collect();
+    assert_eq!(candidates(&chars), ["お茶", "お茶a", "茶", "茶a"]);
+}
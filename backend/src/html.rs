@@ -0,0 +1,56 @@
+//! A minimal template engine for the HTML views. Templates contain
+//! `{{name}}` placeholders which are replaced with escaped values;
+//! placeholders without a value are left empty.
+
+/// Escape text for use in HTML content and attribute values
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Fill in the placeholders of a template
+pub fn render(template: &str, vars: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => {
+                // not a placeholder, keep it as is
+                out.push_str(&rest[start..]);
+                return out;
+            }
+        };
+
+        let name = after[..end].trim();
+        if let Some((_, value)) = vars.iter().find(|(n, _)| *n == name) {
+            out.push_str(&escape(value));
+        }
+        rest = &after[end + 2..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[test]
+fn test_render() {
+    let vars = [("name", "<b>\"水\"</b>".to_owned())];
+    assert_eq!(
+        render("<p title=\"{{name}}\">{{ name }}{{missing}}</p>{{", &vars),
+        "<p title=\"&lt;b&gt;&quot;水&quot;&lt;/b&gt;\">&lt;b&gt;&quot;水&quot;&lt;/b&gt;</p>{{"
+    );
+}
@@ -0,0 +1,183 @@
+use axum::{
+    http::{
+        header::{ACCEPT_LANGUAGE, CONTENT_LENGTH},
+        Request,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::errors::{ErrorCode, ErrorInfo};
+
+/// Languages error messages are available in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lang {
+    En,
+    Ja,
+}
+
+/// Translated messages keyed by error code. English is not listed since
+/// the original message is already in English.
+const MESSAGES: &[(ErrorCode, Lang, &str)] = &[
+    (
+        ErrorCode::InvalidQuery,
+        Lang::Ja,
+        "リクエストが正しくありません",
+    ),
+    (
+        ErrorCode::KanjiNotFound,
+        Lang::Ja,
+        "漢字が見つかりませんでした",
+    ),
+    (ErrorCode::EntryNotFound, Lang::Ja, "見つかりませんでした"),
+    (ErrorCode::Conflict, Lang::Ja, "処理中のため実行できません"),
+    (ErrorCode::RateLimited, Lang::Ja, "リクエストが多すぎます"),
+    (
+        ErrorCode::StoreUnavailable,
+        Lang::Ja,
+        "漢字データを読み込めませんでした",
+    ),
+    (
+        ErrorCode::InvalidWeights,
+        Lang::Ja,
+        "検索の重みの設定が正しくありません",
+    ),
+    (
+        ErrorCode::DumpUnavailable,
+        Lang::Ja,
+        "ダンプを読み込めませんでした",
+    ),
+    (
+        ErrorCode::JobFailed,
+        Lang::Ja,
+        "データ更新を実行できませんでした",
+    ),
+    (
+        ErrorCode::InternalError,
+        Lang::Ja,
+        "内部エラーが発生しました",
+    ),
+    (
+        ErrorCode::DatabaseError,
+        Lang::Ja,
+        "データベースエラーが発生しました",
+    ),
+    (
+        ErrorCode::UpstreamError,
+        Lang::Ja,
+        "外部サービスに接続できませんでした",
+    ),
+    (
+        ErrorCode::SerializationError,
+        Lang::Ja,
+        "データの変換に失敗しました",
+    ),
+];
+
+/// Look up the translated message for an error code
+pub fn translate(code: ErrorCode, lang: Lang) -> Option<&'static str> {
+    MESSAGES
+        .iter()
+        .find(|(c, l, _)| *c == code && *l == lang)
+        .map(|(_, _, m)| *m)
+}
+
+/// The message of an error in a language, with the detail of the original
+/// message, such as which parameter was invalid, kept after the
+/// translation
+pub fn localize_message(info: &ErrorInfo, lang: Lang) -> Option<String> {
+    let message = translate(info.code, lang)?;
+    match info.message == info.code.default_message() {
+        true => Some(message.to_owned()),
+        false => Some(format!("{}: {}", message, info.message)),
+    }
+}
+
+/// Pick the supported language with the highest weight from an
+/// Accept-Language header value, defaulting to English.
+pub fn negotiate(header: &str) -> Lang {
+    let mut best = (Lang::En, 0.0);
+
+    for item in header.split(',') {
+        let mut parts = item.split(';').map(|p| p.trim());
+        let tag = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let q = parts
+            .find_map(|p| p.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        let lang = match tag.split('-').next() {
+            // any language, so the default
+            Some("en" | "*") => Lang::En,
+            Some("ja") => Lang::Ja,
+            _ => continue,
+        };
+        if q > best.1 {
+            best = (lang, q);
+        }
+    }
+
+    best.0
+}
+
+/// Middleware replacing the body of error responses with a message in the
+/// language requested by the client
+pub async fn localize<B>(req: Request<B>, next: Next<B>) -> Response {
+    let lang = req
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|h| h.to_str().ok())
+        .map(negotiate)
+        .unwrap_or(Lang::En);
+
+    let mut res = next.run(req).await;
+
+    let info = res.extensions().get::<ErrorInfo>().and_then(|info| {
+        Some(ErrorInfo {
+            code: info.code,
+            message: localize_message(info, lang)?,
+            data: info.data.clone(),
+        })
+    });
+
+    match info {
+        Some(info) => {
+            let mut headers = std::mem::take(res.headers_mut());
+            headers.remove(CONTENT_LENGTH);
+            let mut out = (res.status(), headers, info.message.clone()).into_response();
+            out.extensions_mut().insert(info);
+            out
+        }
+        None => res,
+    }
+}
+
+#[test]
+fn test_negotiate() {
+    assert_eq!(negotiate("ja-JP,ja;q=0.9,en;q=0.8"), Lang::Ja);
+    assert_eq!(negotiate("en-US,en;q=0.9,ja;q=0.8"), Lang::En);
+    assert_eq!(negotiate("fr-FR,ja;q=0.5"), Lang::Ja);
+    assert_eq!(negotiate("fr-FR"), Lang::En);
+    assert_eq!(negotiate("*"), Lang::En);
+    assert_eq!(negotiate("ja;q=0.5, *"), Lang::En);
+    assert_eq!(negotiate("ja, *;q=0.5"), Lang::Ja);
+}
+
+#[test]
+fn test_localize_message() {
+    let info = |message: &str| ErrorInfo {
+        code: ErrorCode::InvalidQuery,
+        message: message.into(),
+        data: None,
+    };
+
+    assert_eq!(
+        localize_message(&info("invalid from -1"), Lang::Ja).as_deref(),
+        Some("リクエストが正しくありません: invalid from -1")
+    );
+    assert_eq!(
+        localize_message(&info(ErrorCode::InvalidQuery.default_message()), Lang::Ja).as_deref(),
+        Some("リクエストが正しくありません")
+    );
+    assert_eq!(localize_message(&info("invalid from -1"), Lang::En), None);
+}
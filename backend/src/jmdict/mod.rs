@@ -0,0 +1,213 @@
+use axum::{
+    extract::{Path, Query},
+    http::HeaderMap,
+    response::IntoResponse,
+    Extension, Json,
+};
+use futures::TryStreamExt;
+use model::{
+    entry::Entry,
+    gloss::{self, Gloss},
+    kana::Script,
+};
+use mongodb::{
+    bson::{doc, Document},
+    options::FindOptions,
+};
+use serde::Deserialize;
+
+use crate::{
+    admin::{AdminToken, DebugMode},
+    batch,
+    params::{self, Page},
+    permalink::Permalink,
+    raw,
+    relevance::escape_regex,
+    AppError, Database,
+};
+
+/// The most glosses a search will match entries by
+const MAX_GLOSS_MATCHES: i64 = 1000;
+
+#[derive(Deserialize)]
+pub struct EntryParams {
+    /// Include the entry as parsed from JMdict, in debug mode or for
+    /// admins
+    #[serde(default)]
+    pub include_raw: bool,
+}
+
+pub async fn get_entry(
+    Path(seq): Path<u32>,
+    params: Query<EntryParams>,
+    headers: HeaderMap,
+    db: Extension<Database>,
+    debug: Extension<DebugMode>,
+    token: Option<Extension<AdminToken>>,
+) -> Result<impl IntoResponse, AppError> {
+    let found = db
+        .collection::<Entry>("jmdict")
+        .find_one(doc! { "ent_seq": seq }, None)
+        .await?;
+
+    match found {
+        Some(e) => {
+            let raw = match params.include_raw {
+                true => {
+                    let filter = doc! { "ent_seq": seq };
+                    Some(raw::find(&headers, *debug, token, &db, "jmdict_raw", filter).await?)
+                }
+                false => None,
+            };
+            let entry = expand_one(&db, e).await?;
+            Ok((Permalink::Word(seq).header(), raw::json(entry, raw)))
+        }
+        None => Err(AppError::EntryNotFound(format!("no entry {}", seq))),
+    }
+}
+
+pub async fn get_random(db: Extension<Database>) -> Result<Json<Entry>, AppError> {
+    let mut cursor = db
+        .collection::<Entry>("jmdict")
+        .aggregate([doc! { "$sample": { "size": 1 } }], None)
+        .await?
+        .with_type::<Entry>();
+
+    match cursor.try_next().await? {
+        Some(e) => Ok(Json(expand_one(&db, e).await?)),
+        None => Err(AppError::EntryNotFound("no entries loaded".into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    pub q: String,
+    /// Only entries whose headword is in this script
+    pub script: Option<Script>,
+    pub from: Option<i64>,
+    pub count: Option<i64>,
+}
+
+impl SearchParams {
+    /// The normalized search and the page of results
+    pub fn validate(&self) -> Result<(String, Page), AppError> {
+        Ok((
+            params::search(&self.q)?,
+            Page::new(self.from, self.count, 10)?,
+        ))
+    }
+}
+
+/// Search entries by the start of a written form or reading, or by an
+/// English gloss
+pub async fn get_search(
+    params: Query<SearchParams>,
+    db: Extension<Database>,
+) -> Result<Json<Vec<Entry>>, AppError> {
+    let (q, page) = params.validate()?;
+
+    // glosses are stored once in their own collection, so find the keys
+    // of the matching ones first
+    let options = FindOptions::builder().limit(MAX_GLOSS_MATCHES).build();
+    let keys: Vec<String> = db
+        .collection::<Gloss>("glosses")
+        .find(gloss_filter(&q), options)
+        .await?
+        .map_ok(|g| g.key)
+        .try_collect()
+        .await?;
+
+    let options = FindOptions::builder()
+        .sort(doc! { "ent_seq": 1 })
+        .skip(page.from)
+        .limit(page.count)
+        .build();
+    let mut found: Vec<Entry> = db
+        .collection::<Entry>("jmdict")
+        .find(search_filter(&q, keys, params.script), options)
+        .await?
+        .try_collect()
+        .await?;
+    expand_glosses(&db, &mut found).await?;
+
+    Ok(Json(found))
+}
+
+/// Glosses equal to the search, ignoring case and a leading "to " so
+/// verbs are found by their bare form
+fn gloss_filter(q: &str) -> Document {
+    let regex = format!("^(to )?{}$", escape_regex(q));
+    doc! { "text": { "$regex": regex, "$options": "i" } }
+}
+
+fn search_filter(q: &str, gloss_keys: Vec<String>, script: Option<Script>) -> Document {
+    let prefix = format!("^{}", escape_regex(q));
+    let mut filter = doc! { "$or": [
+        { "kanji.text": { "$regex": &prefix } },
+        { "readings.text": { "$regex": &prefix } },
+        { "senses.gloss_keys": { "$in": gloss_keys } },
+    ]};
+    if let Some(script) = script {
+        filter.insert("script", mongodb::bson::to_bson(&script).unwrap());
+    }
+    filter
+}
+
+pub async fn post_batch(
+    db: Extension<Database>,
+    Json(seqs): Json<Vec<u32>>,
+) -> Result<Json<Vec<Option<Entry>>>, AppError> {
+    batch::validate(&seqs)?;
+
+    let mut found: Vec<Entry> = db
+        .collection::<Entry>("jmdict")
+        .find(doc! { "ent_seq": { "$in": &seqs } }, None)
+        .await?
+        .try_collect()
+        .await?;
+    expand_glosses(&db, &mut found).await?;
+
+    Ok(Json(batch::in_order(&seqs, found, |e| e.ent_seq)))
+}
+
+async fn expand_one(db: &Database, entry: Entry) -> Result<Entry, AppError> {
+    let mut entries = [entry];
+    expand_glosses(db, &mut entries).await?;
+    let [entry] = entries;
+    Ok(entry)
+}
+
+/// Replace the gloss keys of stored entries with the glosses themselves
+async fn expand_glosses(db: &Database, entries: &mut [Entry]) -> Result<(), AppError> {
+    let keys = gloss::keys(entries);
+    if keys.is_empty() {
+        return Ok(());
+    }
+
+    let table: Vec<Gloss> = db
+        .collection::<Gloss>("glosses")
+        .find(doc! { "key": { "$in": keys } }, None)
+        .await?
+        .try_collect()
+        .await?;
+    gloss::expand(entries, &table);
+
+    Ok(())
+}
+
+#[test]
+fn test_search_filter() {
+    let filter = search_filter("a.b", vec!["k".into()], None);
+    assert!(!filter.contains_key("script"));
+    let or = filter.get_array("$or").unwrap();
+    let regex = or[0].as_document().unwrap().get_document("kanji.text");
+    assert_eq!(regex.unwrap().get_str("$regex"), Ok("^a\\.b"));
+
+    let filter = search_filter("a", vec![], Some(Script::Katakana));
+    assert_eq!(filter.get_str("script"), Ok("katakana"));
+
+    let filter = gloss_filter("eat");
+    let text = filter.get_document("text").unwrap();
+    assert_eq!(text.get_str("$regex"), Ok("^(to )?eat$"));
+    assert_eq!(text.get_str("$options"), Ok("i"));
+}
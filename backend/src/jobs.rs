@@ -0,0 +1,271 @@
+//! A scheduler running the repopulate command on an interval, so data
+//! refreshes need no outside cron. Every run is recorded in the "meta"
+//! collection and can be inspected or triggered through admin endpoints.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{http::HeaderMap, Extension, Json};
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, DateTime},
+    options::FindOptions,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    admin::{self, AdminToken},
+    cache::Caching,
+    AppError, Database,
+};
+
+/// The kind of meta document jobs are recorded as
+const KIND: &str = "populate_job";
+
+/// How much of the end of the output of a job is kept
+const MAX_OUTPUT: usize = 4096;
+
+/// A run of the repopulate command
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Job {
+    pub kind: String,
+    /// "schedule" or "manual"
+    pub trigger: String,
+    /// RFC 3339 timestamps
+    pub started: String,
+    pub finished: String,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    /// The end of the combined stdout and stderr of the command
+    pub output: String,
+}
+
+pub struct Scheduler {
+    /// Run with `sh -c`, e.g. `./fetch.sh && populate mongo`
+    command: String,
+    db: Database,
+    /// Busted after every successful run
+    cache: Caching,
+    running: AtomicBool,
+}
+
+/// Parse an interval like 90s, 30m, 6h or 1d
+pub fn parse_interval(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let unit = s.chars().last()?;
+    let n: u64 = s[..s.len() - unit.len_utf8()].parse().ok()?;
+    let secs = match unit {
+        's' => Some(n),
+        'm' => n.checked_mul(60),
+        'h' => n.checked_mul(60 * 60),
+        'd' => n.checked_mul(60 * 60 * 24),
+        _ => return None,
+    }?;
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// A failure to run the command at all, as opposed to it failing
+fn job_error(e: std::io::Error) -> AppError {
+    tracing::error!("populate job failed to start: {}", e);
+    AppError::JobFailed("the populate command could not be run".into())
+}
+
+/// Clears the running flag when a job ends, however it ends
+struct Running<'a>(&'a AtomicBool);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+fn now() -> String {
+    DateTime::now().try_to_rfc3339_string().unwrap_or_default()
+}
+
+/// Keep the end of a string, on a character boundary
+fn tail(s: &str, max: usize) -> &str {
+    let mut start = s.len().saturating_sub(max);
+    while !s.is_char_boundary(start) {
+        start += 1;
+    }
+    &s[start..]
+}
+
+impl Scheduler {
+    pub fn new(command: String, db: Database, cache: Caching) -> Self {
+        Scheduler {
+            command,
+            db,
+            cache,
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Run the command every `interval` in the background
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(interval);
+            // the first tick completes immediately, don't run on startup
+            timer.tick().await;
+            loop {
+                timer.tick().await;
+                if let Err(e) = self.clone().run("schedule").await {
+                    tracing::error!("scheduled populate failed to run: {}", e.code().as_str());
+                }
+            }
+        });
+    }
+
+    /// Run the command once, unless it is already running. The job runs
+    /// in its own task, so it is seen through and recorded even if the
+    /// caller stops waiting for it.
+    pub async fn run(self: Arc<Self>, trigger: &str) -> Result<Job, AppError> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err(AppError::Conflict(
+                "a populate job is already running".into(),
+            ));
+        }
+        let trigger = trigger.to_owned();
+        tokio::spawn(async move { self.finish(&trigger).await })
+            .await
+            .map_err(|e| {
+                tracing::error!("populate job panicked: {}", e);
+                AppError::JobFailed("the populate job did not finish".into())
+            })?
+    }
+
+    /// Run the command, then bust the cache and record the job
+    async fn finish(&self, trigger: &str) -> Result<Job, AppError> {
+        let job = {
+            let _running = Running(&self.running);
+            self.execute(trigger).await?
+        };
+        if let (true, Some(cache)) = (job.success, &self.cache) {
+            if let Err(e) = cache.bust().await {
+                tracing::error!("failed to bust the cache after populate: {}", e);
+            }
+        }
+        self.db
+            .collection::<Job>("meta")
+            .insert_one(&job, None)
+            .await?;
+        Ok(job)
+    }
+
+    async fn execute(&self, trigger: &str) -> Result<Job, AppError> {
+        tracing::info!("running populate job: {}", self.command);
+        let started = now();
+        let out = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .output()
+            .await
+            .map_err(job_error)?;
+
+        let output = [out.stdout, out.stderr].concat();
+        let output = String::from_utf8_lossy(&output);
+        Ok(Job {
+            kind: KIND.into(),
+            trigger: trigger.into(),
+            started,
+            finished: now(),
+            success: out.status.success(),
+            exit_code: out.status.code(),
+            output: tail(&output, MAX_OUTPUT).into(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct Status {
+    pub running: bool,
+    /// The latest jobs, newest first
+    pub history: Vec<Job>,
+}
+
+pub async fn get_jobs(
+    headers: HeaderMap,
+    token: Extension<AdminToken>,
+    scheduler: Extension<Arc<Scheduler>>,
+) -> Result<Json<Status>, AppError> {
+    admin::authorize(&headers, &token)?;
+
+    let options = FindOptions::builder()
+        .sort(doc! { "started": -1 })
+        .limit(20)
+        .build();
+    let history = scheduler
+        .db
+        .collection::<Job>("meta")
+        .find(doc! { "kind": KIND }, options)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(Json(Status {
+        running: scheduler.running.load(Ordering::SeqCst),
+        history,
+    }))
+}
+
+/// Run the job now and wait for it to finish
+pub async fn post_run(
+    headers: HeaderMap,
+    token: Extension<AdminToken>,
+    scheduler: Extension<Arc<Scheduler>>,
+) -> Result<Json<Job>, AppError> {
+    admin::authorize(&headers, &token)?;
+    Ok(Json(scheduler.0.run("manual").await?))
+}
+
+#[test]
+fn test_job_error() {
+    let e = job_error(std::io::ErrorKind::NotFound.into());
+    assert_eq!(e.code(), crate::errors::ErrorCode::JobFailed);
+}
+
+#[test]
+fn test_parse_interval() {
+    assert_eq!(parse_interval("90s"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_interval("6h"), Some(Duration::from_secs(6 * 3600)));
+    assert_eq!(parse_interval("1d"), Some(Duration::from_secs(86400)));
+    assert_eq!(parse_interval("0m"), None);
+    assert_eq!(parse_interval("h"), None);
+    assert_eq!(parse_interval("6 hours"), None);
+    assert_eq!(parse_interval("18446744073709551615d"), None);
+    assert_eq!(tail("あいう", 4), "う");
+}
+
+#[tokio::test]
+async fn test_dropped_run() {
+    // fail fast, nothing listens on port 1
+    let client =
+        mongodb::Client::with_uri_str("mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=100")
+            .await
+            .unwrap();
+    let scheduler = Arc::new(Scheduler::new(
+        "sleep 0.2".into(),
+        Arc::new(client.database("test")),
+        None,
+    ));
+
+    // the caller gives up, the job carries on
+    let run = scheduler.clone().run("manual");
+    assert!(tokio::time::timeout(Duration::from_millis(50), run)
+        .await
+        .is_err());
+    assert!(scheduler.running.load(Ordering::SeqCst));
+    assert!(matches!(
+        scheduler.clone().run("manual").await,
+        Err(AppError::Conflict(_))
+    ));
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!scheduler.running.load(Ordering::SeqCst));
+}
@@ -1,19 +1,49 @@
 use axum::{
-    extract::{Path, Query},
-    response::IntoResponse,
+    extract::{Path, Query, RawQuery},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE, LOCATION, VARY},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{Html, IntoResponse, Response},
     Extension, Json,
 };
-use backend::data::kanji::Kanji;
-use futures::{StreamExt, TryStreamExt};
+use futures::TryStreamExt;
+use model::{
+    kanji::Kanji,
+    meta::{KanjidicMeta, KANJIDIC_KIND},
+    provenance::Provenance,
+    strokes::Strokes,
+};
 use mongodb::{
-    bson::doc,
-    options::{AggregateOptions, Collation, FindOptions},
+    bson::{doc, from_document, Bson, Document},
+    options::FindOptions,
+    Cursor,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
 
-use crate::{AppError, Database};
+use crate::{
+    admin::{AdminToken, DebugMode},
+    batch,
+    cursor::{self, CursorPage, Paging},
+    html, lists,
+    normalize::normalize,
+    params::{self, Page},
+    permalink::Permalink,
+    raw,
+    relevance::{self, Fields, Reading, Weights},
+    stream::JsonArray,
+    AppError, Database, Store,
+};
+
+pub async fn get_index(
+    db: Extension<Database>,
+    store: Extension<Store>,
+) -> Result<Json<Vec<String>>, AppError> {
+    if let Some(store) = store.as_ref() {
+        return Ok(Json(store.literals().map(|c| c.to_string()).collect()));
+    }
 
-pub async fn get_index(db: Extension<Database>) -> Result<Json<Vec<String>>, AppError> {
     let out = db
         .collection::<Kanji>("kanjidic")
         .distinct("literal", None, None)
@@ -21,37 +51,443 @@ pub async fn get_index(db: Extension<Database>) -> Result<Json<Vec<String>>, App
     Ok(Json(out.iter().map(|b| b.to_string()).collect()))
 }
 
-pub async fn get_random(db: Extension<Database>) -> Result<Json<Kanji>, AppError> {
-    let mut cursor = db
+/// The header of the kanjidic file the entries were populated from, with
+/// when that was
+pub async fn get_meta(db: Extension<Database>) -> Result<Json<KanjidicMeta>, AppError> {
+    db.collection::<KanjidicMeta>("meta")
+        .find_one(doc! { "kind": KANJIDIC_KIND }, None)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::EntryNotFound("kanjidic has not been populated".into()))
+}
+
+#[derive(Deserialize)]
+pub struct RandomParams {
+    /// A grade or range of grades, e.g. 1..6
+    pub grade: Option<String>,
+    /// A new JLPT level or range of levels
+    pub jlptn: Option<String>,
+    /// Only kanji ranked at most this frequent
+    pub freq_max: Option<u32>,
+    /// A stroke count or range of stroke counts
+    pub strokes: Option<String>,
+    /// How many kanji to sample, returned as an array. A single kanji is
+    /// returned when unset.
+    pub count: Option<i64>,
+}
+
+impl RandomParams {
+    /// The filter the kanji are sampled from, as for a list, and how many
+    pub fn validate(&self) -> Result<(Document, Page), AppError> {
+        let filter = list_filter(
+            self.grade.as_deref(),
+            self.jlptn.as_deref(),
+            self.strokes.as_deref(),
+            self.freq_max,
+        )?;
+        Ok((filter, Page::new(None, self.count, 1)?))
+    }
+}
+
+/// Random kanji matching the same filters as a list, for flashcards.
+/// Sampled kanji are distinct.
+pub async fn get_random(
+    params: Query<RandomParams>,
+    db: Extension<Database>,
+) -> Result<Response, AppError> {
+    let (filter, page) = params.validate()?;
+
+    let out: Vec<Kanji> = db
         .collection::<Kanji>("kanjidic")
         .aggregate(
-            [doc! {
-                "$sample": {
-                    "size": 1
-                }
-            }],
+            [
+                doc! { "$match": filter },
+                doc! { "$sample": { "size": page.count } },
+            ],
             None,
         )
         .await?
-        .with_type::<Kanji>();
+        .with_type::<Kanji>()
+        .try_collect()
+        .await?;
 
-    if let Some(c) = cursor.try_next().await? {
-        return Ok(Json(c));
+    if params.count.is_some() {
+        return Ok(Json(out).into_response());
+    }
+    match out.into_iter().next() {
+        Some(k) => Ok(Json(k).into_response()),
+        None => Err(AppError::KanjiNotFound("no kanji match".into())),
     }
+}
 
-    Err(AppError::Error("No thing".into()))
+#[derive(Deserialize)]
+pub struct KanjiParams {
+    /// Include the source of each field in the response
+    #[serde(default)]
+    pub provenance: bool,
+    /// Include the entry as parsed from KANJIDIC, in debug mode or for
+    /// admins
+    #[serde(default)]
+    pub include_raw: bool,
+    /// The response format. When unset, HTML is returned to clients
+    /// accepting it, such as browsers and link unfurlers.
+    pub format: Option<Format>,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Json,
+    Html,
+}
+
+#[derive(Serialize)]
+pub struct WithProvenance {
+    #[serde(flatten)]
+    pub kanji: Kanji,
+    pub provenance: Option<Provenance>,
 }
 
 pub async fn get_kanji(
     Path(kanji): Path<String>,
+    // the raw query is passed on when redirecting
+    (params, RawQuery(query)): (Query<KanjiParams>, RawQuery),
+    headers: HeaderMap,
     db: Extension<Database>,
-) -> Result<Json<Kanji>, AppError> {
-    let out = db
+    store: Extension<Store>,
+    debug: Extension<DebugMode>,
+    token: Option<Extension<AdminToken>>,
+) -> Result<Response, AppError> {
+    let kanji = normalize(&kanji);
+    let con = db.collection::<Kanji>("kanjidic");
+    let found = match store.as_ref() {
+        Some(store) => match single_char(&kanji) {
+            Some(c) => store.get(c)?,
+            None => None,
+        },
+        None => con.find_one(doc! { "literal": &kanji}, None).await?,
+    };
+
+    if let Some(k) = found {
+        // JSON or HTML by the Accept header, so caches must tell them apart
+        let parts = (
+            Permalink::Kanji(k.literal).header(),
+            [(VARY, HeaderValue::from_static("accept"))],
+        );
+        if wants_html(params.format, &headers) {
+            return Ok((parts, Html(render_html(&k))).into_response());
+        }
+        let raw = match params.include_raw {
+            true => {
+                let filter = doc! { "literal": &kanji };
+                Some(raw::find(&headers, *debug, token, &db, "kanjidic_raw", filter).await?)
+            }
+            false => None,
+        };
+        if !params.provenance {
+            return Ok((parts, raw::json(k, raw)).into_response());
+        }
+
+        let provenance = db
+            .collection::<Provenance>("provenance")
+            .find_one(doc! { "literal": &kanji}, None)
+            .await?;
+        let entry = WithProvenance {
+            kanji: k,
+            provenance,
+        };
+        return Ok((parts, raw::json(entry, raw)).into_response());
+    }
+
+    // the literal may be a variant form of an entry in the dictionary
+    let suggestions: Vec<char> = con
+        .find(doc! { "variants": &kanji }, None)
+        .await?
+        .map_ok(|k| k.literal)
+        .try_collect()
+        .await?;
+
+    if let [canonical] = suggestions[..] {
+        let location = variant_location(canonical, query.as_deref());
+        return Ok((
+            StatusCode::FOUND,
+            [(LOCATION, HeaderValue::from_str(&location).unwrap())],
+        )
+            .into_response());
+    }
+
+    Err(AppError::KanjiSuggestions(
+        format!("no kanji {}", kanji),
+        suggestions,
+    ))
+}
+
+/// Where a variant redirects to, keeping the query so the format and
+/// other options carry over
+fn variant_location(canonical: char, query: Option<&str>) -> String {
+    let path = format!("/kanjidic/{}", percent_encode(canonical));
+    match query {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    }
+}
+
+/// The weight an Accept header gives a media type, from its most specific
+/// matching range, and whether that range names the type exactly. None
+/// when no range matches.
+fn quality(accept: &str, media: &str) -> Option<(f32, bool)> {
+    let (kind, _) = media.split_once('/')?;
+    let mut best: Option<(u8, f32)> = None;
+    for item in accept.split(',') {
+        let mut parts = item.split(';').map(|p| p.trim());
+        let range = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let specificity = match range.split_once('/') {
+            _ if range == media => 3,
+            Some((k, "*")) if k == kind => 2,
+            Some(("*", "*")) => 1,
+            _ => continue,
+        };
+        let q = parts
+            .find_map(|p| p.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if best.is_none_or(|(s, _)| specificity > s) {
+            best = Some((specificity, q));
+        }
+    }
+    best.map(|(specificity, q)| (q, specificity == 3))
+}
+
+/// Look up several kanji at once, e.g. every kanji of a sentence. The
+/// entries are returned in the order requested, with null for the
+/// characters without one.
+pub async fn post_batch(
+    db: Extension<Database>,
+    store: Extension<Store>,
+    Json(literals): Json<Vec<char>>,
+) -> Result<Json<Vec<Option<Kanji>>>, AppError> {
+    batch::validate(&literals)?;
+    let literals: Vec<char> = literals.into_iter().map(normalize_char).collect();
+
+    if let Some(store) = store.as_ref() {
+        let found = literals
+            .iter()
+            .map(|c| store.get(*c))
+            .collect::<Result<_, _>>()?;
+        return Ok(Json(found));
+    }
+
+    let keys: Vec<String> = literals.iter().map(|c| c.to_string()).collect();
+    let found: Vec<Kanji> = db
         .collection::<Kanji>("kanjidic")
-        .find_one(doc! { "literal": kanji}, None)
+        .find(doc! { "literal": { "$in": keys } }, None)
+        .await?
+        .try_collect()
         .await?;
+    Ok(Json(batch::in_order(&literals, found, |k| k.literal)))
+}
 
-    Ok(Json(out.unwrap()))
+/// Normalize a single character as looked up, keeping it as it is if it
+/// would become several
+fn normalize_char(c: char) -> char {
+    single_char(&normalize(&c.to_string())).unwrap_or(c)
+}
+
+/// The other forms of a kanji, such as its kyuujitai or shinjitai, in
+/// both directions
+#[derive(Serialize)]
+pub struct Variants {
+    pub literal: char,
+    /// The forms the entry lists as its variants
+    pub variants: Vec<char>,
+    /// The entries listing this kanji as one of their variants
+    pub variant_of: Vec<char>,
+}
+
+pub async fn get_variants(
+    Path(kanji): Path<String>,
+    db: Extension<Database>,
+    store: Extension<Store>,
+) -> Result<Json<Variants>, AppError> {
+    let kanji = normalize(&kanji);
+    let con = db.collection::<Kanji>("kanjidic");
+    let found = match store.as_ref() {
+        Some(store) => match single_char(&kanji) {
+            Some(c) => store.get(c)?,
+            None => None,
+        },
+        None => con.find_one(doc! { "literal": &kanji }, None).await?,
+    };
+    let k = found.ok_or_else(|| AppError::KanjiNotFound(format!("no kanji {}", kanji)))?;
+
+    let variant_of = con
+        .find(doc! { "variants": &kanji }, None)
+        .await?
+        .map_ok(|k| k.literal)
+        .try_collect()
+        .await?;
+    Ok(Json(Variants {
+        literal: k.literal,
+        variants: k.variants,
+        variant_of,
+    }))
+}
+
+/// The kanji which look alike a kanji, most alike first, as populated by
+/// `populate similar`
+pub async fn get_similar(
+    Path(kanji): Path<String>,
+    db: Extension<Database>,
+    store: Extension<Store>,
+) -> Result<Json<Vec<Kanji>>, AppError> {
+    let kanji = normalize(&kanji);
+    let not_found = || AppError::KanjiNotFound(format!("no kanji {}", kanji));
+
+    if let Some(store) = store.as_ref() {
+        let k = single_char(&kanji)
+            .map(|c| store.get(c))
+            .transpose()?
+            .flatten()
+            .ok_or_else(not_found)?;
+        let similar = k
+            .similar
+            .iter()
+            .map(|c| store.get(*c))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(Json(similar.into_iter().flatten().collect()));
+    }
+
+    let con = db.collection::<Kanji>("kanjidic");
+    let k = con
+        .find_one(doc! { "literal": &kanji }, None)
+        .await?
+        .ok_or_else(not_found)?;
+    let literals: Vec<String> = k.similar.iter().map(|c| c.to_string()).collect();
+    let mut similar: Vec<Kanji> = con
+        .find(doc! { "literal": { "$in": literals } }, None)
+        .await?
+        .try_collect()
+        .await?;
+    similar.sort_by_key(|s| k.similar.iter().position(|c| *c == s.literal));
+    Ok(Json(similar))
+}
+
+#[derive(Deserialize)]
+pub struct StrokesParams {
+    pub format: Option<StrokesFormat>,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StrokesFormat {
+    #[default]
+    Json,
+    Svg,
+}
+
+/// The stroke order of a kanji from KanjiVG, as populated by `populate
+/// strokes`, as a list of strokes or an SVG image
+pub async fn get_strokes(
+    Path(kanji): Path<String>,
+    params: Query<StrokesParams>,
+    db: Extension<Database>,
+) -> Result<Response, AppError> {
+    let kanji = normalize(&kanji);
+    let strokes = db
+        .collection::<Strokes>("strokes")
+        .find_one(doc! { "literal": &kanji }, None)
+        .await?
+        .ok_or_else(|| AppError::KanjiNotFound(format!("no strokes for {}", kanji)))?;
+
+    Ok(match params.format.unwrap_or_default() {
+        StrokesFormat::Json => Json(strokes).into_response(),
+        StrokesFormat::Svg => {
+            ([(CONTENT_TYPE, "image/svg+xml")], render_svg(&strokes)).into_response()
+        }
+    })
+}
+
+/// Draw the strokes as an SVG image in the 109 by 109 box of KanjiVG
+fn render_svg(strokes: &Strokes) -> String {
+    let mut out = String::from(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"109\" height=\"109\" viewBox=\"0 0 109 109\">\n\
+         <g style=\"fill:none;stroke:#000;stroke-width:3;stroke-linecap:round;stroke-linejoin:round\">\n",
+    );
+    for s in &strokes.strokes {
+        out.push_str(&format!("<path d=\"{}\"/>\n", html::escape(&s.path)));
+    }
+    out.push_str("</g>\n</svg>\n");
+    out
+}
+
+/// Whether to respond with HTML, from the format parameter or else the
+/// Accept header. HTML must be asked for by name, and preferred at least
+/// as much as JSON, so clients accepting anything get JSON.
+fn wants_html(format: Option<Format>, headers: &HeaderMap) -> bool {
+    if let Some(format) = format {
+        return format == Format::Html;
+    }
+    let Some(accept) = headers.get(ACCEPT).and_then(|h| h.to_str().ok()) else {
+        return false;
+    };
+    match quality(accept, "text/html") {
+        Some((html, true)) if html > 0.0 => {
+            html >= quality(accept, "application/json").map_or(0.0, |(q, _)| q)
+        }
+        _ => false,
+    }
+}
+
+/// Render the HTML page of an entry
+fn render_html(k: &Kanji) -> String {
+    let list = |v: &[String]| v.join("、");
+    let number = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+
+    html::render(
+        include_str!("../../templates/kanji.html"),
+        &[
+            ("literal", k.literal.to_string()),
+            ("path", percent_encode(k.literal)),
+            (
+                "description",
+                format!(
+                    "{} - {}",
+                    k.meanings.join(", "),
+                    list(&[k.on_readings.clone(), k.kun_readings.clone()].concat())
+                ),
+            ),
+            ("meanings", k.meanings.join(", ")),
+            ("on_readings", list(&k.on_readings)),
+            ("kun_readings", list(&k.kun_readings)),
+            ("nanoris", list(&k.nanoris)),
+            ("stroke_count", k.info.stroke_count.to_string()),
+            ("radical", k.info.radical.to_string()),
+            ("grade", number(k.info.grade)),
+            (
+                "jlpt",
+                k.info.jlptn.map(|n| format!("N{}", n)).unwrap_or_default(),
+            ),
+            ("variants", k.variants.iter().collect()),
+        ],
+    )
+}
+
+/// The character of a string containing exactly one
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Percent encode a character for use in a URL path
+pub fn percent_encode(c: char) -> String {
+    let mut buf = [0; 4];
+    c.encode_utf8(&mut buf)
+        .bytes()
+        .map(|b| format!("%{:02X}", b))
+        .collect()
 }
 
 #[derive(Deserialize)]
@@ -60,13 +496,18 @@ pub struct DictEntry {
     pub entry: u32,
 }
 
+impl DictEntry {
+    /// The field the entry number is looked up in
+    pub fn key(&self) -> Result<String, AppError> {
+        Ok("dict_index.".to_owned() + params::dict_name(&self.dict)?)
+    }
+}
+
 pub async fn get_dict_entry(
     params: Path<DictEntry>,
     db: Extension<Database>,
 ) -> Result<Json<Kanji>, AppError> {
-    // use unwrap_or() for these
-
-    let key = "references.".to_owned() + &params.dict;
+    let key = params.key()?;
     let out = db
         .collection::<Kanji>("kanjidic")
         .find_one(
@@ -77,80 +518,649 @@ pub async fn get_dict_entry(
         )
         .await?;
 
-    Ok(Json(out.unwrap()))
+    out.map(Json).ok_or_else(|| {
+        AppError::EntryNotFound(format!("no entry {} in {}", params.entry, params.dict))
+    })
 }
 
+/// The cursor of a listing of kanji: the number they are sorted by, and
+/// the literal to break ties
+pub type CursorKey = (f64, char);
+
 #[derive(Deserialize)]
 pub struct DictEntries {
     pub dict: String,
     pub from: Option<i64>,
     pub count: Option<i64>,
+    /// Page by cursor instead of offset, empty for the first page
+    pub cursor: Option<String>,
 }
 
+impl DictEntries {
+    /// The dictionary and the paging and page of the listing
+    pub fn validate(&self) -> Result<(&str, Paging<CursorKey>, Page), AppError> {
+        let dict = params::dict_name(&self.dict)?;
+        let paging = Paging::new(self.cursor.as_deref(), self.from)?;
+        Ok((dict, paging, Page::new(self.from, self.count, 10)?))
+    }
+}
+
+/// List the kanji of a dictionary in its order
 pub async fn get_dict_entries(
     params: Query<DictEntries>,
     db: Extension<Database>,
-) -> Result<Json<Vec<Kanji>>, AppError> {
-    let from = params.from.unwrap_or(0);
-    let count = params.count.unwrap_or(10);
+) -> Result<Response, AppError> {
+    let (dict, paging, page) = params.validate()?;
 
-    let collation = Collation::builder()
-        .locale("en_US")
-        .numeric_ordering(true)
-        .build();
-    // let options = AggregateOptions::builder().collation(collation).build();
+    let key = format!("dict_index.{}", dict);
+    let mut filter = doc! { &key: { "$exists": true } };
+    if let Some((value, literal)) = paging.after() {
+        filter = doc! { "$and": [filter, { "$or": [
+            { &key: { "$gt": value } },
+            { &key: value, "literal": { "$gt": literal.to_string() } },
+        ] }] };
+    }
+    let sort = doc! { &key: 1, "literal": 1 };
+
+    if paging == Paging::Offset {
+        let find_options = FindOptions::builder()
+            .sort(sort)
+            .skip(page.from)
+            .limit(page.count)
+            .build();
+        let out = db
+            .collection::<Kanji>("kanjidic")
+            .find(filter, find_options)
+            .await?;
+        return Ok(JsonArray(out).into_response());
+    }
 
     let find_options = FindOptions::builder()
-        .sort(doc! { "dict.value": 1})
-        .skip(from as u64)
-        .limit(count)
-        .collation(collation)
+        .sort(sort)
+        .limit(page.count + 1)
         .build();
+    let found: Vec<Document> = db
+        .collection::<Document>("kanjidic")
+        .find(filter, find_options)
+        .await?
+        .try_collect()
+        .await?;
+    let found = keyed(found, |d| d.get_document("dict_index").ok()?.get(dict))?;
+    Ok(Json(CursorPage::new(found, page.count)).into_response())
+}
+
+/// Read kanji fetched as documents along with their cursor keys, the
+/// number `key` finds and the literal
+fn keyed(
+    found: Vec<Document>,
+    key: impl Fn(&Document) -> Option<&Bson>,
+) -> Result<Vec<(CursorKey, Kanji)>, AppError> {
+    found
+        .into_iter()
+        .map(|d| {
+            let value = cursor::number(key(&d))
+                .ok_or_else(|| AppError::Error("result without a sort key".into()))?;
+            let k: Kanji = from_document(d).map_err(|e| AppError::Error(e.to_string()))?;
+            Ok(((value, k.literal), k))
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+pub struct QueryPath {
+    pub qc_type: String,
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct QueryParams {
+    /// Include kanji the code is a common misclassification of
+    #[serde(default = "default_true")]
+    pub misclass: bool,
+    pub from: Option<i64>,
+    pub count: Option<i64>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Find kanji by a query code such as SKIP. Kanji with the code as their
+/// correct code come before those it is a misclassification of.
+pub async fn get_query(
+    path: Path<QueryPath>,
+    params: Query<QueryParams>,
+    db: Extension<Database>,
+) -> Result<Json<Vec<Kanji>>, AppError> {
+    let code = params::query_code(&path.qc_type, &path.code)?;
+    let page = Page::new(params.from, params.count, 100)?;
+
+    let mut matches = doc! { "qc_type": &path.qc_type, "code": &code };
+    if !params.misclass {
+        matches.insert("misclass", doc! { "$exists": false });
+    }
+    let find_options = FindOptions::builder().sort(doc! { "literal": 1 }).build();
+    let mut found: Vec<Kanji> = db
+        .collection::<Kanji>("kanjidic")
+        .find(doc! { "query": { "$elemMatch": matches } }, find_options)
+        .await?
+        .try_collect()
+        .await?;
 
+    // a code rarely matches more than a few hundred kanji, so rank and
+    // page here rather than in the database
+    found.sort_by_key(|k| {
+        !k.query
+            .iter()
+            .any(|q| q.qc_type == path.qc_type && q.code == code && q.misclass.is_none())
+    });
+    Ok(Json(
+        found
+            .into_iter()
+            .skip(page.from as usize)
+            .take(page.count as usize)
+            .collect(),
+    ))
+}
+
+/// The order of a filtered list
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ListSort {
+    /// By codepoint
+    #[default]
+    Literal,
+    /// Most frequent first, unranked kanji last
+    Freq,
+    /// Fewest strokes first
+    Strokes,
+    /// In the order of the custom list given by `list`
+    List,
+}
+
+#[derive(Deserialize)]
+pub struct ListParams {
+    /// A grade or range of grades, e.g. 1..6
+    pub grade: Option<String>,
+    /// A new JLPT level or range of levels
+    pub jlptn: Option<String>,
+    /// Only kanji ranked at most this frequent
+    pub freq_max: Option<u32>,
+    /// A stroke count or range of stroke counts
+    pub strokes: Option<String>,
+    /// Only kanji in this custom list
+    pub list: Option<String>,
+    #[serde(default)]
+    pub sort: ListSort,
+    pub from: Option<i64>,
+    pub count: Option<i64>,
+}
+
+impl ListParams {
+    /// The filter matching every given condition and the page of the list
+    pub fn validate(&self) -> Result<(Document, Page), AppError> {
+        let filter = list_filter(
+            self.grade.as_deref(),
+            self.jlptn.as_deref(),
+            self.strokes.as_deref(),
+            self.freq_max,
+        )?;
+        match &self.list {
+            Some(list) => {
+                params::dict_name(list)?;
+            }
+            None if self.sort == ListSort::List => {
+                return Err(AppError::BadRequest("sort=list needs a list".into()))
+            }
+            None => (),
+        }
+        Ok((filter, Page::new(self.from, self.count, 100)?))
+    }
+}
+
+/// The filter matching every given grade, JLPT level and stroke count
+/// range and frequency rank
+fn list_filter(
+    grade: Option<&str>,
+    jlptn: Option<&str>,
+    strokes: Option<&str>,
+    freq_max: Option<u32>,
+) -> Result<Document, AppError> {
+    let mut filter = doc! {};
+    for (name, field, value) in [
+        ("grade", "info.grade", grade),
+        ("jlptn", "info.jlptn", jlptn),
+        ("strokes", "info.stroke_count", strokes),
+    ] {
+        if let Some(value) = value {
+            let (low, high) = params::range(name, value)?;
+            filter.insert(field, doc! { "$gte": low, "$lte": high });
+        }
+    }
+    if let Some(freq) = freq_max {
+        filter.insert("info.freq", doc! { "$lte": freq });
+    }
+    Ok(filter)
+}
+
+/// List kanji by grade, JLPT level, frequency, stroke count and custom
+/// list, for building study lists
+pub async fn get_list(
+    params: Query<ListParams>,
+    db: Extension<Database>,
+) -> Result<JsonArray<Cursor<Kanji>>, AppError> {
+    let (mut filter, page) = params.validate()?;
+
+    // missing fields sort first, so rank unranked kanji after the rest
+    let mut rank = doc! { "$ifNull": ["$info.freq", i32::MAX] };
+    if let Some(list) = &params.list {
+        let literals: Vec<String> = lists::literals(&db, list)
+            .await?
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        if params.sort == ListSort::List {
+            rank = doc! { "$indexOfArray": [&literals, "$literal"] };
+        }
+        filter.insert("literal", doc! { "$in": literals });
+    }
+
+    let sort = match params.sort {
+        ListSort::Literal => doc! { "literal": 1 },
+        ListSort::Freq | ListSort::List => doc! { "rank": 1, "literal": 1 },
+        ListSort::Strokes => doc! { "info.stroke_count": 1, "literal": 1 },
+    };
+    let pipeline = [
+        doc! { "$match": filter },
+        doc! { "$addFields": { "rank": rank } },
+        doc! { "$sort": sort },
+        doc! { "$skip": page.from as i64 },
+        doc! { "$limit": page.count },
+        doc! { "$project": { "rank": 0 } },
+    ];
     let out = db
         .collection::<Kanji>("kanjidic")
-        .find(
-            doc! {
-                "dict.type": &params.dict,
-            },
-            find_options,
-        )
+        .aggregate(pipeline, None)
         .await?
         .with_type::<Kanji>();
 
-    Ok(Json(out.try_collect().await?))
+    Ok(JsonArray(out))
 }
 
-#[derive(Deserialize)]
+/// What a search matches
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Literals, readings and meanings
+    #[default]
+    All,
+    /// Only on and kun readings, written in either kana or in romaji
+    Reading,
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct SearchParams {
     pub search: String,
+    #[serde(default)]
+    pub mode: SearchMode,
+    /// Ignore okurigana separators and prefix and suffix markers in
+    /// reading searches
+    #[serde(default = "default_true")]
+    pub strip: bool,
+    /// Only kanji in this custom list
+    pub list: Option<String>,
+    /// The fields searched besides the literal, a comma separated list of
+    /// meanings, readings and nanori, all of them by default
+    pub fields: Option<String>,
     pub from: Option<i64>,
     pub count: Option<i64>,
+    /// Page by cursor instead of offset, empty for the first page
+    pub cursor: Option<String>,
+}
+
+impl SearchParams {
+    /// The normalized search, the fields it covers, the reading searched
+    /// for in reading mode and the page of results
+    pub fn validate(&self) -> Result<(String, Fields, Option<Reading>, Page), AppError> {
+        let search = params::search(&self.search)?;
+        let fields = Fields::parse(self.fields.as_deref())?;
+        let reading = match self.mode {
+            SearchMode::Reading => Some(Reading::new(&search, self.strip)?),
+            SearchMode::All => None,
+        };
+        Ok((
+            search,
+            fields,
+            reading,
+            Page::new(self.from, self.count, 10)?,
+        ))
+    }
+
+    /// Whether results are paged by offset or by cursor
+    pub fn paging(&self) -> Result<Paging<CursorKey>, AppError> {
+        Paging::new(self.cursor.as_deref(), self.from)
+    }
+}
+
+/// The aggregation pipeline a search is run with
+#[derive(Serialize)]
+pub struct SearchQuery {
+    /// Matches every entry relevant to the search
+    filter: Document,
+    /// Scores the matches for ranking
+    score: Document,
+    /// Orders the scored matches
+    sort: Document,
+    #[serde(skip)]
+    page: Page,
+    /// Whether the meanings are searched with the text index, to be
+    /// retried with partial matches if that finds nothing
+    text: bool,
+}
+
+impl SearchQuery {
+    /// Only match the given literals, e.g. those of a custom list
+    fn restrict(&mut self, literals: &[char]) {
+        let literals: Vec<String> = literals.iter().map(|c| c.to_string()).collect();
+        self.filter = doc! { "$and": [self.filter.clone(), { "literal": { "$in": literals } }] };
+    }
+
+    /// The pipeline of a page starting after the result with the given
+    /// score and literal, keeping the scores for the next cursor. One
+    /// more result than the page is read to tell whether there are more.
+    fn cursor_pipeline(&self, after: Option<&CursorKey>) -> Vec<Document> {
+        let mut pipeline = vec![
+            doc! { "$match": self.filter.clone() },
+            doc! { "$addFields": { "score": self.score.clone() } },
+        ];
+        if let Some((score, literal)) = after {
+            pipeline.push(doc! { "$match": { "$or": [
+                { "score": { "$lt": score } },
+                { "score": score, "literal": { "$gt": literal.to_string() } },
+            ] } });
+        }
+        pipeline.push(doc! { "$sort": self.sort.clone() });
+        pipeline.push(doc! { "$limit": self.page.count + 1 });
+        pipeline
+    }
+
+    fn pipeline(&self) -> Vec<Document> {
+        vec![
+            doc! { "$match": self.filter.clone() },
+            doc! { "$addFields": { "score": self.score.clone() } },
+            doc! { "$sort": self.sort.clone() },
+            doc! { "$skip": self.page.from as i64 },
+            doc! { "$limit": self.page.count },
+            doc! { "$project": { "score": 0 } },
+        ]
+    }
+}
+
+/// Build the query of a search. Words are looked up in the meanings
+/// with the text index unless `partial`, which instead matches any
+/// meaning containing the search.
+fn search_query(
+    params: &SearchParams,
+    weights: &Weights,
+    partial: bool,
+) -> Result<SearchQuery, AppError> {
+    let (search, fields, reading, page) = params.validate()?;
+    let text = !partial && reading.is_none() && relevance::wants_text(&search, fields);
+    let (filter, score) = match reading {
+        Some(reading) => (reading.filter(), reading.score(weights)),
+        None if text => (
+            relevance::text_filter(&search),
+            relevance::text_score(weights, &search),
+        ),
+        None => (
+            relevance::filter(&search, fields),
+            relevance::score(weights, &search, fields),
+        ),
+    };
+    Ok(SearchQuery {
+        filter,
+        score,
+        sort: doc! { "score": -1, "literal": 1 },
+        page,
+        text,
+    })
+}
+
+/// Build the query of a search restricted to its list, falling back to
+/// partial matches of the meanings when the text search finds nothing,
+/// e.g. for part of a word
+async fn build_search(
+    params: &SearchParams,
+    db: &Database,
+    weights: &Weights,
+) -> Result<SearchQuery, AppError> {
+    let mut query = search_query(params, weights, false)?;
+    let literals = match &params.list {
+        Some(list) => Some(lists::literals(db, list).await?),
+        None => None,
+    };
+    if let Some(literals) = &literals {
+        query.restrict(literals);
+    }
+
+    if query.text {
+        let found = db
+            .collection::<Document>("kanjidic")
+            .find_one(query.filter.clone(), None)
+            .await?;
+        if found.is_none() {
+            query = search_query(params, weights, true)?;
+            if let Some(literals) = &literals {
+                query.restrict(literals);
+            }
+        }
+    }
+    Ok(query)
 }
 
 pub async fn get_search(
     params: Query<SearchParams>,
     db: Extension<Database>,
-) -> Result<Json<Vec<Kanji>>, AppError> {
-    // use unwrap_or() for these
+    weights: Extension<relevance::Shared>,
+) -> Result<Response, AppError> {
+    let paging = params.paging()?;
+    let weights = weights.read().unwrap().clone();
+    let query = build_search(&params, &db, &weights).await?;
 
-    let from = params.from.unwrap_or(0) as u64;
-    let count = params.count.unwrap_or(10);
-    let find_options = FindOptions::builder()
-        .sort(doc! { "literal": 1})
-        .skip(from)
-        .limit(count)
-        .build();
+    if paging == Paging::Offset {
+        let out = db
+            .collection::<Kanji>("kanjidic")
+            .aggregate(query.pipeline(), None)
+            .await?
+            .with_type::<Kanji>();
+        return Ok(JsonArray(out).into_response());
+    }
 
-    let out = db
+    let found: Vec<Document> = db
         .collection::<Kanji>("kanjidic")
-        .find(
-            doc! { "meanings": {
-            "$elemMatch": {
-                "value": &params.search    } }},
-            find_options,
-        )
+        .aggregate(query.cursor_pipeline(paging.after()), None)
+        .await?
+        .try_collect()
         .await?;
+    let found = keyed(found, |d| d.get("score"))?;
+    Ok(Json(CursorPage::new(found, query.page.count)).into_response())
+}
+
+#[derive(Serialize)]
+pub struct Explain {
+    /// The search parameters as understood by the server
+    pub params: SearchParams,
+    /// The weights results were ranked with
+    pub weights: Weights,
+    /// The query built from the search: its filter, score and sort, and
+    /// whether it uses the text index
+    pub query: SearchQuery,
+    /// The aggregation pipeline sent to the database
+    pub pipeline: Vec<Document>,
+    /// The plan chosen by the query planner, including any index used
+    pub winning_plan: Option<Document>,
+    /// Documents and keys examined and time spent executing the query
+    pub execution_stats: Option<Document>,
+    pub timing: Timing,
+}
 
-    Ok(Json(out.try_collect().await?))
+#[derive(Serialize)]
+pub struct Timing {
+    /// Time spent building the query, in microseconds
+    pub build_us: u64,
+    /// Round trip time of the explain command, in milliseconds
+    pub explain_ms: u64,
+}
+
+/// Explain how a search would be executed without returning its results
+pub async fn get_search_explain(
+    params: Query<SearchParams>,
+    db: Extension<Database>,
+    weights: Extension<relevance::Shared>,
+) -> Result<Json<Explain>, AppError> {
+    let weights = weights.read().unwrap().clone();
+    let start = Instant::now();
+    let query = build_search(&params, &db, &weights).await?;
+    let pipeline = query.pipeline();
+    let build = start.elapsed();
+
+    let command = doc! {
+        "explain": {
+            "aggregate": "kanjidic",
+            "pipeline": pipeline.clone(),
+            "cursor": {},
+        },
+        "verbosity": "executionStats",
+    };
+
+    let start = Instant::now();
+    let out = db.run_command(command, None).await?;
+    let explain = start.elapsed();
+
+    Ok(Json(Explain {
+        params: params.0,
+        weights,
+        query,
+        pipeline,
+        winning_plan: planner(&out)
+            .and_then(|p| p.get_document("winningPlan").ok())
+            .cloned(),
+        execution_stats: out
+            .get_document("executionStats")
+            .ok()
+            .or_else(|| cursor_stage(&out)?.get_document("executionStats").ok())
+            .cloned(),
+        timing: Timing {
+            build_us: build.as_micros() as u64,
+            explain_ms: explain.as_millis() as u64,
+        },
+    }))
+}
+
+/// The $cursor stage an aggregation explain nests the query plan in,
+/// unless the whole pipeline ran as a single query
+fn cursor_stage(explain: &Document) -> Option<&Document> {
+    explain
+        .get_array("stages")
+        .ok()?
+        .first()?
+        .as_document()?
+        .get_document("$cursor")
+        .ok()
+}
+
+fn planner(explain: &Document) -> Option<&Document> {
+    explain
+        .get_document("queryPlanner")
+        .ok()
+        .or_else(|| cursor_stage(explain)?.get_document("queryPlanner").ok())
+}
+
+#[test]
+fn test_normalize_char() {
+    assert_eq!(normalize_char('水'), '水');
+    // a CJK compatibility ideograph
+    assert_eq!(normalize_char('\u{F91D}'), '欄');
+}
+
+#[test]
+fn test_percent_encode() {
+    assert_eq!(percent_encode('塩'), "%E5%A1%A9");
+}
+
+#[test]
+fn test_variant_location() {
+    assert_eq!(variant_location('高', None), "/kanjidic/%E9%AB%98");
+    assert_eq!(
+        variant_location('高', Some("format=html&provenance=true")),
+        "/kanjidic/%E9%AB%98?format=html&provenance=true"
+    );
+}
+
+#[test]
+fn test_wants_html() {
+    let accepts = |accept: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, accept.parse().unwrap());
+        wants_html(None, &headers)
+    };
+
+    assert!(accepts(
+        "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+    ));
+    assert!(accepts("text/html"));
+    assert!(!accepts("text/html;q=0"));
+    assert!(!accepts("*/*"));
+    assert!(!accepts("text/*"));
+    assert!(!accepts("application/json, text/html;q=0.5"));
+    assert!(!accepts("application/json"));
+    assert!(!wants_html(None, &HeaderMap::new()));
+    assert!(wants_html(Some(Format::Html), &HeaderMap::new()));
+}
+
+#[test]
+fn test_search_query() {
+    let params: SearchParams = serde_json::from_value(serde_json::json!({
+        "search": "kai",
+        "mode": "reading",
+    }))
+    .unwrap();
+    let query = search_query(&params, &Weights::default(), false)
+        .ok()
+        .unwrap();
+    let out = serde_json::to_value(&query).unwrap();
+    assert_eq!(out["text"], false);
+    assert_eq!(
+        out["sort"],
+        serde_json::json!({ "score": -1, "literal": 1 })
+    );
+    assert_eq!(
+        query.filter,
+        Reading::new("kai", true).ok().unwrap().filter()
+    );
+    assert_eq!(query.pipeline()[2], doc! { "$sort": query.sort.clone() });
+}
+
+#[test]
+fn test_render_svg() {
+    use model::strokes::Stroke;
+
+    let strokes = Strokes {
+        literal: '二',
+        strokes: vec![
+            Stroke {
+                kind: Some("㇐".into()),
+                path: "M27.5,28.51c1.75,0.49".into(),
+            },
+            Stroke {
+                kind: None,
+                path: "M14.5,77.75\"".into(),
+            },
+        ],
+    };
+    let svg = render_svg(&strokes);
+    assert!(svg.starts_with("<svg "));
+    assert_eq!(svg.matches("<path ").count(), 2);
+    assert!(svg.contains("<path d=\"M27.5,28.51c1.75,0.49\"/>"));
+    assert!(svg.contains("<path d=\"M14.5,77.75&quot;\"/>"));
 }
@@ -0,0 +1,219 @@
+//! Custom ordered kanji lists, such as the curriculum of a course, which
+//! admins upload and which can then be browsed in order, used to filter
+//! searches and kanji listings, or used as the order of a listing. Lists
+//! are stored in the "lists" collection.
+
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Path, Query},
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use futures::TryStreamExt;
+use model::kanji::Kanji;
+use mongodb::{
+    bson::{doc, DateTime},
+    options::{FindOptions, ReplaceOptions},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    admin::{self, AdminToken},
+    params::{self, Page},
+    AppError, Database,
+};
+
+/// The most literals a list may have
+const MAX_LITERALS: usize = 20_000;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct KanjiList {
+    pub name: String,
+    /// The literals in list order
+    pub literals: Vec<char>,
+    /// RFC 3339
+    pub updated: String,
+}
+
+/// Read the literals of an uploaded list. Each line is either plain text,
+/// whose every character is a literal, or CSV, whose first field is.
+/// Blank lines and lines starting with # are skipped.
+pub fn parse_literals(text: &str) -> Result<Vec<char>, AppError> {
+    let literals: Vec<char> = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .flat_map(|l| l.split(',').next().unwrap_or_default().chars())
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    if literals.is_empty() {
+        return Err(AppError::BadRequest("the list is empty".into()));
+    }
+    if literals.len() > MAX_LITERALS {
+        return Err(AppError::BadRequest(format!(
+            "the list has more than {} literals",
+            MAX_LITERALS
+        )));
+    }
+    let mut seen = HashSet::new();
+    let duplicates: String = literals.iter().filter(|c| !seen.insert(**c)).collect();
+    if !duplicates.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "duplicate literals: {}",
+            duplicates
+        )));
+    }
+    Ok(literals)
+}
+
+/// The literals of a stored list
+pub async fn literals(db: &Database, name: &str) -> Result<Vec<char>, AppError> {
+    let name = params::dict_name(name)?;
+    db.collection::<KanjiList>("lists")
+        .find_one(doc! { "name": name }, None)
+        .await?
+        .map(|l| l.literals)
+        .ok_or_else(|| AppError::EntryNotFound(format!("no list {}", name)))
+}
+
+fn to_strings(literals: &[char]) -> Vec<String> {
+    literals.iter().map(|c| c.to_string()).collect()
+}
+
+/// Create or replace a list from a plain text or CSV body
+pub async fn put_list(
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    token: Extension<AdminToken>,
+    db: Extension<Database>,
+    body: String,
+) -> Result<Json<KanjiList>, AppError> {
+    admin::authorize(&headers, &token)?;
+    let name = params::dict_name(&name)?.to_owned();
+    let literals = parse_literals(&body)?;
+
+    let known: HashSet<char> = db
+        .collection::<Kanji>("kanjidic")
+        .find(
+            doc! { "literal": { "$in": to_strings(&literals) } },
+            FindOptions::builder()
+                .projection(doc! { "_id": 0, "literal": 1 })
+                .build(),
+        )
+        .await?
+        .with_type::<mongodb::bson::Document>()
+        .try_filter_map(
+            |d| async move { Ok(d.get_str("literal").ok().and_then(|l| l.chars().next())) },
+        )
+        .try_collect()
+        .await?;
+    let unknown: String = literals.iter().filter(|c| !known.contains(c)).collect();
+    if !unknown.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "literals not in the dictionary: {}",
+            unknown
+        )));
+    }
+
+    let list = KanjiList {
+        name,
+        literals,
+        updated: DateTime::now().try_to_rfc3339_string().unwrap_or_default(),
+    };
+    db.collection::<KanjiList>("lists")
+        .replace_one(
+            doc! { "name": &list.name },
+            &list,
+            ReplaceOptions::builder().upsert(true).build(),
+        )
+        .await?;
+    Ok(Json(list))
+}
+
+pub async fn delete_list(
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    token: Extension<AdminToken>,
+    db: Extension<Database>,
+) -> Result<StatusCode, AppError> {
+    admin::authorize(&headers, &token)?;
+    let name = params::dict_name(&name)?;
+    let out = db
+        .collection::<KanjiList>("lists")
+        .delete_one(doc! { "name": name }, None)
+        .await?;
+    match out.deleted_count {
+        0 => Err(AppError::EntryNotFound(format!("no list {}", name))),
+        _ => Ok(StatusCode::NO_CONTENT),
+    }
+}
+
+/// The names of every list
+pub async fn get_lists(db: Extension<Database>) -> Result<Json<Vec<String>>, AppError> {
+    let names = db
+        .collection::<KanjiList>("lists")
+        .distinct("name", None, None)
+        .await?
+        .into_iter()
+        .filter_map(|n| n.as_str().map(str::to_owned))
+        .collect();
+    Ok(Json(names))
+}
+
+pub async fn get_list(
+    Path(name): Path<String>,
+    db: Extension<Database>,
+) -> Result<Json<KanjiList>, AppError> {
+    let name = params::dict_name(&name)?;
+    db.collection::<KanjiList>("lists")
+        .find_one(doc! { "name": name }, None)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::EntryNotFound(format!("no list {}", name)))
+}
+
+#[derive(Deserialize)]
+pub struct ListPage {
+    pub from: Option<i64>,
+    pub count: Option<i64>,
+}
+
+/// The kanji of a list, in list order
+pub async fn get_list_kanji(
+    Path(name): Path<String>,
+    params: Query<ListPage>,
+    db: Extension<Database>,
+) -> Result<Json<Vec<Kanji>>, AppError> {
+    let page = Page::new(params.from, params.count, 100)?;
+    let literals = literals(&db, &name).await?;
+    let literals: Vec<char> = literals
+        .into_iter()
+        .skip(page.from as usize)
+        .take(page.count as usize)
+        .collect();
+
+    let mut found: Vec<Kanji> = db
+        .collection::<Kanji>("kanjidic")
+        .find(doc! { "literal": { "$in": to_strings(&literals) } }, None)
+        .await?
+        .try_collect()
+        .await?;
+    found.sort_by_key(|k| literals.iter().position(|c| *c == k.literal));
+    Ok(Json(found))
+}
+
+#[test]
+fn test_parse_literals() {
+    let ok = |text| parse_literals(text).ok();
+    assert_eq!(ok("一二三\n四"), Some(vec!['一', '二', '三', '四']));
+    assert_eq!(
+        ok("# grade 1\n一,one,1\n\n二 , two\r\n"),
+        Some(vec!['一', '二'])
+    );
+    assert_eq!(ok("一 二\t三"), Some(vec!['一', '二', '三']));
+    assert_eq!(ok("\n# nothing\n"), None);
+    assert_eq!(ok("一二一"), None);
+    assert_eq!(ok(&"一".repeat(MAX_LITERALS + 1)), None);
+}
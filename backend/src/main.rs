@@ -1,76 +1,268 @@
-mod data;
+mod admin;
+mod analyze;
+mod batch;
+mod cache;
+mod config;
+mod cursor;
+mod debug;
+mod decks;
+mod dumps;
+mod errors;
+mod examples;
+mod furigana;
+mod html;
+mod i18n;
+mod jmdict;
+mod jobs;
 mod kanji;
-use std::{net::SocketAddr, sync::Arc};
+mod lists;
+mod names;
+mod normalize;
+mod params;
+mod permalink;
+mod radicals;
+mod ratelimit;
+mod raw;
+mod relevance;
+mod schema;
+mod stream;
+mod sync;
+mod tenant;
+use std::sync::{Arc, RwLock};
 
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Extension, Router};
+use admin::{AdminToken, DebugMode};
+use axum::{
+    body::HttpBody,
+    http::HeaderValue,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post, put},
+    BoxError, Extension, Router,
+};
+use cache::{Cache, Caching};
+use config::Config;
+use dumps::Dumps;
+use errors::{ErrorCode, ErrorInfo};
+use jobs::Scheduler;
+use model::{namespace, store::MmapStore};
+use ratelimit::Limiter;
 use std::env;
+use tenant::Tenants;
 use tower_http::trace::TraceLayer;
 
-pub struct Config {
-    redis_url: String,
-    mongo_url: String,
-    server_port: u16,
-}
-
 pub enum AppError {
     Error(String),
-    // RedisError(RedisError),
+    BadRequest(String),
+    Unauthorized(String),
+    Conflict(String),
+    RateLimited(String),
+    /// The search weights file is missing or invalid
+    InvalidWeights(String),
+    /// A dump file exists but could not be read
+    DumpUnavailable(String),
+    /// A populate job could not be run
+    JobFailed(String),
+    /// A service the API depends on could not be reached
+    Upstream(String),
+    KanjiNotFound(String),
+    /// No kanji matches, but the literal is a variant of these
+    KanjiSuggestions(String, Vec<char>),
+    EntryNotFound(String),
     MongoError(mongodb::error::Error),
     SerdeError(serde_json::Error),
-}
-
-fn get_config() -> Config {
-    Config {
-        redis_url: env::var("REDIS_URL").unwrap(),
-        mongo_url: env::var("MONGODB_URL").unwrap(),
-        server_port: env::var("SERVER_PORT").unwrap().parse().unwrap(),
-    }
+    IoError(std::io::Error),
 }
 
 type Database = Arc<mongodb::Database>;
+type Store = Option<Arc<MmapStore>>;
 
 #[tokio::main]
 async fn main() {
-    let config = get_config();
-    // let client = redis::Client::open(config.redis_url).unwrap();
-    // let state = Arc::new(client);
-
-    let db = mongodb::Client::with_uri_str(config.mongo_url)
+    let config = Config::from_env().unwrap_or_else(|e| {
+        eprint!("{}", e);
+        std::process::exit(1)
+    });
+    let weights = relevance::load(config.search_weights.as_deref()).unwrap_or_else(|e| {
+        eprintln!("invalid configuration:\n  SEARCH_WEIGHTS {}", e);
+        std::process::exit(1)
+    });
+    // `--check-config` only validates the configuration, e.g. in a deploy
+    if env::args().any(|a| a == "--check-config") {
+        println!("configuration ok");
+        return;
+    }
+    let client = mongodb::Client::with_uri_str(&config.mongo_url)
         .await
-        .unwrap()
-        .database("kanjisho");
-    let state = Arc::new(db);
+        .unwrap();
+    let state = Arc::new(client.database(&namespace::database_name(config.namespace.as_deref())));
+    let tenants = Arc::new(Tenants::new(client, config.namespaces.clone()));
+
+    let store: Store = config
+        .store_path
+        .as_ref()
+        .map(|p| Arc::new(MmapStore::open(p).expect("failed to open kanji store")));
 
     tracing_subscriber::fmt::init();
 
-    let app = Router::new()
+    // only the default namespace is checked, the others are migrated
+    // along with it
+    if let Err(reason) = schema::check(&state).await {
+        panic!("refusing to start: {}", reason);
+    }
+
+    let app = build_router(&config, weights, state, store, tenants);
+
+    #[cfg(feature = "lambda")]
+    lambda_http::run(app).await.unwrap();
+
+    #[cfg(not(feature = "lambda"))]
+    {
+        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], config.server_port));
+        tracing::debug!("listening on {}", addr);
+        axum::Server::bind(&addr)
+            .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+            .await
+            .unwrap();
+    }
+}
+
+/// Build the application router, searching with the weights loaded from
+/// the configuration. Generic over the request body so it can be served
+/// by hyper or by a serverless runtime.
+fn build_router<B>(
+    config: &Config,
+    weights: relevance::Weights,
+    db: Database,
+    store: Store,
+    tenants: Arc<Tenants>,
+) -> Router<B>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    let envelope = config.error_envelope;
+    let debug = config.debug;
+    let relevance_config = relevance::Config {
+        path: config.search_weights.clone(),
+    };
+    let cache: Caching = config
+        .redis_url
+        .as_ref()
+        .map(|url| Arc::new(Cache::new(url, config.cache_ttl).expect("invalid REDIS_URL")));
+
+    let cached = {
+        let cache = cache.clone();
+        Router::new()
+            .route("/kanjidic", get(kanji::get_index))
+            .route("/kanjidic/dict", get(kanji::get_dict_entries))
+            .route("/kanjidic/list", get(kanji::get_list))
+            .route("/kanjidic/:kanji", get(kanji::get_kanji))
+            .route("/kanjidic/:kanji/variants", get(kanji::get_variants))
+            .route("/kanjidic/:kanji/similar", get(kanji::get_similar))
+            .route("/kanjidic/:kanji/strokes", get(kanji::get_strokes))
+            .route_layer(middleware::from_fn(move |req, next| {
+                cache::layer(cache.clone(), req, next)
+            }))
+    };
+    let mut app = Router::new()
         .route("/", get(|| async { "pong" }))
-        .route("/kanjidic", get(kanji::get_index))
+        .merge(cached)
+        .route("/kanjidic/meta", get(kanji::get_meta))
         .route("/kanjidic/random", get(kanji::get_random))
-        .route("/kanjidic/dict", get(kanji::get_dict_entries))
+        .route("/kanjidic/batch", post(kanji::post_batch))
         .route("/kanjidic/dict/:dict/:entry", get(kanji::get_dict_entry))
         .route("/kanjidic/search", get(kanji::get_search))
-        .route("/kanjidic/:kanji", get(kanji::get_kanji))
-        .layer(Extension(state))
-        .layer(TraceLayer::new_for_http());
-
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.server_port));
-    tracing::debug!("listening on {}", addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
-}
+        .route("/kanjidic/query/:qc_type/:code", get(kanji::get_query))
+        .route("/normalize", get(normalize::get_normalize))
+        .route("/analyze", post(analyze::post_analyze))
+        .route("/furigana", get(furigana::get_furigana))
+        .route("/examples", get(examples::get_examples))
+        .route("/decks/generate", get(decks::get_generate))
+        .route("/jmdict/random", get(jmdict::get_random))
+        .route("/jmdict/search", get(jmdict::get_search))
+        .route("/jmdict/batch", post(jmdict::post_batch))
+        .route("/jmdict/:seq", get(jmdict::get_entry))
+        .route("/names/search", get(names::get_search))
+        .route("/names/:seq", get(names::get_name))
+        .route("/e/:code", get(permalink::get_permalink))
+        .route("/lists", get(lists::get_lists))
+        .route("/lists/:name", get(lists::get_list))
+        .route("/lists/:name/kanji", get(lists::get_list_kanji))
+        .route("/radicals/narrow", get(radicals::get_narrow))
+        .route("/sync", get(sync::get_sync));
+
+    if config.debug {
+        app = app.route("/kanjidic/search/explain", get(kanji::get_search_explain));
+    }
+    if let Some(dir) = &config.dump_dir {
+        let dumps = Dumps::new(dir.into(), config.count_downloads);
+        app = app
+            .route("/meta", get(dumps::get_meta))
+            .route("/dumps/:name", get(dumps::get_dump))
+            .layer(Extension(Arc::new(dumps)));
+    }
+    if let Some(token) = &config.admin_token {
+        if let Some(command) = &config.repopulate_command {
+            let scheduler = Arc::new(Scheduler::new(command.clone(), db.clone(), cache.clone()));
+            if let Some(interval) = config.repopulate_interval {
+                scheduler.clone().spawn(interval);
+            }
+            app = app
+                .route("/admin/jobs", get(jobs::get_jobs))
+                .route("/admin/jobs/run", post(jobs::post_run))
+                .layer(Extension(scheduler));
+        }
+        app = app
+            .route(
+                "/admin/lists/:name",
+                put(lists::put_list).delete(lists::delete_list),
+            )
+            .route("/admin/cache/bust", post(cache::post_bust))
+            .route("/admin/search/weights", get(relevance::get_weights))
+            .route("/admin/search/weights/reload", post(relevance::post_reload))
+            .layer(Extension(AdminToken(token.clone())));
+    }
 
-// impl From<RedisError> for AppError {
-//     fn from(e: RedisError) -> Self {
-//         AppError::RedisError(e)
-//     }
-// }
+    if let Some(limit) = config.rate_limit {
+        let limiter = Arc::new(Limiter::new(limit));
+        limiter.clone().spawn_sweeper();
+        app = app.layer(middleware::from_fn(move |req, next| {
+            ratelimit::limit(limiter.clone(), req, next)
+        }));
+    }
+
+    app.layer(middleware::from_fn(move |req, next| {
+        tenant::select(tenants.clone(), req, next)
+    }))
+    .layer(Extension(db))
+    .layer(Extension(DebugMode(debug)))
+    .layer(Extension(Arc::new(RwLock::new(weights))))
+    .layer(Extension(relevance_config))
+    .layer(Extension(store))
+    .layer(Extension(cache))
+    .layer(Extension(Arc::new(config.clone())))
+    .layer(middleware::from_fn(i18n::localize))
+    .layer(middleware::from_fn(move |req, next| {
+        errors::envelope(envelope, req, next)
+    }))
+    .layer(middleware::from_fn(move |req, next| {
+        debug::layer(debug, req, next)
+    }))
+    .layer(TraceLayer::new_for_http())
+}
 
 impl From<mongodb::error::Error> for AppError {
     fn from(e: mongodb::error::Error) -> Self {
-        AppError::MongoError(e)
+        use mongodb::error::ErrorKind;
+        match *e.kind {
+            ErrorKind::ServerSelection { .. } | ErrorKind::DnsResolve { .. } | ErrorKind::Io(_) => {
+                // don't leak the database address to clients
+                tracing::error!("database unreachable: {}", e);
+                AppError::Upstream("database unavailable".into())
+            }
+            _ => AppError::MongoError(e),
+        }
     }
 }
 
@@ -80,14 +272,334 @@ impl From<serde_json::Error> for AppError {
     }
 }
 
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::IoError(e)
+    }
+}
+
+impl AppError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::Error(_) => ErrorCode::InternalError,
+            AppError::BadRequest(_) => ErrorCode::InvalidQuery,
+            AppError::Unauthorized(_) => ErrorCode::Unauthorized,
+            AppError::Conflict(_) => ErrorCode::Conflict,
+            AppError::RateLimited(_) => ErrorCode::RateLimited,
+            AppError::InvalidWeights(_) => ErrorCode::InvalidWeights,
+            AppError::DumpUnavailable(_) => ErrorCode::DumpUnavailable,
+            AppError::JobFailed(_) => ErrorCode::JobFailed,
+            AppError::Upstream(_) => ErrorCode::UpstreamError,
+            AppError::KanjiNotFound(_) | AppError::KanjiSuggestions(..) => ErrorCode::KanjiNotFound,
+            AppError::EntryNotFound(_) => ErrorCode::EntryNotFound,
+            AppError::MongoError(_) => ErrorCode::DatabaseError,
+            AppError::SerdeError(_) => ErrorCode::SerializationError,
+            AppError::IoError(_) => ErrorCode::StoreUnavailable,
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
-        let body = match self {
-            AppError::Error(e) => e,
-            // AppError::RedisError(e) => e.to_string(),
-            AppError::MongoError(e) => e.to_string(),
+        let code = self.code();
+        let mut data = None;
+        let message = match self {
+            AppError::Error(e)
+            | AppError::BadRequest(e)
+            | AppError::Unauthorized(e)
+            | AppError::Conflict(e)
+            | AppError::RateLimited(e)
+            | AppError::InvalidWeights(e)
+            | AppError::DumpUnavailable(e)
+            | AppError::JobFailed(e)
+            | AppError::Upstream(e)
+            | AppError::KanjiNotFound(e)
+            | AppError::EntryNotFound(e) => e,
+            AppError::KanjiSuggestions(e, suggestions) => {
+                data = Some(serde_json::Map::from_iter([(
+                    "suggestions".to_owned(),
+                    serde_json::json!(suggestions),
+                )]));
+                e
+            }
+            AppError::MongoError(e) => {
+                // don't leak hosts or queries to clients
+                tracing::error!("database: {}", e);
+                code.default_message().to_owned()
+            }
             AppError::SerdeError(e) => e.to_string(),
+            AppError::IoError(e) => {
+                // don't leak file paths to clients
+                tracing::error!("kanji store: {}", e);
+                code.default_message().to_owned()
+            }
         };
-        (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+        let mut res = (code.status(), message.clone()).into_response();
+        res.headers_mut()
+            .insert("x-error-code", HeaderValue::from_static(code.as_str()));
+        res.extensions_mut().insert(ErrorInfo {
+            code,
+            message,
+            data,
+        });
+        res
     }
 }
+
+#[cfg(test)]
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+#[cfg(test)]
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn test_failure_paths() {
+    let config = Config {
+        redis_url: None,
+        cache_ttl: std::time::Duration::from_secs(300),
+        // fail fast, nothing listens on port 1
+        mongo_url: "mongodb://127.0.0.1:1/?serverSelectionTimeoutMS=100".into(),
+        server_port: 0,
+        debug: false,
+        store_path: None,
+        error_envelope: errors::Envelope::Json,
+        namespace: None,
+        namespaces: vec![],
+        search_weights: None,
+        admin_token: Some("secret".into()),
+        dump_dir: None,
+        count_downloads: false,
+        repopulate_command: None,
+        repopulate_interval: None,
+        rate_limit: None,
+    };
+    let client = mongodb::Client::with_uri_str(&config.mongo_url)
+        .await
+        .unwrap();
+    let db = Arc::new(client.database("test"));
+    let tenants = Arc::new(Tenants::new(client, vec![]));
+    let app = build_router::<Body>(&config, Default::default(), db, None, tenants);
+
+    for (uri, status, code) in [
+        (
+            "/kanjidic/%E6%B0%B4",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/jmdict/1000220",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/jmdict/search?q=water",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/kanjidic/search?search=water&count=0",
+            StatusCode::BAD_REQUEST,
+            Some("INVALID_QUERY"),
+        ),
+        (
+            "/names/search?q=%E7%94%B0%E4%B8%AD",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/names/5327549",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/names/search?q=%20",
+            StatusCode::BAD_REQUEST,
+            Some("INVALID_QUERY"),
+        ),
+        (
+            "/decks/generate?order=klc&batch=10&from=2",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/decks/generate?order=heisig&batch=200",
+            StatusCode::BAD_REQUEST,
+            Some("INVALID_QUERY"),
+        ),
+        (
+            "/decks/generate?order=jlpt",
+            StatusCode::UNPROCESSABLE_ENTITY,
+            None,
+        ),
+        (
+            "/examples?kanji=%E6%BC%A2",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/examples?word=%E5%8B%89%E5%BC%B7",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/examples?kanji=%E6%BC%A2&word=%E5%8B%89%E5%BC%B7",
+            StatusCode::BAD_REQUEST,
+            Some("INVALID_QUERY"),
+        ),
+        (
+            "/kanjidic/search?search=water&cursor=",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/kanjidic/dict?dict=rtk&cursor=zz",
+            StatusCode::BAD_REQUEST,
+            Some("INVALID_QUERY"),
+        ),
+        (
+            "/kanjidic/%E6%B0%B4/variants",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/kanjidic/%E6%B0%B4/similar",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/kanjidic/%E6%B0%B4/strokes?format=svg",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/kanjidic/meta",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/kanjidic/list?grade=1..6&sort=freq",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/kanjidic/list?list=n5&sort=list",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/kanjidic/list?grade=6..1",
+            StatusCode::BAD_REQUEST,
+            Some("INVALID_QUERY"),
+        ),
+        (
+            "/kanjidic/random?jlptn=5&count=10",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/kanjidic/random?grade=x",
+            StatusCode::BAD_REQUEST,
+            Some("INVALID_QUERY"),
+        ),
+        (
+            "/kanjidic/query/skip/9-9-9",
+            StatusCode::BAD_REQUEST,
+            Some("INVALID_QUERY"),
+        ),
+        (
+            "/furigana?text=%E6%97%A5%E6%9C%AC",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        (
+            "/furigana?text=",
+            StatusCode::BAD_REQUEST,
+            Some("INVALID_QUERY"),
+        ),
+        ("/e/zz", StatusCode::BAD_REQUEST, Some("INVALID_QUERY")),
+        (
+            "/admin/search/weights",
+            StatusCode::UNAUTHORIZED,
+            Some("UNAUTHORIZED"),
+        ),
+        ("/lists/a.b", StatusCode::BAD_REQUEST, Some("INVALID_QUERY")),
+        (
+            "/lists/n5/kanji",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        ("/jmdict/abc", StatusCode::BAD_REQUEST, None),
+        (
+            "/sync?since_version=2024-02",
+            StatusCode::BAD_GATEWAY,
+            Some("UPSTREAM_ERROR"),
+        ),
+        ("/nowhere", StatusCode::NOT_FOUND, None),
+    ] {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), status, "{}", uri);
+        let header = res
+            .headers()
+            .get("x-error-code")
+            .map(|h| h.to_str().unwrap());
+        assert_eq!(header, code, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn test_suggestions() {
+    use axum::{middleware, routing::get, Router};
+
+    let app = Router::new()
+        .route(
+            "/",
+            get(|| async {
+                Err::<(), _>(AppError::KanjiSuggestions(
+                    "no kanji 髙".into(),
+                    vec!['高', '嵩'],
+                ))
+            }),
+        )
+        .layer(middleware::from_fn(i18n::localize))
+        .layer(middleware::from_fn(|req, next| {
+            errors::envelope(errors::Envelope::Json, req, next)
+        }));
+
+    for (lang, message) in [
+        ("en", "no kanji 髙"),
+        ("ja", "漢字が見つかりませんでした: no kanji 髙"),
+    ] {
+        let req = Request::builder()
+            .uri("/")
+            .header("accept-language", lang)
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let body = debug::collect(axum::body::boxed(res.into_body()))
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!({
+                "code": "KANJI_NOT_FOUND",
+                "message": message,
+                "suggestions": ["高", "嵩"],
+            })
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_database_error() {
+    let e = std::io::Error::other("connection to db.internal:27017 reset");
+    let res = AppError::MongoError(mongodb::error::Error::from(e)).into_response();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body = debug::collect(axum::body::boxed(res.into_body()))
+        .await
+        .unwrap();
+    assert_eq!(body, b"database error");
+}
@@ -0,0 +1,86 @@
+//! Proper names from JMnedict, as populated by `populate names`.
+
+use axum::{
+    extract::{Path, Query},
+    Extension, Json,
+};
+use model::name::Name;
+use mongodb::{
+    bson::{doc, Document},
+    options::FindOptions,
+    Cursor,
+};
+use serde::Deserialize;
+
+use crate::{
+    params::{self, Page},
+    relevance::escape_regex,
+    stream::JsonArray,
+    AppError, Database,
+};
+
+pub async fn get_name(
+    Path(seq): Path<u32>,
+    db: Extension<Database>,
+) -> Result<Json<Name>, AppError> {
+    db.collection::<Name>("names")
+        .find_one(doc! { "ent_seq": seq }, None)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::EntryNotFound(format!("no name {}", seq)))
+}
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    pub q: String,
+    pub from: Option<i64>,
+    pub count: Option<i64>,
+}
+
+/// Names written or read starting with the search, or romanized as it
+/// regardless of case
+fn search_filter(q: &str) -> Document {
+    let prefix = format!("^{}", escape_regex(q));
+    let exact = format!("^{}$", escape_regex(q));
+    doc! { "$or": [
+        { "kanji.text": { "$regex": &prefix } },
+        { "readings.text": { "$regex": &prefix } },
+        { "translations.details": { "$regex": exact, "$options": "i" } },
+    ]}
+}
+
+/// Search names by the start of a written form or reading, or by their
+/// romanization
+pub async fn get_search(
+    params: Query<SearchParams>,
+    db: Extension<Database>,
+) -> Result<JsonArray<Cursor<Name>>, AppError> {
+    let q = params::search(&params.q)?;
+    let page = Page::new(params.from, params.count, 10)?;
+
+    let options = FindOptions::builder()
+        .sort(doc! { "ent_seq": 1 })
+        .skip(page.from)
+        .limit(page.count)
+        .build();
+    let cursor = db
+        .collection::<Name>("names")
+        .find(search_filter(&q), options)
+        .await?;
+    Ok(JsonArray(cursor))
+}
+
+#[test]
+fn test_search_filter() {
+    let filter = search_filter("田中");
+    let or = filter.get_array("$or").unwrap();
+    let kanji = or[0].as_document().unwrap().get_document("kanji.text");
+    assert_eq!(kanji.unwrap().get_str("$regex"), Ok("^田中"));
+
+    let filter = search_filter("a.b");
+    let or = filter.get_array("$or").unwrap();
+    let details = or[2].as_document().unwrap();
+    let details = details.get_document("translations.details").unwrap();
+    assert_eq!(details.get_str("$regex"), Ok("^a\\.b$"));
+    assert_eq!(details.get_str("$options"), Ok("i"));
+}
@@ -0,0 +1,77 @@
+//! Normalization of user input to the literals used by KANJIDIC.
+//! Compatibility ideographs and Kangxi radicals are folded by NFKC,
+//! variation selectors are dropped, and a few CJK radical supplement
+//! forms which have no decomposition are mapped by hand.
+
+use axum::{extract::Query, Json};
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+/// Radical forms without a Unicode decomposition which are usually
+/// meant as the full character
+const SUPPLEMENT: &[(char, char)] = &[
+    ('\u{2EC6}', '角'),
+    ('\u{2ED1}', '長'),
+    ('\u{2ED7}', '雨'),
+    ('\u{2ED8}', '青'),
+    ('\u{2EDF}', '食'),
+    ('\u{2EE3}', '骨'),
+    ('\u{2EE4}', '鬼'),
+    ('\u{2EE8}', '麦'),
+    ('\u{2EE9}', '黄'),
+    ('\u{2EED}', '歯'),
+    ('\u{2EF2}', '亀'),
+];
+
+fn is_variation_selector(c: char) -> bool {
+    matches!(c, '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}')
+}
+
+/// Normalize text to canonical KANJIDIC literals
+pub fn normalize(text: &str) -> String {
+    text.nfkc()
+        .filter(|c| !is_variation_selector(*c))
+        .map(|c| {
+            SUPPLEMENT
+                .iter()
+                .find(|(from, _)| *from == c)
+                .map_or(c, |(_, to)| *to)
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+pub struct NormalizeParams {
+    pub text: String,
+}
+
+#[derive(Serialize)]
+pub struct Normalized {
+    pub text: String,
+    pub normalized: String,
+    /// Whether normalization changed anything
+    pub changed: bool,
+}
+
+pub async fn get_normalize(params: Query<NormalizeParams>) -> Json<Normalized> {
+    let normalized = normalize(&params.text);
+    Json(Normalized {
+        changed: normalized != params.text,
+        text: params.0.text,
+        normalized,
+    })
+}
+
+#[test]
+fn test_normalize() {
+    // compatibility ideograph
+    assert_eq!(normalize("\u{F91D}"), "欄");
+    // ideographic variation sequence
+    assert_eq!(normalize("辻\u{E0100}"), "辻");
+    assert_eq!(normalize("葛\u{FE00}"), "葛");
+    // Kangxi and supplement radicals
+    assert_eq!(normalize("\u{2F08}\u{2ED7}"), "人雨");
+    // fullwidth and halfwidth forms
+    assert_eq!(normalize("ｗａｔｅｒ ｶﾀ"), "water カタ");
+    assert_eq!(normalize("日本"), "日本");
+}
@@ -0,0 +1,375 @@
+use model::query::{self, Skip};
+
+use crate::{normalize::normalize, AppError};
+
+/// The largest page of results a client can request
+pub const MAX_COUNT: i64 = 1000;
+
+/// The longest search string accepted, in characters
+pub const MAX_SEARCH_LEN: usize = 256;
+
+/// A validated range of results
+#[derive(Debug, PartialEq)]
+pub struct Page {
+    pub from: u64,
+    pub count: i64,
+}
+
+impl Page {
+    /// Validate the from/count query parameters, falling back to the
+    /// first `default_count` results
+    pub fn new(
+        from: Option<i64>,
+        count: Option<i64>,
+        default_count: i64,
+    ) -> Result<Self, AppError> {
+        let from = from.unwrap_or(0);
+        let count = count.unwrap_or(default_count);
+
+        if from < 0 {
+            return Err(AppError::BadRequest(format!("invalid from {}", from)));
+        }
+        if !(1..=MAX_COUNT).contains(&count) {
+            return Err(AppError::BadRequest(format!(
+                "count must be between 1 and {}",
+                MAX_COUNT
+            )));
+        }
+
+        Ok(Page {
+            from: from as u64,
+            count,
+        })
+    }
+}
+
+/// Validate the name of a dictionary, which is used as part of a field
+/// path and so must not contain dots or operators
+pub fn dict_name(dict: &str) -> Result<&str, AppError> {
+    let valid = !dict.is_empty()
+        && dict.len() <= 32
+        && dict.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_');
+
+    if valid {
+        Ok(dict)
+    } else {
+        Err(AppError::BadRequest(format!(
+            "invalid dictionary {:?}",
+            dict
+        )))
+    }
+}
+
+/// The kinds of query code kanji can be looked up by
+pub const QUERY_CODES: &[&str] = &["skip", "sh_desc", "four_corner", "deroo"];
+
+/// Validate and normalize a query code of the given kind. SKIP codes may
+/// be written with any separator, e.g. 2-3-4, 2_3_4 or ２・３・４, while
+/// the other kinds must be in the form KANJIDIC uses.
+pub fn query_code(qc_type: &str, code: &str) -> Result<String, AppError> {
+    let invalid = || AppError::BadRequest(format!("invalid {} code {:?}", qc_type, code));
+    if !QUERY_CODES.contains(&qc_type) {
+        return Err(AppError::BadRequest(format!(
+            "unknown query code type {:?}, expected one of {}",
+            qc_type,
+            QUERY_CODES.join(", ")
+        )));
+    }
+
+    let normalized = normalize(code.trim());
+    if qc_type == "skip" {
+        let parts: Vec<u8> = normalized
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|p| !p.is_empty())
+            .map(|p| p.parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        return match parts[..] {
+            [category @ 1..=4, strokes1, strokes2] => Ok(Skip {
+                category,
+                strokes1,
+                strokes2,
+            }
+            .to_string()),
+            _ => Err(invalid()),
+        };
+    }
+    if qc_type != "deroo" {
+        return match query::validate(qc_type, &normalized) {
+            Ok(()) => Ok(normalized),
+            Err(_) => Err(invalid()),
+        };
+    }
+
+    let valid = !normalized.is_empty()
+        && normalized.len() <= 16
+        && normalized.bytes().all(|b| b.is_ascii_digit());
+    match valid {
+        true => Ok(normalized),
+        false => Err(invalid()),
+    }
+}
+
+/// Parse a filter on a number, either a single value like 8 or an
+/// inclusive range like 1..6
+pub fn range(name: &str, value: &str) -> Result<(u32, u32), AppError> {
+    let invalid = || AppError::BadRequest(format!("invalid {} {:?}", name, value));
+    let number = |s: &str| s.trim().parse::<u32>().map_err(|_| invalid());
+    let (low, high) = match value.split_once("..") {
+        Some((low, high)) => (number(low)?, number(high)?),
+        None => (number(value)?, number(value)?),
+    };
+    match low <= high {
+        true => Ok((low, high)),
+        false => Err(invalid()),
+    }
+}
+
+/// Validate and normalize a search string
+pub fn search(search: &str) -> Result<String, AppError> {
+    let search = normalize(search.trim());
+    let search = search.trim();
+    if search.is_empty() {
+        return Err(AppError::BadRequest("empty search".into()));
+    }
+    if search.chars().count() > MAX_SEARCH_LEN {
+        return Err(AppError::BadRequest(format!(
+            "search is longer than {} characters",
+            MAX_SEARCH_LEN
+        )));
+    }
+    Ok(search.to_owned())
+}
+
+#[cfg(test)]
+use axum::{
+    body::Body,
+    extract::{Path, Query},
+    http::{Request, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+#[cfg(test)]
+use proptest::prelude::*;
+#[cfg(test)]
+use tower::ServiceExt;
+
+#[cfg(test)]
+use crate::{
+    jmdict,
+    kanji::{DictEntries, DictEntry, ListParams, RandomParams, SearchParams},
+};
+
+#[cfg(test)]
+/// Routes extracting the same parameters as the real handlers and running
+/// their validation, without touching the database
+fn app() -> Router {
+    Router::new()
+        .route(
+            "/kanjidic/search",
+            get(|p: Query<SearchParams>| async move {
+                p.paging()?;
+                p.validate()?;
+                Ok::<_, AppError>(())
+            }),
+        )
+        .route(
+            "/kanjidic/list",
+            get(|p: Query<ListParams>| async move {
+                p.validate()?;
+                Ok::<_, AppError>(())
+            }),
+        )
+        .route(
+            "/kanjidic/random",
+            get(|p: Query<RandomParams>| async move {
+                p.validate()?;
+                Ok::<_, AppError>(())
+            }),
+        )
+        .route(
+            "/kanjidic/dict",
+            get(|p: Query<DictEntries>| async move {
+                p.validate()?;
+                Ok::<_, AppError>(())
+            }),
+        )
+        .route(
+            "/kanjidic/dict/:dict/:entry",
+            get(|p: Path<DictEntry>| async move {
+                p.key()?;
+                Ok::<_, AppError>(())
+            }),
+        )
+        .route(
+            "/kanjidic/:kanji",
+            get(|p: Path<String>| async move { p.0.into_response() }),
+        )
+        .route(
+            "/jmdict/search",
+            get(|p: Query<jmdict::SearchParams>| async move {
+                p.validate()?;
+                Ok::<_, AppError>(())
+            }),
+        )
+        .route(
+            "/jmdict/:seq",
+            get(|p: Path<u32>| async move { p.0.to_string() }),
+        )
+}
+
+#[cfg(test)]
+fn status(uri: &str) -> StatusCode {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app().oneshot(req).await.unwrap().status()
+    })
+}
+
+#[test]
+fn test_malformed_requests() {
+    for uri in [
+        "/kanjidic/search",
+        "/kanjidic/search?search=",
+        "/kanjidic/search?search=water&from=-1",
+        "/kanjidic/search?search=water&from=99999999999999999999",
+        "/kanjidic/search?search=water&count=0",
+        "/kanjidic/search?search=water&count=1000000",
+        "/kanjidic/search?search=water&mode=reading",
+        "/kanjidic/search?search=%E3%81%8B&mode=meaning",
+        "/kanjidic/search?search=fly&fields=meanings,strokes",
+        "/kanjidic/list?grade=6..1",
+        "/kanjidic/list?grade=a",
+        "/kanjidic/list?strokes=-1",
+        "/kanjidic/list?freq_max=%24ne",
+        "/kanjidic/list?sort=radical",
+        "/kanjidic/list?sort=list",
+        "/kanjidic/list?list=a.b",
+        "/kanjidic/list?jlptn=5&count=0",
+        "/kanjidic/random?jlptn=5..a",
+        "/kanjidic/random?count=0",
+        "/kanjidic/random?count=1000000",
+        "/kanjidic/random?freq_max=-1",
+        "/kanjidic/dict?dict=references.ucs",
+        "/kanjidic/dict?dict=%24where",
+        "/kanjidic/dict?dict=rtk&cursor=zz",
+        "/kanjidic/dict?dict=rtk&cursor=&from=10",
+        "/kanjidic/search?search=water&cursor=7b",
+        "/kanjidic/dict/heisig6/-1",
+        "/kanjidic/dict/heisig6/abc",
+        "/kanjidic/dict/a.b/1",
+        "/kanjidic/%FF",
+        "/jmdict/search?search=water",
+        "/jmdict/search?q=%20",
+        "/jmdict/search?q=water&script=latin",
+        "/jmdict/-1",
+        "/jmdict/4294967296",
+    ] {
+        assert!(status(uri).is_client_error(), "{}", uri);
+    }
+
+    assert_eq!(status("/kanjidic/search?search=water"), StatusCode::OK);
+    assert_eq!(
+        status("/kanjidic/search?search=fly&fields=meanings,nanori"),
+        StatusCode::OK
+    );
+    assert_eq!(
+        status("/kanjidic/search?search=kai&mode=reading"),
+        StatusCode::OK
+    );
+    assert_eq!(
+        status("/kanjidic/search?search=%E3%82%AB%E3%82%A4&mode=reading&strip=false"),
+        StatusCode::OK
+    );
+    assert_eq!(status("/kanjidic/list"), StatusCode::OK);
+    assert_eq!(
+        status("/kanjidic/list?grade=1..6&jlptn=5&freq_max=500&strokes=8&sort=freq"),
+        StatusCode::OK
+    );
+    assert_eq!(status("/kanjidic/list?list=n5&sort=list"), StatusCode::OK);
+    assert_eq!(status("/kanjidic/random"), StatusCode::OK);
+    assert_eq!(
+        status("/kanjidic/random?jlptn=5&strokes=1..8&count=20"),
+        StatusCode::OK
+    );
+    assert_eq!(status("/kanjidic/dict/heisig6/12"), StatusCode::OK);
+    assert_eq!(status("/kanjidic/dict?dict=rtk&cursor="), StatusCode::OK);
+    assert_eq!(
+        status("/kanjidic/search?search=water&cursor=5b322e352c22e6b0b4225d"),
+        StatusCode::OK
+    );
+    assert_eq!(status("/jmdict/search?q=water&count=5"), StatusCode::OK);
+    assert_eq!(
+        status("/jmdict/search?q=ko&script=katakana"),
+        StatusCode::OK
+    );
+    assert_eq!(status("/jmdict/1000220"), StatusCode::OK);
+    // invalid UTF-8 is decoded lossily rather than rejected
+    assert_eq!(status("/kanjidic/search?search=%FF%FE"), StatusCode::OK);
+}
+
+#[cfg(test)]
+proptest! {
+    #[test]
+    fn test_page_never_panics(from: Option<i64>, count: Option<i64>) {
+        if let Ok(page) = Page::new(from, count, 10) {
+            prop_assert!(page.count >= 1 && page.count <= MAX_COUNT);
+        }
+    }
+
+    #[test]
+    fn test_search_query_strings(q in "([a-z]{1,6}=([-a-zA-Z0-9.*+]|%[0-9A-Fa-f]{2}|%){0,12}&?){0,4}") {
+        let s = status(&format!("/kanjidic/search?{}", q));
+        prop_assert!(s == StatusCode::OK || s.is_client_error(), "{} {}", q, s);
+    }
+
+    #[test]
+    fn test_dict_paths(dict in "([a-zA-Z0-9_.$]|%[0-9A-F]{2}){0,40}", entry in "-?[0-9a-z]{1,24}") {
+        let s = status(&format!("/kanjidic/dict/{}/{}", dict, entry));
+        prop_assert!(s == StatusCode::OK || s.is_client_error(), "{} {}", dict, s);
+    }
+}
+
+#[test]
+fn test_query_code() {
+    assert_eq!(query_code("skip", "2-3-4").ok().as_deref(), Some("2-3-4"));
+    assert_eq!(
+        query_code("skip", " 2_03_4 ").ok().as_deref(),
+        Some("2-3-4")
+    );
+    assert_eq!(
+        query_code("skip", "２・３・４").ok().as_deref(),
+        Some("2-3-4")
+    );
+    assert!(query_code("skip", "5-3-4").is_err());
+    assert!(query_code("skip", "2-3").is_err());
+    assert!(query_code("skip", "2-3-99999999999").is_err());
+    assert_eq!(
+        query_code("four_corner", "0040.1").ok().as_deref(),
+        Some("0040.1")
+    );
+    assert_eq!(
+        query_code("sh_desc", "３k11.2").ok().as_deref(),
+        Some("3k11.2")
+    );
+    assert!(query_code("sh_desc", "$ne").is_err());
+    assert!(query_code("sh_desc", "3k11").is_err());
+    assert!(query_code("four_corner", "0040").is_err());
+    assert_eq!(query_code("deroo", "1715").ok().as_deref(), Some("1715"));
+    assert!(query_code("nelson", "1").is_err());
+}
+
+#[test]
+fn test_range() {
+    assert_eq!(range("grade", "1..6").ok(), Some((1, 6)));
+    assert_eq!(range("grade", "3").ok(), Some((3, 3)));
+    assert_eq!(range("strokes", " 8 .. 8 ").ok(), Some((8, 8)));
+    assert!(range("grade", "6..1").is_err());
+    assert!(range("grade", "1..").is_err());
+    assert!(range("grade", "..6").is_err());
+    assert!(range("grade", "-1").is_err());
+    assert!(range("grade", "1...6").is_err());
+}
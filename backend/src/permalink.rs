@@ -0,0 +1,118 @@
+use axum::{
+    extract::Path,
+    http::{
+        header::{HeaderName, LINK, LOCATION},
+        HeaderValue, StatusCode,
+    },
+    response::IntoResponse,
+};
+
+use crate::{kanji, AppError};
+
+/// Crockford's base32, without the letters easily mistaken for digits
+const ALPHABET: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+
+/// What a permalink refers to, with the prefix of its code
+#[derive(Debug, PartialEq, Eq)]
+pub enum Permalink {
+    /// A kanji by code point, k
+    Kanji(char),
+    /// A JMdict entry by sequence number, w
+    Word(u32),
+}
+
+impl Permalink {
+    /// The short code of the permalink, e.g. kv1m for 水
+    pub fn encode(&self) -> String {
+        match self {
+            Permalink::Kanji(c) => format!("k{}", base32(*c as u32)),
+            Permalink::Word(seq) => format!("w{}", base32(*seq)),
+        }
+    }
+
+    /// Parse a short code, accepting upper case and the letters Crockford
+    /// reads as digits
+    pub fn decode(code: &str) -> Option<Self> {
+        let mut chars = code.chars();
+        let kind = chars.next()?;
+        let n = unbase32(chars.as_str())?;
+        match kind.to_ascii_lowercase() {
+            'k' => char::from_u32(n).map(Permalink::Kanji),
+            'w' => Some(Permalink::Word(n)),
+            _ => None,
+        }
+    }
+
+    /// A Link header advertising the permalink of an entry
+    pub fn header(&self) -> [(HeaderName, HeaderValue); 1] {
+        let link = format!("</e/{}>; rel=\"shortlink\"", self.encode());
+        [(LINK, HeaderValue::from_str(&link).unwrap())]
+    }
+
+    /// The API path serving the entry
+    pub fn path(&self) -> String {
+        match self {
+            Permalink::Kanji(c) => format!("/kanjidic/{}", kanji::percent_encode(*c)),
+            Permalink::Word(seq) => format!("/jmdict/{}", seq),
+        }
+    }
+}
+
+fn base32(mut n: u32) -> String {
+    let mut out = vec![];
+    loop {
+        out.push(ALPHABET[(n % 32) as usize]);
+        n /= 32;
+        if n == 0 {
+            break;
+        }
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap()
+}
+
+fn unbase32(s: &str) -> Option<u32> {
+    if s.is_empty() || s.len() > 7 {
+        return None;
+    }
+    s.chars().try_fold(0u32, |n, c| {
+        let c = match c.to_ascii_lowercase() {
+            'o' => '0',
+            'i' | 'l' => '1',
+            c => c,
+        };
+        let digit = ALPHABET.iter().position(|&a| a as char == c)? as u32;
+        n.checked_mul(32)?.checked_add(digit)
+    })
+}
+
+/// Redirect a permalink to its entry
+pub async fn get_permalink(Path(code): Path<String>) -> Result<impl IntoResponse, AppError> {
+    let link = Permalink::decode(&code)
+        .ok_or_else(|| AppError::BadRequest(format!("invalid permalink {:?}", code)))?;
+    Ok((
+        StatusCode::FOUND,
+        [(LOCATION, HeaderValue::from_str(&link.path()).unwrap())],
+    ))
+}
+
+#[test]
+fn test_permalink() {
+    assert_eq!(Permalink::Kanji('水').encode(), "kv1m");
+    assert_eq!(Permalink::Word(1000220).encode(), "wygrw");
+    for link in [
+        Permalink::Kanji('水'),
+        Permalink::Kanji('𠀋'),
+        Permalink::Word(0),
+        Permalink::Word(u32::MAX),
+    ] {
+        assert_eq!(Permalink::decode(&link.encode()), Some(link));
+    }
+    assert_eq!(Permalink::decode("KV1M"), Some(Permalink::Kanji('水')));
+    assert_eq!(Permalink::decode("wO"), Some(Permalink::Word(0)));
+    assert_eq!(Permalink::Kanji('水').path(), "/kanjidic/%E6%B0%B4");
+
+    for code in ["", "k", "xv1m", "kv1u", "w7zzzzzz", "kzzzzzz"] {
+        assert_eq!(Permalink::decode(code), None, "{}", code);
+    }
+}
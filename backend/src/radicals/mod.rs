@@ -0,0 +1,86 @@
+use std::collections::BTreeSet;
+
+use axum::{extract::Query, Extension, Json};
+use futures::TryStreamExt;
+use model::krad::Decomposition;
+use mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, Database};
+
+#[derive(Deserialize)]
+pub struct NarrowParams {
+    /// The selected radicals, optionally separated by commas
+    pub selected: String,
+}
+
+#[derive(Serialize)]
+pub struct Narrowed {
+    /// Kanji containing every selected radical
+    pub kanji: Vec<char>,
+    /// Radicals that can still be added to the selection, including the
+    /// selected radicals themselves
+    pub radicals: Vec<char>,
+}
+
+pub async fn get_narrow(
+    params: Query<NarrowParams>,
+    db: Extension<Database>,
+) -> Result<Json<Narrowed>, AppError> {
+    let selected: Vec<String> = params
+        .selected
+        .chars()
+        .filter(|c| *c != ',' && !c.is_whitespace())
+        .map(|c| c.to_string())
+        .collect();
+
+    if selected.is_empty() {
+        return Err(AppError::BadRequest("no radicals selected".into()));
+    }
+
+    let found: Vec<Decomposition> = db
+        .collection::<Decomposition>("krad")
+        .find(doc! { "radicals": { "$all": selected } }, None)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(Json(narrow(found)))
+}
+
+/// Collect the matching kanji and every radical they contain
+fn narrow(found: Vec<Decomposition>) -> Narrowed {
+    let mut kanji = BTreeSet::new();
+    let mut radicals = BTreeSet::new();
+
+    for d in found {
+        kanji.insert(d.kanji);
+        radicals.extend(d.radicals);
+    }
+
+    Narrowed {
+        kanji: kanji.into_iter().collect(),
+        radicals: radicals.into_iter().collect(),
+    }
+}
+
+#[test]
+fn test_narrow() {
+    let found = vec![
+        Decomposition {
+            kanji: '沐',
+            radicals: vec!['氵', '木'],
+        },
+        Decomposition {
+            kanji: '淋',
+            radicals: vec!['氵', '木'],
+        },
+        Decomposition {
+            kanji: '渠',
+            radicals: vec!['氵', '木', '匚', 'コ'],
+        },
+    ];
+    let out = narrow(found);
+    assert_eq!(out.kanji.into_iter().collect::<String>(), "沐淋渠");
+    assert_eq!(out.radicals.into_iter().collect::<String>(), "コ匚木氵");
+}
@@ -0,0 +1,279 @@
+//! Per-client rate limiting with a token bucket for each IP address.
+//! Each request takes a token; buckets refill at the configured rate up
+//! to the burst size, and requests finding theirs empty get a 429 with a
+//! Retry-After header. Buckets which have refilled are swept out on a
+//! timer, and the least recently used is evicted for a new client when
+//! too many are tracked.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::ConnectInfo,
+    http::{header::RETRY_AFTER, HeaderValue, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::AppError;
+
+/// How many clients are tracked before the least recently used is
+/// forgotten
+const MAX_CLIENTS: usize = 10_000;
+
+/// How often buckets which have refilled are forgotten
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    /// The sustained rate allowed per client
+    pub per_minute: u32,
+    /// How many requests a client can make at once
+    pub burst: u32,
+    /// Identify clients by the last X-Forwarded-For address, when served
+    /// behind a proxy or API gateway which appends it
+    pub trust_forwarded: bool,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Default)]
+struct Buckets {
+    by_ip: HashMap<IpAddr, Bucket>,
+    /// The clients by when their bucket was last used, oldest first
+    by_age: BTreeSet<(Instant, IpAddr)>,
+}
+
+pub struct Limiter {
+    limit: RateLimit,
+    buckets: Mutex<Buckets>,
+}
+
+impl Limiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Limiter {
+            limit,
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    /// How long an unused bucket takes to refill from empty
+    fn refill_time(&self) -> Duration {
+        let rate = self.limit.per_minute as f64 / 60.0;
+        Duration::from_secs_f64(self.limit.burst as f64 / rate)
+    }
+
+    /// Forget the buckets which have refilled by `now`, which are the
+    /// same as new ones
+    pub fn sweep(&self, now: Instant) {
+        let refill = self.refill_time();
+        let mut buckets = self.buckets.lock().unwrap();
+        while let Some(&(updated, ip)) = buckets.by_age.first() {
+            if now.saturating_duration_since(updated) < refill {
+                break;
+            }
+            buckets.by_age.pop_first();
+            buckets.by_ip.remove(&ip);
+        }
+    }
+
+    /// Sweep the buckets every [`SWEEP_INTERVAL`]
+    pub fn spawn_sweeper(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                timer.tick().await;
+                self.sweep(Instant::now());
+            }
+        });
+    }
+
+    /// The tokens of a bucket after refilling it until `now`
+    fn level(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let rate = self.limit.per_minute as f64 / 60.0;
+        let refilled = now.saturating_duration_since(bucket.updated).as_secs_f64() * rate;
+        (bucket.tokens + refilled).min(self.limit.burst as f64)
+    }
+
+    /// Take a token for a request from `ip`, or return how long until one
+    /// is available
+    pub fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let burst = self.limit.burst as f64;
+        let mut guard = self.buckets.lock().unwrap();
+        let buckets = &mut *guard;
+        if !buckets.by_ip.contains_key(&ip) && buckets.by_ip.len() >= MAX_CLIENTS {
+            if let Some((_, oldest)) = buckets.by_age.pop_first() {
+                buckets.by_ip.remove(&oldest);
+            }
+        }
+
+        let bucket = buckets.by_ip.entry(ip).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        buckets.by_age.remove(&(bucket.updated, ip));
+        bucket.tokens = self.level(bucket, now);
+        bucket.updated = bucket.updated.max(now);
+        buckets.by_age.insert((bucket.updated, ip));
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let rate = self.limit.per_minute as f64 / 60.0;
+        Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+    }
+}
+
+/// The address of the client making a request, if known. Only the last
+/// X-Forwarded-For address is trusted, as the one added by the proxy:
+/// the others are whatever the client sent.
+fn client_ip<B>(req: &Request<B>, trust_forwarded: bool) -> Option<IpAddr> {
+    let forwarded = req
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.rsplit(',').next())
+        .and_then(|ip| ip.trim().parse().ok());
+    match forwarded {
+        Some(ip) if trust_forwarded => Some(ip),
+        _ => req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|c| c.0.ip()),
+    }
+}
+
+/// Reject requests over the limit of their client. Requests from unknown
+/// clients are let through.
+pub async fn limit<B>(limiter: Arc<Limiter>, req: Request<B>, next: Next<B>) -> Response {
+    let Some(ip) = client_ip(&req, limiter.limit.trust_forwarded) else {
+        return next.run(req).await;
+    };
+    match limiter.check(ip, Instant::now()) {
+        Ok(()) => next.run(req).await,
+        Err(wait) => {
+            let seconds = wait.as_secs_f64().ceil().max(1.0) as u64;
+            let mut res =
+                AppError::RateLimited(format!("too many requests, retry in {} seconds", seconds))
+                    .into_response();
+            res.headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+            res
+        }
+    }
+}
+
+#[test]
+fn test_check() {
+    let limiter = Limiter::new(RateLimit {
+        per_minute: 60,
+        burst: 2,
+        trust_forwarded: false,
+    });
+    let a: IpAddr = "192.0.2.1".parse().unwrap();
+    let b: IpAddr = "192.0.2.2".parse().unwrap();
+    let start = Instant::now();
+
+    assert_eq!(limiter.check(a, start), Ok(()));
+    assert_eq!(limiter.check(a, start), Ok(()));
+    assert_eq!(limiter.check(a, start), Err(Duration::from_secs(1)));
+    // other clients have their own bucket
+    assert_eq!(limiter.check(b, start), Ok(()));
+    // one token a second refills
+    let later = start + Duration::from_millis(1500);
+    assert_eq!(limiter.check(a, later), Ok(()));
+    assert!(limiter.check(a, later).is_err());
+    // up to the burst size
+    let much_later = start + Duration::from_secs(600);
+    assert_eq!(limiter.check(a, much_later), Ok(()));
+    assert_eq!(limiter.check(a, much_later), Ok(()));
+    assert!(limiter.check(a, much_later).is_err());
+}
+
+#[test]
+fn test_eviction() {
+    let limiter = Limiter::new(RateLimit {
+        per_minute: 60,
+        burst: 2,
+        trust_forwarded: false,
+    });
+    let ip = |n: usize| IpAddr::from((n as u32).to_be_bytes());
+    let start = Instant::now();
+    let len = || limiter.buckets.lock().unwrap().by_ip.len();
+
+    for n in 0..MAX_CLIENTS {
+        limiter.check(ip(n), start).unwrap();
+    }
+    // the least recently used client makes room for a new one
+    limiter
+        .check(ip(0), start + Duration::from_millis(1))
+        .unwrap();
+    limiter
+        .check(ip(MAX_CLIENTS), start + Duration::from_millis(2))
+        .unwrap();
+    assert_eq!(len(), MAX_CLIENTS);
+    let buckets = limiter.buckets.lock().unwrap();
+    assert!(buckets.by_ip.contains_key(&ip(0)));
+    assert!(!buckets.by_ip.contains_key(&ip(1)));
+    assert_eq!(buckets.by_age.len(), MAX_CLIENTS);
+    drop(buckets);
+
+    // buckets refill in two seconds, and are swept out after
+    limiter.sweep(start + Duration::from_secs(1));
+    assert_eq!(len(), MAX_CLIENTS);
+    limiter.sweep(start + Duration::from_millis(2001));
+    assert_eq!(len(), 1);
+    limiter.sweep(start + Duration::from_secs(3));
+    assert_eq!(len(), 0);
+}
+
+#[tokio::test]
+async fn test_limit() {
+    use axum::{body::Body, http::StatusCode, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    let limiter = Arc::new(Limiter::new(RateLimit {
+        per_minute: 1,
+        burst: 1,
+        trust_forwarded: true,
+    }));
+    let app = Router::new()
+        .route("/", get(|| async { "ok" }))
+        .layer(middleware::from_fn(move |req, next| {
+            limit(limiter.clone(), req, next)
+        }));
+
+    let request = |forwarded: &str| {
+        let mut req = Request::builder().uri("/");
+        if !forwarded.is_empty() {
+            req = req.header("x-forwarded-for", forwarded);
+        }
+        let mut req = req.body(Body::empty()).unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4000))));
+        req
+    };
+
+    let res = app.clone().oneshot(request("")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = app.clone().oneshot(request("")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(res.headers()[RETRY_AFTER], "60");
+    // a trusted proxy forwarding for another client
+    let res = app.clone().oneshot(request("198.51.100.7")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    // addresses the client sent itself are ignored
+    let res = app
+        .clone()
+        .oneshot(request("203.0.113.9, 198.51.100.7"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+}
@@ -0,0 +1,71 @@
+//! The entries of the dictionaries as parsed, before conversion, which
+//! populate stores with `--raw` for debugging data issues
+
+use axum::{
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use mongodb::bson::Document;
+use serde::Serialize;
+
+use crate::{
+    admin::{self, AdminToken, DebugMode},
+    AppError, Database,
+};
+
+/// A response with the raw source record of its entry
+#[derive(Serialize)]
+pub struct WithRaw<T> {
+    #[serde(flatten)]
+    pub entry: T,
+    /// The record as parsed, null if it was not stored
+    pub raw: Option<Document>,
+}
+
+/// Find the raw record of an entry in `collection`, after checking the
+/// request may see it
+pub async fn find(
+    headers: &HeaderMap,
+    debug: DebugMode,
+    token: Option<Extension<AdminToken>>,
+    db: &Database,
+    collection: &str,
+    filter: Document,
+) -> Result<Option<Document>, AppError> {
+    admin::authorize_debug(headers, debug, token.as_deref())?;
+    let found = db
+        .collection::<Document>(collection)
+        .find_one(filter, None)
+        .await?;
+    Ok(found.and_then(|d| d.get_document("raw").ok().cloned()))
+}
+
+/// Respond with an entry, and its raw record when one was asked for
+pub fn json<T: Serialize>(entry: T, raw: Option<Option<Document>>) -> Response {
+    match raw {
+        Some(raw) => Json(WithRaw { entry, raw }).into_response(),
+        None => Json(entry).into_response(),
+    }
+}
+
+#[cfg(test)]
+use mongodb::bson::doc;
+
+#[test]
+fn test_with_raw() {
+    let out = serde_json::to_value(WithRaw {
+        entry: doc! { "literal": "水" },
+        raw: Some(doc! { "literal": "水", "grade": 1 }),
+    })
+    .unwrap();
+    assert_eq!(out["literal"], "水");
+    assert_eq!(out["raw"]["grade"], 1);
+
+    let out = serde_json::to_value(WithRaw {
+        entry: doc! { "literal": "水" },
+        raw: None,
+    })
+    .unwrap();
+    assert!(out["raw"].is_null());
+}
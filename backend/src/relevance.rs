@@ -0,0 +1,441 @@
+//! Tunable weights for ranking search results. Weights are read from the
+//! JSON file named by `SEARCH_WEIGHTS`, and can be reloaded from it
+//! without a restart through the admin endpoint.
+
+use std::{
+    fmt, io,
+    sync::{Arc, RwLock},
+};
+
+use axum::{http::HeaderMap, Extension, Json};
+use mongodb::bson::{doc, Bson, Document, Regex};
+use serde::{Deserialize, Serialize};
+
+use model::kana;
+
+use crate::{
+    admin::{self, AdminToken},
+    AppError,
+};
+
+/// How much each kind of match adds to the score of a result
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Weights {
+    /// The search is the kanji itself
+    pub literal: f64,
+    /// The search is one of the on or kun readings
+    pub reading: f64,
+    /// The search is one of the meanings
+    pub meaning: f64,
+    /// The search appears in one of the meanings
+    pub meaning_partial: f64,
+    /// Scaled by the text search score of the meanings, which matches the
+    /// words of the search in any form, e.g. fly in "to fly; flight"
+    pub text: f64,
+    /// The search is one of the readings only used in names
+    pub nanori: f64,
+    /// Scaled by the newspaper frequency rank, from 1 for the most
+    /// frequent kanji to 0 for unranked ones
+    pub frequency: f64,
+    /// Scaled by the estimated JLPT level, from 1 for N5 to 0.2 for N1
+    pub jlpt: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            literal: 100.0,
+            reading: 10.0,
+            meaning: 10.0,
+            meaning_partial: 2.0,
+            text: 10.0,
+            nanori: 5.0,
+            frequency: 1.0,
+            jlpt: 1.0,
+        }
+    }
+}
+
+/// The weights in use, shared between requests
+pub type Shared = Arc<RwLock<Weights>>;
+
+/// Where weights are loaded from
+#[derive(Clone)]
+pub struct Config {
+    pub path: Option<String>,
+}
+
+/// Why weights could not be loaded from a file
+#[derive(Debug)]
+pub enum LoadError {
+    Read(String, io::Error),
+    Parse(String, serde_json::Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Read(path, e) => write!(f, "can't read {}: {}", path, e),
+            LoadError::Parse(path, e) => write!(f, "invalid weights in {}: {}", path, e),
+        }
+    }
+}
+
+impl From<LoadError> for AppError {
+    fn from(e: LoadError) -> Self {
+        // don't leak file paths to clients
+        tracing::error!("search weights: {}", e);
+        AppError::InvalidWeights(match e {
+            LoadError::Read(..) => "search weights can't be read".into(),
+            LoadError::Parse(_, e) => format!("invalid search weights: {}", e),
+        })
+    }
+}
+
+/// Read weights from a file, or the defaults if there is none
+pub fn load(path: Option<&str>) -> Result<Weights, LoadError> {
+    let Some(path) = path else {
+        return Ok(Weights::default());
+    };
+    let json = std::fs::read(path).map_err(|e| LoadError::Read(path.to_owned(), e))?;
+    serde_json::from_slice(&json).map_err(|e| LoadError::Parse(path.to_owned(), e))
+}
+
+/// Escape a string for literal use in a regular expression
+pub fn escape_regex(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The fields a search matches besides the literal, selected with e.g.
+/// `fields=meanings,readings`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fields {
+    pub meanings: bool,
+    /// The on and kun readings
+    pub readings: bool,
+    pub nanori: bool,
+}
+
+impl Fields {
+    pub const ALL: Fields = Fields {
+        meanings: true,
+        readings: true,
+        nanori: true,
+    };
+
+    /// Parse a comma separated list of fields, every field if unset
+    pub fn parse(list: Option<&str>) -> Result<Self, AppError> {
+        let Some(list) = list else {
+            return Ok(Fields::ALL);
+        };
+        let mut fields = Fields {
+            meanings: false,
+            readings: false,
+            nanori: false,
+        };
+        for field in list.split(',').map(str::trim) {
+            match field {
+                "meanings" => fields.meanings = true,
+                "readings" => fields.readings = true,
+                "nanori" => fields.nanori = true,
+                _ => {
+                    return Err(AppError::BadRequest(format!(
+                        "unknown search field {:?}, expected meanings, readings or nanori",
+                        field
+                    )))
+                }
+            }
+        }
+        Ok(fields)
+    }
+}
+
+/// Whether to search the meanings with the text index first, which only
+/// knows about words written in latin letters
+pub fn wants_text(search: &str, fields: Fields) -> bool {
+    fields.meanings && search.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+/// The filter of every entry matching a search in any way
+pub fn filter(search: &str, fields: Fields) -> Document {
+    let mut or = vec![doc! { "literal": search }];
+    if fields.readings {
+        or.push(doc! { "on_readings": search });
+        or.push(doc! { "kun_readings": search });
+    }
+    if fields.nanori {
+        or.push(doc! { "nanoris": search });
+    }
+    if fields.meanings {
+        or.push(doc! { "meanings": { "$regex": escape_regex(search), "$options": "i" } });
+    }
+    doc! { "$or": or }
+}
+
+/// The filter of the entries whose meanings contain the words of a
+/// search, through the text index of the meanings
+pub fn text_filter(search: &str) -> Document {
+    doc! { "$text": { "$search": search } }
+}
+
+/// An expression computing the score of an entry found by a text search
+pub fn text_score(weights: &Weights, search: &str) -> Document {
+    doc! { "$add": [
+        { "$multiply": [weights.text, { "$meta": "textScore" }] },
+        { "$cond": [
+            { "$in": [search, { "$ifNull": ["$meanings", []] }] },
+            weights.meaning,
+            0,
+        ]},
+        popularity(weights),
+    ]}
+}
+
+/// A search for kanji by reading, matching katakana on readings and
+/// hiragana kun readings whichever kana, or romaji, the search is
+/// written in
+pub struct Reading {
+    /// The search in katakana, as on readings are written
+    pub on: String,
+    /// The search in hiragana, as kun readings are written
+    pub kun: String,
+    /// Ignore the okurigana separator (.) and the prefix and suffix
+    /// markers (-) of readings, so あげる finds あ.げる
+    pub strip: bool,
+}
+
+impl Reading {
+    pub fn new(search: &str, strip: bool) -> Result<Self, AppError> {
+        // typed romaji such as kai or zi
+        let romaji = match search.is_ascii() {
+            true => kana::from_romaji(search),
+            false => None,
+        };
+        let search = romaji.as_deref().unwrap_or(search);
+        let search: String = match strip {
+            true => search.chars().filter(|c| *c != '.' && *c != '-').collect(),
+            false => search.to_owned(),
+        };
+        if !search
+            .chars()
+            .all(|c| kana::is_kana(c) || c == '.' || c == '-')
+        {
+            return Err(AppError::BadRequest(format!(
+                "reading search {:?} is not kana",
+                search
+            )));
+        }
+        Ok(Reading {
+            on: kana::to_katakana(&search),
+            kun: kana::to_hiragana(&search),
+            strip,
+        })
+    }
+
+    fn pattern(&self, reading: &str) -> Bson {
+        match self.strip {
+            true => {
+                let chars: Vec<String> = reading
+                    .chars()
+                    .map(|c| escape_regex(&c.to_string()))
+                    .collect();
+                let regex = format!("^-?{}-?$", chars.join(r"\.?"));
+                Bson::RegularExpression(Regex {
+                    pattern: regex,
+                    options: String::new(),
+                })
+            }
+            false => reading.into(),
+        }
+    }
+
+    /// The filter of every entry with the reading
+    pub fn filter(&self) -> Document {
+        doc! { "$or": [
+            { "on_readings": self.pattern(&self.on) },
+            { "kun_readings": self.pattern(&self.kun) },
+        ]}
+    }
+
+    /// An expression computing the score of an entry. Readings matching
+    /// without stripping anything rank first.
+    pub fn score(&self, weights: &Weights) -> Document {
+        doc! { "$add": [
+            { "$cond": [
+                { "$or": [
+                    { "$in": [&self.on, { "$ifNull": ["$on_readings", []] }] },
+                    { "$in": [&self.kun, { "$ifNull": ["$kun_readings", []] }] },
+                ]},
+                weights.reading,
+                0,
+            ]},
+            popularity(weights),
+        ]}
+    }
+}
+
+/// The part of the score independent of the search
+fn popularity(weights: &Weights) -> Document {
+    doc! { "$add": [
+        { "$multiply": [
+            weights.frequency,
+            { "$divide": [{ "$subtract": [2501, { "$ifNull": ["$info.freq", 2501] }] }, 2500] },
+        ]},
+        { "$multiply": [
+            weights.jlpt,
+            { "$divide": [{ "$ifNull": ["$info.jlptn", 0] }, 5] },
+        ]},
+    ]}
+}
+
+/// An expression computing the score of an entry for a search. Fields
+/// which aren't searched don't add to it.
+pub fn score(weights: &Weights, search: &str, fields: Fields) -> Document {
+    let weight = |selected: bool, weight: f64| if selected { weight } else { 0.0 };
+    doc! { "$add": [
+        { "$cond": [{ "$eq": ["$literal", search] }, weights.literal, 0] },
+        { "$cond": [
+            { "$in": [search, { "$concatArrays": [
+                { "$ifNull": ["$on_readings", []] },
+                { "$ifNull": ["$kun_readings", []] },
+            ]}]},
+            weight(fields.readings, weights.reading),
+            0,
+        ]},
+        { "$cond": [
+            { "$in": [search, { "$ifNull": ["$nanoris", []] }] },
+            weight(fields.nanori, weights.nanori),
+            0,
+        ]},
+        { "$cond": [
+            { "$in": [search, { "$ifNull": ["$meanings", []] }] },
+            weight(fields.meanings, weights.meaning),
+            0,
+        ]},
+        { "$cond": [
+            { "$anyElementTrue": [{ "$map": {
+                "input": { "$ifNull": ["$meanings", []] },
+                "as": "m",
+                "in": { "$regexMatch": {
+                    "input": "$$m",
+                    "regex": escape_regex(search),
+                    "options": "i",
+                }},
+            }}]},
+            weight(fields.meanings, weights.meaning_partial),
+            0,
+        ]},
+        popularity(weights),
+    ]}
+}
+
+pub async fn get_weights(
+    headers: HeaderMap,
+    weights: Extension<Shared>,
+    token: Extension<AdminToken>,
+) -> Result<Json<Weights>, AppError> {
+    admin::authorize(&headers, &token)?;
+    Ok(Json(weights.read().unwrap().clone()))
+}
+
+/// Reload the weights from the configured file
+pub async fn post_reload(
+    headers: HeaderMap,
+    weights: Extension<Shared>,
+    config: Extension<Config>,
+    token: Extension<AdminToken>,
+) -> Result<Json<Weights>, AppError> {
+    admin::authorize(&headers, &token)?;
+
+    let loaded = load(config.path.as_deref())?;
+    tracing::info!("reloaded search weights: {:?}", loaded);
+    *weights.write().unwrap() = loaded.clone();
+    Ok(Json(loaded))
+}
+
+#[test]
+fn test_weights() {
+    let w: Weights = serde_json::from_str(r#"{ "literal": 5, "jlpt": 0 }"#).unwrap();
+    assert_eq!(w.literal, 5.0);
+    assert_eq!(w.jlpt, 0.0);
+    assert_eq!(w.reading, Weights::default().reading);
+
+    assert_eq!(load(None).unwrap(), Weights::default());
+    let e = load(Some("/nonexistent/weights.json")).unwrap_err();
+    assert!(e
+        .to_string()
+        .starts_with("can't read /nonexistent/weights.json: "));
+    let e = AppError::from(e);
+    assert_eq!(e.code(), crate::errors::ErrorCode::InvalidWeights);
+
+    assert_eq!(escape_regex("a.b(c)"), r"a\.b\(c\)");
+    let f = filter("wa+ter", Fields::ALL);
+    let or = f.get_array("$or").unwrap();
+    assert_eq!(or.len(), 5);
+}
+
+#[test]
+fn test_fields() {
+    assert_eq!(Fields::parse(None).ok(), Some(Fields::ALL));
+    let fields = Fields::parse(Some("meanings, nanori")).ok().unwrap();
+    assert!(fields.meanings && fields.nanori && !fields.readings);
+    assert!(Fields::parse(Some("meanings,strokes")).is_err());
+    assert!(Fields::parse(Some("")).is_err());
+
+    let f = filter("みず", Fields::parse(Some("readings")).ok().unwrap());
+    let or = f.get_array("$or").unwrap();
+    assert_eq!(or.len(), 3);
+    assert!(or
+        .iter()
+        .all(|c| !c.as_document().unwrap().contains_key("meanings")));
+
+    assert!(wants_text("fly", Fields::ALL));
+    assert!(!wants_text("みず", Fields::ALL));
+    let readings = Fields {
+        meanings: false,
+        ..Fields::ALL
+    };
+    assert!(!wants_text("fly", readings));
+    assert_eq!(
+        text_filter("fly")
+            .get_document("$text")
+            .unwrap()
+            .get_str("$search"),
+        Ok("fly")
+    );
+}
+
+#[test]
+fn test_reading() {
+    let r = Reading::new("かい", true).ok().unwrap();
+    assert_eq!((r.on.as_str(), r.kun.as_str()), ("カイ", "かい"));
+    let f = r.filter();
+    let or = f.get_array("$or").unwrap();
+    let on = or[0].as_document().unwrap().get("on_readings").unwrap();
+    match on {
+        Bson::RegularExpression(re) => assert_eq!(re.pattern, r"^-?カ\.?イ-?$"),
+        _ => panic!("expected a regex, got {}", on),
+    }
+
+    let r = Reading::new("カイ", false).ok().unwrap();
+    assert_eq!(r.kun, "かい");
+    let f = r.filter();
+    let kun = f.get_array("$or").unwrap()[1]
+        .as_document()
+        .unwrap()
+        .clone();
+    assert_eq!(kun.get_str("kun_readings"), Ok("かい"));
+
+    assert_eq!(Reading::new("あ.げる", true).ok().unwrap().kun, "あげる");
+    assert_eq!(Reading::new("kai", true).ok().unwrap().on, "カイ");
+    assert_eq!(Reading::new("zi", true).ok().unwrap().kun, "じ");
+    assert!(Reading::new("water", true).is_err());
+}
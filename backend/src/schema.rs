@@ -0,0 +1,47 @@
+//! The startup check that the database holds documents of the schema
+//! version this build reads, as recorded by `populate migrate`.
+
+use model::meta::{SchemaVersion, SCHEMA_KIND, SCHEMA_VERSION};
+use mongodb::bson::doc;
+
+/// Why a stored schema version can't be served, if it can't
+fn mismatch(stored: u32) -> Option<String> {
+    match stored {
+        v if v == SCHEMA_VERSION => None,
+        v if v < SCHEMA_VERSION => Some(format!(
+            "database schema version {} is older than {}, run `populate migrate`",
+            v, SCHEMA_VERSION
+        )),
+        v => Some(format!(
+            "database schema version {} is newer than {}, update the backend",
+            v, SCHEMA_VERSION
+        )),
+    }
+}
+
+/// Check the schema version of a database. An unreachable database only
+/// logs a warning, requests fail on their own until it is back.
+pub async fn check(db: &mongodb::Database) -> Result<(), String> {
+    let stored = match db
+        .collection::<SchemaVersion>("meta")
+        .find_one(doc! { "kind": SCHEMA_KIND }, None)
+        .await
+    {
+        Ok(stored) => stored.map_or(0, |s| s.version),
+        Err(e) => {
+            tracing::warn!("could not check the schema version: {}", e);
+            return Ok(());
+        }
+    };
+    match mismatch(stored) {
+        Some(reason) => Err(reason),
+        None => Ok(()),
+    }
+}
+
+#[test]
+fn test_mismatch() {
+    assert_eq!(mismatch(SCHEMA_VERSION), None);
+    assert!(mismatch(0).unwrap().contains("populate migrate"));
+    assert!(mismatch(SCHEMA_VERSION + 1).unwrap().contains("newer"));
+}
@@ -0,0 +1,72 @@
+//! Responses serialized one item at a time as they are read from the
+//! database, instead of collecting whole result sets in memory first.
+
+use axum::{
+    body::{Bytes, StreamBody},
+    http::{header::CONTENT_TYPE, HeaderValue},
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use futures::{stream, Stream, StreamExt};
+use serde::Serialize;
+
+/// A JSON array written to a chunked body as the stream yields items.
+///
+/// The status is sent before the first item is read, so an error part
+/// way through can only be reported by aborting the body. Anything that
+/// can fail before the first item, like building the query, should be
+/// checked before returning this.
+pub struct JsonArray<S>(pub S);
+
+impl<S, T, E> IntoResponse for JsonArray<S>
+where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Serialize,
+    E: Into<BoxError>,
+{
+    fn into_response(self) -> Response {
+        let items = self.0.enumerate().map(|(i, item)| {
+            let item = item.map_err(Into::into).map_err(|e| {
+                tracing::error!("failed streaming response: {}", e);
+                e
+            })?;
+            let mut buf = if i == 0 { vec![] } else { vec![b','] };
+            serde_json::to_writer(&mut buf, &item)?;
+            Ok::<_, BoxError>(Bytes::from(buf))
+        });
+
+        let body = stream::once(async { Ok(Bytes::from_static(b"[")) })
+            .chain(items)
+            .chain(stream::once(async { Ok(Bytes::from_static(b"]")) }));
+
+        let mut res = StreamBody::new(body).into_response();
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        res
+    }
+}
+
+#[cfg(test)]
+async fn body_of<S: Stream<Item = Result<u32, std::io::Error>> + Send + 'static>(
+    items: S,
+) -> Result<String, BoxError> {
+    use axum::body::HttpBody;
+
+    let mut body = JsonArray(items).into_response().into_body();
+    let mut out = vec![];
+    while let Some(chunk) = body.data().await {
+        out.extend_from_slice(&chunk?);
+    }
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[tokio::test]
+async fn test_json_array() {
+    let ok = |v: Vec<u32>| stream::iter(v.into_iter().map(Ok));
+    assert_eq!(body_of(ok(vec![])).await.unwrap(), "[]");
+    assert_eq!(body_of(ok(vec![1])).await.unwrap(), "[1]");
+    assert_eq!(body_of(ok(vec![1, 2, 3])).await.unwrap(), "[1,2,3]");
+
+    let failing = stream::iter([Ok(1), Err(std::io::Error::other("lost"))]);
+    assert!(body_of(failing).await.is_err());
+}
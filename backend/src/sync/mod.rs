@@ -0,0 +1,194 @@
+use std::collections::BTreeSet;
+
+use axum::{extract::Query, Extension, Json};
+use futures::TryStreamExt;
+use model::{changelog::Change, kanji::Kanji};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    options::{FindOneOptions, FindOptions},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{params::Page, AppError, Database};
+
+#[derive(Deserialize)]
+pub struct SyncParams {
+    /// The version the client holds, as returned by an earlier sync.
+    /// Omit to download every entry.
+    pub since_version: Option<String>,
+    pub from: Option<i64>,
+    pub count: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct SyncResponse {
+    /// The latest version known to the server, if anything has been loaded
+    pub version: Option<String>,
+    /// The total number of changed entries across all pages
+    pub total: usize,
+    /// One page of added or changed entries
+    pub changed: Vec<Kanji>,
+    /// Every literal removed since the requested version
+    pub removed: Vec<char>,
+}
+
+impl SyncResponse {
+    /// Nothing changed since the client's version, which is the latest
+    fn up_to_date(version: Option<String>) -> Self {
+        SyncResponse {
+            version,
+            total: 0,
+            changed: vec![],
+            removed: vec![],
+        }
+    }
+}
+
+/// The latest load recorded in the changelog, of `version` if given, with
+/// its id and version. Ids increase with every load, so loads are ordered
+/// by them rather than by comparing version strings.
+async fn latest_load(
+    db: &Database,
+    version: Option<&str>,
+) -> Result<Option<(ObjectId, String)>, AppError> {
+    let options = FindOneOptions::builder()
+        .sort(doc! { "_id": -1 })
+        .projection(doc! { "version": 1 })
+        .build();
+    let filter = version.map(|v| doc! { "version": v });
+    let latest = db
+        .collection::<Document>("changelog")
+        .find_one(filter, options)
+        .await?;
+    Ok(latest.and_then(|d| {
+        let id = d.get_object_id("_id").ok()?;
+        Some((id, d.get_str("version").ok()?.to_owned()))
+    }))
+}
+
+/// One page of every entry, for clients without a local copy. The
+/// changelog can't be replayed for this, as it may not cover the first
+/// load.
+async fn full_download(
+    db: &Database,
+    version: Option<String>,
+    page: Page,
+) -> Result<SyncResponse, AppError> {
+    let con = db.collection::<Kanji>("kanjidic");
+    let total = con.count_documents(None, None).await?;
+    let find_options = FindOptions::builder()
+        .sort(doc! { "literal": 1 })
+        .skip(page.from)
+        .limit(page.count)
+        .build();
+    let changed = con.find(None, find_options).await?.try_collect().await?;
+    Ok(SyncResponse {
+        version,
+        total: total as usize,
+        changed,
+        removed: vec![],
+    })
+}
+
+pub async fn get_sync(
+    params: Query<SyncParams>,
+    db: Extension<Database>,
+) -> Result<Json<SyncResponse>, AppError> {
+    let page = Page::new(params.from, params.count, 100)?;
+    let version = latest_load(&db, None).await?.map(|(_, v)| v);
+    let Some(since) = params.since_version.as_deref() else {
+        return Ok(Json(full_download(&db, version, page).await?));
+    };
+    let (since, _) = latest_load(&db, Some(since))
+        .await?
+        .ok_or_else(|| AppError::BadRequest(format!("unknown version {:?}", since)))?;
+
+    // only the loads after the client's are read
+    let find_options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+    let changes: Vec<Change> = db
+        .collection::<Change>("changelog")
+        .find(doc! { "_id": { "$gt": since } }, find_options)
+        .await?
+        .try_collect()
+        .await?;
+    if changes.is_empty() {
+        return Ok(Json(SyncResponse::up_to_date(version)));
+    }
+
+    let (changed, removed) = fold_changes(changes);
+
+    let page: Vec<String> = changed
+        .iter()
+        .skip(page.from as usize)
+        .take(page.count as usize)
+        .map(|c| c.to_string())
+        .collect();
+
+    let entries = if page.is_empty() {
+        vec![]
+    } else {
+        let find_options = FindOptions::builder().sort(doc! { "literal": 1 }).build();
+        db.collection::<Kanji>("kanjidic")
+            .find(doc! { "literal": { "$in": page } }, find_options)
+            .await?
+            .try_collect()
+            .await?
+    };
+
+    Ok(Json(SyncResponse {
+        version,
+        total: changed.len(),
+        changed: entries,
+        removed: removed.into_iter().collect(),
+    }))
+}
+
+/// Collapse a list of changes in load order into the final sets of
+/// changed and removed literals. Later changes win, so a literal removed
+/// and then added again is only reported as changed.
+fn fold_changes(changes: Vec<Change>) -> (BTreeSet<char>, BTreeSet<char>) {
+    let mut changed = BTreeSet::new();
+    let mut removed = BTreeSet::new();
+
+    for change in changes {
+        for c in change.changed {
+            removed.remove(&c);
+            changed.insert(c);
+        }
+        for c in change.removed {
+            changed.remove(&c);
+            removed.insert(c);
+        }
+    }
+
+    (changed, removed)
+}
+
+#[test]
+fn test_fold_changes() {
+    let changes = vec![
+        Change {
+            version: "2024-01".into(),
+            changed: vec!['日', '月'],
+            removed: vec!['火'],
+        },
+        Change {
+            version: "2024-02".into(),
+            changed: vec!['火'],
+            removed: vec!['月'],
+        },
+    ];
+    let (changed, removed) = fold_changes(changes);
+    assert_eq!(changed.into_iter().collect::<String>(), "日火");
+    assert_eq!(removed.into_iter().collect::<String>(), "月");
+}
+
+#[test]
+fn test_up_to_date() {
+    // a client with nothing to fetch keeps its sync point
+    let res = serde_json::to_value(SyncResponse::up_to_date(Some("2024-02".into()))).unwrap();
+    assert_eq!(
+        res,
+        serde_json::json!({ "version": "2024-02", "total": 0, "changed": [], "removed": [] })
+    );
+}
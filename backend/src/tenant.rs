@@ -0,0 +1,72 @@
+//! Selecting the data namespace a request is served from
+
+use std::sync::Arc;
+
+use axum::{
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use model::namespace;
+
+use crate::{AppError, Database, Store};
+
+/// The header a client selects a namespace with
+pub const HEADER: &str = "x-kanjisho-namespace";
+
+/// The namespaces besides the default one a server will serve
+pub struct Tenants {
+    client: mongodb::Client,
+    allowed: Vec<String>,
+}
+
+impl Tenants {
+    pub fn new(client: mongodb::Client, allowed: Vec<String>) -> Self {
+        Tenants { client, allowed }
+    }
+
+    fn database(&self, ns: &str) -> Option<Database> {
+        self.allowed
+            .iter()
+            .any(|a| a == ns)
+            .then(|| Arc::new(self.client.database(&namespace::database_name(Some(ns)))))
+    }
+}
+
+/// Parse a comma separated list of namespaces
+pub fn parse_list(s: &str) -> Result<Vec<String>, String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|ns| !ns.is_empty())
+        .map(|ns| match namespace::is_valid(ns) {
+            true => Ok(ns.to_owned()),
+            false => Err(format!("invalid namespace {:?}", ns)),
+        })
+        .collect()
+}
+
+/// Middleware swapping the database of a request for the one of the
+/// namespace named in its header
+pub async fn select<B>(tenants: Arc<Tenants>, mut req: Request<B>, next: Next<B>) -> Response {
+    let ns = match req.headers().get(HEADER) {
+        Some(ns) => ns.to_str().unwrap_or_default().to_owned(),
+        None => return next.run(req).await,
+    };
+
+    let db = match tenants.database(&ns) {
+        Some(db) => db,
+        None => return AppError::BadRequest(format!("unknown namespace {:?}", ns)).into_response(),
+    };
+
+    req.extensions_mut().insert::<Database>(db);
+    // the read-only store only ever holds the default dataset
+    req.extensions_mut().insert::<Store>(None);
+    next.run(req).await
+}
+
+#[test]
+fn test_parse_list() {
+    assert_eq!(parse_list("").unwrap(), Vec::<String>::new());
+    assert_eq!(parse_list("staging, test").unwrap(), ["staging", "test"]);
+    assert!(parse_list("staging,a.b").is_err());
+}
@@ -4,7 +4,10 @@ rm -rf data
 
 wget -P data http://ftp.edrdg.org/pub/Nihongo/JMdict_e.gz
 wget -P data http://ftp.edrdg.org/pub/Nihongo/kanjidic2.xml.gz
+wget -P data http://ftp.edrdg.org/pub/Nihongo/JMnedict.xml.gz
 wget -P data http://ftp.edrdg.org/pub/Nihongo/kradzip.zip
+wget -P data http://ftp.edrdg.org/pub/Nihongo/examples.utf.gz
+wget -O data/kanjivg.xml.gz https://github.com/KanjiVG/kanjivg/releases/download/r20230110/kanjivg-20230110.xml.gz
 
 unzip -d data data/*.zip
 gunzip -k data/*.gz
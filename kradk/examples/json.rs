@@ -0,0 +1,29 @@
+//! Convert KRADFILE style files into JSON maps of kanji to radicals and
+//! radicals to kanji.
+//!
+//! Usage: cargo run --example json -- <output dir> <kradfile>...
+
+use std::path::Path;
+
+use kradk::krad;
+
+fn main() -> kradk::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let out = args.next().expect("usage: json <output dir> <kradfile>...");
+
+    let mut entries = vec![];
+    for path in args {
+        let text = kradk::read(path)?;
+        for e in krad::iterator(&text) {
+            entries.push(e?);
+        }
+    }
+
+    let out = Path::new(&out);
+    let kanji = serde_json::to_string(&krad::to_kanji_map(&entries)).unwrap();
+    std::fs::write(out.join("kanji.json"), kanji).or(Err(kradk::Error::IO))?;
+    let radicals = serde_json::to_string(&krad::to_radical_map(&entries)).unwrap();
+    std::fs::write(out.join("radicals.json"), radicals).or(Err(kradk::Error::IO))?;
+
+    Ok(())
+}
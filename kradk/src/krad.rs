@@ -1,10 +1,13 @@
+use std::collections::BTreeMap;
+
 use crate::{Error, NomError, Result};
 use nom::{
     bytes::complete::tag, character::complete::anychar, combinator::rest, sequence::separated_pair,
 };
+use serde::{Deserialize, Serialize};
 
 /// A single KRAD Kanji/Radical entry
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct Entry {
     /// The key kanji
     pub kanji: char,
@@ -19,7 +22,7 @@ pub fn iterator<'a>(input: &'a str) -> impl Iterator<Item = Result<Entry>> + 'a
         .map(|l| l.trim())
         .filter(|l| !l.is_empty())
         .filter(|l| !l.starts_with("#"))
-        .map(|l| parse_line(l))
+        .map(parse_line)
 }
 
 /// Parse a single KRAD entry
@@ -31,7 +34,26 @@ fn parse_line(i: &str) -> Result<Entry> {
             radicals: remove_spaces(radicals),
         })
         // owning the error is easier than dealing with the ref for now
-        .or_else(|e: NomError<&str>| Err(Error::Parse(e.to_owned())))
+        .map_err(|e: NomError<&str>| Error::Parse(e.to_owned()))
+}
+
+/// Map each kanji to the radicals it is composed of
+pub fn to_kanji_map(entries: &[Entry]) -> BTreeMap<char, String> {
+    entries
+        .iter()
+        .map(|e| (e.kanji, e.radicals.clone()))
+        .collect()
+}
+
+/// Map each radical to the kanji it appears in
+pub fn to_radical_map(entries: &[Entry]) -> BTreeMap<char, String> {
+    let mut m = BTreeMap::<char, String>::new();
+    for e in entries {
+        for r in e.radicals.chars() {
+            m.entry(r).or_default().push(e.kanji);
+        }
+    }
+    m
 }
 
 /// Remove spaces from the KRAD radicals list
@@ -40,3 +62,15 @@ fn remove_spaces(i: &str) -> String {
     s.retain(|c| c != ' ');
     s
 }
+
+#[test]
+fn test_maps() {
+    let input = "# comment\n亜 : ｜ 一 口\n唖 : ｜ 一 口\n";
+    let entries: Vec<Entry> = iterator(input).collect::<Result<_>>().unwrap();
+
+    let kanji = to_kanji_map(&entries);
+    assert_eq!(kanji[&'亜'], "｜一口");
+
+    let radicals = to_radical_map(&entries);
+    assert_eq!(radicals[&'口'], "亜唖");
+}
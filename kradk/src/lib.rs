@@ -1,6 +1,9 @@
 pub mod krad;
+pub mod model;
 pub mod radk;
 
+pub use model::Model;
+
 /// `Result` wrapper for `Error`
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -10,6 +13,11 @@ pub enum Error {
     IO,
     Decode,
     Parse(NomError<String>),
+    /// The KRAD and RADK files disagree on whether a kanji contains a radical
+    Mismatch {
+        kanji: char,
+        radical: char,
+    },
 }
 
 // convenience def
@@ -17,6 +25,7 @@ type NomError<T> = nom::Err<nom::error::Error<T>>;
 
 /// Decode the raw file into UTF-8
 pub fn decode(input: &[u8]) -> Result<String> {
+    let _span = tracing::debug_span!("decode", bytes = input.len()).entered();
     encoding_rs::EUC_JP
         .decode_without_bom_handling_and_without_replacement(input)
         .map(|s| s.into_owned())
@@ -25,6 +34,7 @@ pub fn decode(input: &[u8]) -> Result<String> {
 
 /// Read and decode a given file
 pub fn read<P: AsRef<std::path::Path>>(path: P) -> Result<String> {
+    let _span = tracing::info_span!("read", path = %path.as_ref().display()).entered();
     let input = std::fs::read(path)
         // TODO this error should be better
         .or(Err(Error::IO))?;
@@ -0,0 +1,165 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{krad, radk, Error, Result};
+
+/// The KRAD and RADK files combined, indexed both ways
+#[derive(Debug, Default, PartialEq)]
+pub struct Model {
+    /// Each kanji with its radicals, in file order
+    radicals: BTreeMap<char, Vec<char>>,
+    /// Each radical with the kanji it appears in
+    kanji: BTreeMap<char, BTreeSet<char>>,
+    /// Each radical with its stroke count, as first given
+    strokes: BTreeMap<char, u8>,
+    /// The radicals grouped by stroke count, in file order
+    groups: BTreeMap<u8, Vec<char>>,
+}
+
+impl Model {
+    /// Parse and combine the text of KRAD and RADK files, e.g. kradfile
+    /// and kradfile2 with radkfile and radkfile2
+    pub fn parse(krad: &[&str], radk: &[&str]) -> Result<Self> {
+        let krad = krad
+            .iter()
+            .flat_map(|t| krad::iterator(t))
+            .collect::<Result<Vec<_>>>()?;
+        let radk = radk
+            .iter()
+            .flat_map(|t| radk::iterator(t))
+            .collect::<Result<Vec<_>>>()?;
+        Model::new(krad, radk)
+    }
+
+    /// Combine parsed entries. Both files describe the same relation, so
+    /// a kanji listed for a radical in one but not the other is an error.
+    pub fn new(krad: Vec<krad::Entry>, radk: Vec<radk::Entry>) -> Result<Self> {
+        let mut model = Model::default();
+        for e in krad {
+            model
+                .radicals
+                .entry(e.kanji)
+                .or_default()
+                .extend(e.radicals.chars());
+        }
+        model.groups = radk::group_by_strokes(radk.iter().map(|e| (e.radical, e.strokes)));
+        for e in radk {
+            model.strokes.entry(e.radical).or_insert(e.strokes);
+            model
+                .kanji
+                .entry(e.radical)
+                .or_default()
+                .extend(e.kanjis.chars());
+        }
+
+        for (&kanji, radicals) in &model.radicals {
+            for &radical in radicals {
+                if !model
+                    .kanji
+                    .get(&radical)
+                    .is_some_and(|k| k.contains(&kanji))
+                {
+                    return Err(Error::Mismatch { kanji, radical });
+                }
+            }
+        }
+        for (&radical, kanjis) in &model.kanji {
+            for &kanji in kanjis {
+                if !model
+                    .radicals
+                    .get(&kanji)
+                    .is_some_and(|r| r.contains(&radical))
+                {
+                    return Err(Error::Mismatch { kanji, radical });
+                }
+            }
+        }
+
+        Ok(model)
+    }
+
+    /// Every kanji with the radicals it is made of, by codepoint
+    pub fn decompositions(&self) -> impl Iterator<Item = (char, &[char])> {
+        self.radicals.iter().map(|(&k, r)| (k, r.as_slice()))
+    }
+
+    /// The radicals a kanji is made of, empty if it is unknown
+    pub fn decompose(&self, kanji: char) -> Vec<char> {
+        self.radicals.get(&kanji).cloned().unwrap_or_default()
+    }
+
+    /// The kanji containing every one of the radicals, by codepoint
+    pub fn compose(&self, radicals: &[char]) -> Vec<char> {
+        let mut sets = radicals.iter().map(|r| self.kanji.get(r));
+        let first = match sets.next() {
+            Some(Some(first)) => first,
+            _ => return vec![],
+        };
+        let mut kanji = first.clone();
+        for set in sets {
+            match set {
+                Some(set) => kanji.retain(|k| set.contains(k)),
+                None => return vec![],
+            }
+        }
+        kanji.into_iter().collect()
+    }
+
+    /// The stroke count of a radical
+    pub fn strokes(&self, radical: char) -> Option<u8> {
+        self.strokes.get(&radical).copied()
+    }
+
+    /// The radicals grouped by their stroke count, keeping file order
+    /// within each group, as a radical picker lists them. A radical in
+    /// more than one RADK file is listed once.
+    pub fn radicals_by_strokes(&self) -> &BTreeMap<u8, Vec<char>> {
+        &self.groups
+    }
+}
+
+#[test]
+fn test_model() {
+    let krad = "# comment\n亜 : ｜ 一 口\n唖 : ｜ 一 口\n引 : ｜ 弓\n";
+    let radk = "$ 一 1\n亜唖\n$ ｜ 1\n亜唖引\n$ 口 3\n亜唖\n$ 弓 3\n引\n";
+    let model = Model::parse(&[krad], &[radk]).unwrap();
+
+    assert_eq!(model.decompose('亜'), ['｜', '一', '口']);
+    assert_eq!(model.decompose('水'), []);
+    assert_eq!(
+        model.decompositions().map(|(k, _)| k).collect::<String>(),
+        "亜唖引"
+    );
+    assert_eq!(model.compose(&['｜']), ['亜', '唖', '引']);
+    assert_eq!(model.compose(&['｜', '口']), ['亜', '唖']);
+    assert_eq!(model.compose(&['口', '弓']), []);
+    assert_eq!(model.compose(&['口', '水']), []);
+    assert_eq!(model.compose(&[]), []);
+    assert_eq!(model.strokes('口'), Some(3));
+    assert_eq!(model.strokes('水'), None);
+
+    let groups = model.radicals_by_strokes();
+    assert_eq!(groups[&1], ['一', '｜']);
+    assert_eq!(groups[&3], ['口', '弓']);
+
+    // radkfile2 repeats the radicals of radkfile
+    let model = Model::parse(&[krad], &[radk, "$ 口 3\n亜\n"]).unwrap();
+    assert_eq!(model.radicals_by_strokes()[&3], ['口', '弓']);
+    assert_eq!(model.strokes('口'), Some(3));
+
+    let missing = "$ 一 1\n亜唖\n$ ｜ 1\n亜唖\n$ 口 3\n亜唖\n$ 弓 3\n引\n";
+    assert_eq!(
+        Model::parse(&[krad], &[missing]),
+        Err(Error::Mismatch {
+            kanji: '引',
+            radical: '｜'
+        })
+    );
+    let extra = "$ 一 1\n亜唖引\n$ ｜ 1\n亜唖引\n$ 口 3\n亜唖\n$ 弓 3\n引\n";
+    assert_eq!(
+        Model::parse(&[krad], &[extra]),
+        Err(Error::Mismatch {
+            kanji: '引',
+            radical: '一'
+        })
+    );
+}
@@ -1,96 +1,173 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter::Peekable,
+    str::Lines,
+};
+
 use crate::{Error, NomError, Result};
 use nom::{
-    branch::alt,
-    bytes::complete::tag,
-    character::complete::{u8, anychar, char, multispace1, newline, not_line_ending},
-    combinator::{rest, value},
-    sequence::{separated_pair, tuple},
+    character::complete::{anychar, char, not_line_ending, space1, u8},
+    combinator::{eof, opt},
+    sequence::{preceded, tuple},
 };
+use serde::{Deserialize, Serialize};
 
 /// A single RADK Radical/Kanji entry
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct Entry {
-    /// The key kanji
+    /// The key radical
     pub radical: char,
-    /// The list of radicals
+    /// The stroke count of the radical
+    pub strokes: u8,
+    /// The name of an image of the radical, for radicals that have no
+    /// suitable character in JIS X 0208, e.g. js01
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// The list of kanji containing the radical
     pub kanjis: String,
 }
 
-struct MyIter<'a, I: Iterator<Item = &'a str>> {
-    // need the line iterator
-    // wtf is this going to look like?
-    iter: I,
+/// All entries of a RADK file
+#[derive(Debug, Default, PartialEq)]
+pub struct Index {
+    pub entries: Vec<Entry>,
 }
 
-impl<'a, I: Iterator<Item = &'a str>> Iterator for MyIter<'a, I> {
-    type Item = Entry;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let n = self.iter.next()?;
+impl Index {
+    /// Parse a whole RADK file
+    pub fn parse(input: &str) -> Result<Self> {
+        let entries = iterator(input).collect::<Result<_>>()?;
+        Ok(Index { entries })
+    }
 
-        // read first line as char/int
-        // read all lines until one starts with $
-        
+    /// Group the radicals by their stroke count, keeping file order
+    /// within each group
+    pub fn radicals_by_strokes(&self) -> BTreeMap<u8, Vec<char>> {
+        group_by_strokes(self.entries.iter().map(|e| (e.radical, e.strokes)))
+    }
+}
 
-        None
+/// Group radicals by their stroke count, keeping the order they are first
+/// given in within each group. A radical given again, as radkfile2 repeats
+/// those of radkfile, is only listed the first time.
+pub fn group_by_strokes(radicals: impl IntoIterator<Item = (char, u8)>) -> BTreeMap<u8, Vec<char>> {
+    let mut seen = BTreeSet::new();
+    let mut m = BTreeMap::<u8, Vec<char>>::new();
+    for (radical, strokes) in radicals {
+        if seen.insert(radical) {
+            m.entry(strokes).or_default().push(radical);
+        }
     }
+    m
 }
 
 /// Iterator over the entries of a RADK file
-pub fn iterator<'a>(input: &'a str) -> impl Iterator<Item = Result<Entry>> + 'a {
-    let it = input
-        .lines()
-        .map(|l| l.trim())
-        .filter(|l| !l.is_empty())
-        .filter(|l| !l.starts_with("#"));
-    //.map(|l| parse_line(l));
+struct Iter<'a> {
+    lines: Peekable<Lines<'a>>,
 }
 
-/// Parse a single KRAD entry
-/// this currently doesn't guarantee the radical list is not empty
-fn parse_line(i: &str) -> Result<Entry> {
-    separated_pair(anychar, tag(" : "), rest)(i)
-        .map(|(_, (kanji, radicals))| Entry {
-            kanji,
-            radicals: remove_spaces(radicals),
-        })
-        // owning the error is easier than dealing with the ref for now
-        .or_else(|e: NomError<&str>| Err(Error::Parse(e.to_owned())))
-}
+impl<'a> Iterator for Iter<'a> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = loop {
+            let l = self.lines.next()?.trim();
+            if is_content(l) {
+                break l;
+            }
+        };
+
+        // every line up to the next header lists kanji
+        let mut kanjis = String::new();
+        while let Some(l) = self.lines.next_if(|l| !l.trim_start().starts_with('$')) {
+            let l = l.trim();
+            if is_content(l) {
+                kanjis.extend(l.chars().filter(|c| !c.is_whitespace()));
+            }
+        }
+
+        // the kanji are consumed first so a bad header is a single error
+        let (radical, strokes, image) = match parse_header(header) {
+            Ok(h) => h,
+            Err(e) => return Some(Err(e)),
+        };
 
-#[derive(Debug, PartialEq, Clone)]
-enum Token<'a> {
-    Comment,
-    Entry(char, i32),
-    Line(&'a str)
+        Some(Ok(Entry {
+            radical,
+            strokes,
+            image,
+            kanjis,
+        }))
+    }
 }
 
-fn parse_root(i: &str) -> nom::IResult<&str, ()> {
-    alt((parse_comment, entry))(i)
+/// Iterator over the entries of a RADK file
+pub fn iterator<'a>(input: &'a str) -> impl Iterator<Item = Result<Entry>> + 'a {
+    Iter {
+        lines: input.lines().peekable(),
+    }
 }
 
-fn parse_comment(i: &str) -> nom::IResult<&str, Token> {
-    value(
-        Token::Comment,
-        tag("#"), // TODO fix this
-    )(i)
+/// Whether a trimmed line has content, i.e. is neither empty nor a comment
+fn is_content(l: &str) -> bool {
+    !l.is_empty() && !l.starts_with('#')
 }
 
-fn parse_entry(i: &str) -> nom::IResult<&str, Token> {
-    tuple((char('$'), multispace1, u8, rest))(i)
-    // XXX how do you map again?
+/// Parse a single RADK header line like '$ 化 2 js01'
+fn parse_header(i: &str) -> Result<(char, u8, Option<String>)> {
+    tuple((
+        char('$'),
+        space1,
+        anychar,
+        space1,
+        u8,
+        opt(preceded(space1, not_line_ending)),
+        eof,
+    ))(i)
+    .map(|(_, (_, _, radical, _, strokes, image, _))| {
+        let image = image.map(str::trim).filter(|s| !s.is_empty());
+        (radical, strokes, image.map(String::from))
+    })
+    // owning the error is easier than dealing with the ref for now
+    .map_err(|e: NomError<&str>| Error::Parse(e.to_owned()))
 }
 
-/// Parser for single header line
-/// like '$ 化 2 js01'
-/// that extra bit probably means I should
-/// manually replace with a better unicode char
+#[test]
+fn test_radicals_by_strokes() {
+    let input = "\
+# comment
+$ 一 1
+亜唖娃阿
+哀愛
+$ ｜ 1
+引
+$ 化 2 js01
+花
+";
+    let index = Index::parse(input).unwrap();
+    assert_eq!(index.entries[0].kanjis, "亜唖娃阿哀愛");
+
+    assert_eq!(index.entries[2].image.as_deref(), Some("js01"));
 
-/// Parser for character block
+    let results: Vec<_> = iterator("$ 一\n亜\n$ ｜ 1\n引\n").collect();
+    assert!(results[0].is_err());
+    assert_eq!(results[1].as_ref().unwrap().kanjis, "引");
+
+    let m = index.radicals_by_strokes();
+    assert_eq!(m[&1], ['一', '｜']);
+    assert_eq!(m[&2], ['化']);
+}
 
-/// Remove spaces from the KRAD radicals list
-fn remove_spaces(i: &str) -> String {
-    let mut s = i.to_owned();
-    s.retain(|c| c != ' ');
-    s
+#[test]
+fn test_parse_header() {
+    assert_eq!(parse_header("$ 一 1"), Ok(('一', 1, None)));
+    assert_eq!(
+        parse_header("$ 化 2 js01"),
+        Ok(('化', 2, Some("js01".into())))
+    );
+    assert_eq!(parse_header("$ 滴 14 "), Ok(('滴', 14, None)));
+    assert!(parse_header("$ 一").is_err());
+    assert!(parse_header("$ 一 x").is_err());
+    assert!(parse_header("$ 一 1x").is_err());
+    assert!(parse_header("一 1").is_err());
 }
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// The set of kanjidic entries that changed in one populate run
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Change {
+    /// The KANJIDIC database version that was loaded, in the format YYYY-NN.
+    pub version: String,
+    /// Literals that were added or whose entry changed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changed: Vec<char>,
+    /// Literals that are no longer present.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<char>,
+}
@@ -0,0 +1,196 @@
+//! Fields may be added to [`Entry`] in any release, so outside this crate
+//! it is built with [`Entry::builder`] rather than a struct literal.
+
+use serde::{Deserialize, Serialize};
+
+use crate::kana::Script;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct Entry {
+    /// A unique numeric sequence number for each entry
+    pub ent_seq: u32,
+    /// Written forms of the word using at least one non-kana character.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kanji: Vec<Kanji>,
+    /// Kana readings of the word. Every entry has at least one.
+    pub readings: Vec<Reading>,
+    /// Translations and related information, one per distinct meaning.
+    pub senses: Vec<Sense>,
+    /// The script of the headword, i.e. the first written form or else
+    /// the first reading. See [`Entry::classify`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<Script>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Kanji {
+    pub text: String,
+    /// Coded information about unusual orthography, e.g. irregular okurigana.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub info: Vec<String>,
+    /// Priority codes such as news1, ichi1 or nf01.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub priority: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Reading {
+    pub text: String,
+    /// The reading cannot be regarded as a true reading of the kanji.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_kanji: bool,
+    /// The kanji forms this reading is restricted to. Empty means all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub restrictions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub info: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub priority: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Sense {
+    /// Part-of-speech codes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pos: Vec<String>,
+    /// English glosses for this sense.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub glosses: Vec<String>,
+    /// Keys of glosses in the shared gloss table, used in place of
+    /// `glosses` when stored. See [`super::gloss`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gloss_keys: Vec<String>,
+    /// The kanji forms this sense is restricted to. Empty means all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kanji_restrictions: Vec<String>,
+    /// The readings this sense is restricted to. Empty means all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reading_restrictions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub field: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub misc: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dialect: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub info: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub xrefs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub antonyms: Vec<String>,
+}
+
+impl Entry {
+    pub fn builder(ent_seq: u32) -> EntryBuilder {
+        EntryBuilder(Entry {
+            ent_seq,
+            kanji: vec![],
+            readings: vec![],
+            senses: vec![],
+            script: None,
+        })
+    }
+
+    /// The script of the headword, i.e. the first written form or else
+    /// the first reading
+    pub fn classify(&self) -> Script {
+        let headword = self
+            .kanji
+            .first()
+            .map(|k| &k.text)
+            .or_else(|| self.readings.first().map(|r| &r.text));
+        Script::of(headword.map_or("", |h| h.as_str()))
+    }
+
+    /// The first reading of a written form, leaving out readings which
+    /// are restricted to other forms or aren't true readings of the kanji
+    pub fn reading_of(&self, kanji: &str) -> Option<&Reading> {
+        self.readings.iter().find(|r| {
+            !r.no_kanji && (r.restrictions.is_empty() || r.restrictions.iter().any(|k| k == kanji))
+        })
+    }
+
+    pub fn ent_seq(&self) -> u32 {
+        self.ent_seq
+    }
+
+    pub fn kanji(&self) -> &[Kanji] {
+        &self.kanji
+    }
+
+    pub fn readings(&self) -> &[Reading] {
+        &self.readings
+    }
+
+    pub fn senses(&self) -> &[Sense] {
+        &self.senses
+    }
+
+    pub fn script(&self) -> Option<Script> {
+        self.script
+    }
+}
+
+/// Rank a word by its JMdict priority codes, lower being more common.
+/// The nfXX frequency bands are the finest ranking available; words only
+/// marked as common in one of the other lists come after all of them.
+/// Words without any priority are not ranked.
+pub fn priority_rank(priority: &[String]) -> Option<u32> {
+    let nf = priority
+        .iter()
+        .filter_map(|p| p.strip_prefix("nf")?.parse::<u32>().ok())
+        .min();
+    if nf.is_some() {
+        return nf;
+    }
+
+    let common = ["news1", "ichi1", "spec1", "gai1"];
+    let uncommon = ["news2", "ichi2", "spec2", "gai2"];
+    if priority.iter().any(|p| common.contains(&p.as_str())) {
+        Some(100)
+    } else if priority.iter().any(|p| uncommon.contains(&p.as_str())) {
+        Some(200)
+    } else {
+        None
+    }
+}
+
+/// Builds an [`Entry`], with every list empty unless set
+#[derive(Clone, Debug)]
+pub struct EntryBuilder(Entry);
+
+impl EntryBuilder {
+    pub fn kanji(mut self, kanji: Vec<Kanji>) -> Self {
+        self.0.kanji = kanji;
+        self
+    }
+
+    pub fn readings(mut self, readings: Vec<Reading>) -> Self {
+        self.0.readings = readings;
+        self
+    }
+
+    pub fn senses(mut self, senses: Vec<Sense>) -> Self {
+        self.0.senses = senses;
+        self
+    }
+
+    /// Set the script from the written forms and readings given so far
+    pub fn classify(mut self) -> Self {
+        self.0.script = Some(self.0.classify());
+        self
+    }
+
+    pub fn build(self) -> Entry {
+        self.0
+    }
+}
+
+#[test]
+fn test_priority_rank() {
+    assert_eq!(priority_rank(&["nf20".into(), "nf03".into()]), Some(3));
+    assert_eq!(priority_rank(&["ichi1".into()]), Some(100));
+    assert_eq!(priority_rank(&["gai2".into()]), Some(200));
+    assert_eq!(priority_rank(&[]), None);
+}
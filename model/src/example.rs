@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// An example sentence from the Tanaka Corpus
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Example {
+    /// The Tatoeba ids of the Japanese and English sentences
+    pub id: String,
+    pub japanese: String,
+    pub english: String,
+    /// The distinct kanji of the Japanese sentence, for looking up
+    /// examples by kanji
+    pub kanji: Vec<char>,
+    /// The words the sentence is indexed by
+    pub words: Vec<ExampleWord>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ExampleWord {
+    /// The dictionary form of the word
+    pub headword: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading: Option<String>,
+    /// The sense of the JMdict entry used, from 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sense: Option<u32>,
+    /// The word as written in the sentence, if it differs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub form: Option<String>,
+    /// Whether the sentence was checked as a good example of the word
+    #[serde(default)]
+    pub checked: bool,
+}
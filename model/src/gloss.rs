@@ -0,0 +1,107 @@
+//! Interning of JMdict glosses. Many glosses are repeated across entries,
+//! so they are stored once in a shared table keyed by a hash of their
+//! text, and entries only keep the keys.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::entry::Entry;
+
+/// A gloss in the shared table
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Gloss {
+    /// The hash of the text, see [`key`]
+    pub key: String,
+    pub text: String,
+}
+
+/// The key of a gloss, the hash of its text
+pub fn key(text: &str) -> String {
+    super::hash::fnv1a(text.as_bytes())
+}
+
+/// Replace the glosses of every entry with their keys, returning the
+/// table of distinct glosses
+pub fn intern(entries: &mut [Entry]) -> Vec<Gloss> {
+    let mut table = BTreeMap::<String, String>::new();
+
+    for sense in entries.iter_mut().flat_map(|e| e.senses.iter_mut()) {
+        for text in std::mem::take(&mut sense.glosses) {
+            let key = key(&text);
+            let existing = table.entry(key.clone()).or_insert_with(|| text.clone());
+            assert_eq!(*existing, text, "gloss hash collision on {}", key);
+            sense.gloss_keys.push(key);
+        }
+    }
+
+    table
+        .into_iter()
+        .map(|(key, text)| Gloss { key, text })
+        .collect()
+}
+
+/// Every gloss key referenced by some entries
+pub fn keys(entries: &[Entry]) -> Vec<String> {
+    let mut keys: Vec<String> = entries
+        .iter()
+        .flat_map(|e| &e.senses)
+        .flat_map(|s| s.gloss_keys.iter().cloned())
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Put the glosses of interned entries back in place. Keys missing from
+/// the table are dropped.
+pub fn expand(entries: &mut [Entry], table: &[Gloss]) {
+    let table: BTreeMap<&str, &str> = table
+        .iter()
+        .map(|g| (g.key.as_str(), g.text.as_str()))
+        .collect();
+
+    for sense in entries.iter_mut().flat_map(|e| e.senses.iter_mut()) {
+        for key in std::mem::take(&mut sense.gloss_keys) {
+            if let Some(text) = table.get(key.as_str()) {
+                sense.glosses.push(text.to_string());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_intern() {
+    use super::entry::{Reading, Sense};
+
+    let entry = |ent_seq, glosses: &[&str]| {
+        Entry::builder(ent_seq)
+            .readings(vec![Reading {
+                text: "みず".into(),
+                no_kanji: false,
+                restrictions: vec![],
+                info: vec![],
+                priority: vec![],
+            }])
+            .senses(vec![Sense {
+                glosses: glosses.iter().map(|g| g.to_string()).collect(),
+                ..Default::default()
+            }])
+            .build()
+    };
+
+    let original = vec![entry(1, &["water", "fluid"]), entry(2, &["water"])];
+    let mut entries = original.clone();
+    let table = intern(&mut entries);
+
+    assert_eq!(table.len(), 2);
+    assert!(entries[0].senses[0].glosses.is_empty());
+    assert_eq!(
+        entries[0].senses[0].gloss_keys[0],
+        entries[1].senses[0].gloss_keys[0]
+    );
+    assert_eq!(keys(&entries).len(), 2);
+
+    expand(&mut entries, &table);
+    assert_eq!(entries, original);
+}
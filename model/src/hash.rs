@@ -0,0 +1,16 @@
+//! The hash stored documents are keyed or compared by
+
+/// A 64 bit FNV-1a hash of some bytes, as hex. This must stay stable since
+/// hashes are persisted.
+pub fn fnv1a(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+#[test]
+fn test_fnv1a() {
+    assert_eq!(fnv1a(b""), "cbf29ce484222325");
+    assert_eq!(fnv1a(b"a"), "af63dc4c8601ec8c");
+}
@@ -0,0 +1,343 @@
+//! Telling apart the scripts Japanese is written in, and converting
+//! between them.
+
+use serde::{Deserialize, Serialize};
+
+/// The script a word is written in
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Script {
+    /// At least one kanji, with only kana besides, e.g. 食べる
+    Kanji,
+    /// Only hiragana, e.g. ありがとう
+    Kana,
+    /// Only katakana, usually a loanword, e.g. コーヒー
+    Katakana,
+    /// Anything else, e.g. Ｔシャツ or hiragana with katakana
+    Mixed,
+}
+
+pub fn is_kanji(c: char) -> bool {
+    matches!(c as u32, 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x3FFFF)
+        // iteration mark and shime, as in 時々 and 〆切
+        || c == '々'
+        || c == '〆'
+}
+
+pub fn is_hiragana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{309F}')
+}
+
+pub fn is_katakana(c: char) -> bool {
+    matches!(c, '\u{30A0}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}')
+}
+
+pub fn is_kana(c: char) -> bool {
+    is_hiragana(c) || is_katakana(c)
+}
+
+/// Convert katakana to hiragana, leaving everything else as is
+pub fn to_hiragana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap(),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Convert hiragana to katakana, leaving everything else as is
+pub fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{3041}'..='\u{3096}' => char::from_u32(c as u32 + 0x60).unwrap(),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Romaji as typed with an IME, both Hepburn and the wapuro spellings
+/// such as si, zi and tu, with the hiragana they are converted to
+#[rustfmt::skip]
+const ROMAJI: &[(&str, &str)] = &[
+    ("a", "あ"), ("i", "い"), ("u", "う"), ("e", "え"), ("o", "お"),
+    ("ka", "か"), ("ki", "き"), ("ku", "く"), ("ke", "け"), ("ko", "こ"),
+    ("ga", "が"), ("gi", "ぎ"), ("gu", "ぐ"), ("ge", "げ"), ("go", "ご"),
+    ("sa", "さ"), ("shi", "し"), ("si", "し"), ("su", "す"), ("se", "せ"), ("so", "そ"),
+    ("za", "ざ"), ("ji", "じ"), ("zi", "じ"), ("zu", "ず"), ("ze", "ぜ"), ("zo", "ぞ"),
+    ("ta", "た"), ("chi", "ち"), ("ti", "ち"), ("tsu", "つ"), ("tu", "つ"), ("te", "て"), ("to", "と"),
+    ("da", "だ"), ("di", "ぢ"), ("du", "づ"), ("de", "で"), ("do", "ど"),
+    ("na", "な"), ("ni", "に"), ("nu", "ぬ"), ("ne", "ね"), ("no", "の"),
+    ("ha", "は"), ("hi", "ひ"), ("fu", "ふ"), ("hu", "ふ"), ("he", "へ"), ("ho", "ほ"),
+    ("ba", "ば"), ("bi", "び"), ("bu", "ぶ"), ("be", "べ"), ("bo", "ぼ"),
+    ("pa", "ぱ"), ("pi", "ぴ"), ("pu", "ぷ"), ("pe", "ぺ"), ("po", "ぽ"),
+    ("ma", "ま"), ("mi", "み"), ("mu", "む"), ("me", "め"), ("mo", "も"),
+    ("ya", "や"), ("yu", "ゆ"), ("yo", "よ"),
+    ("ra", "ら"), ("ri", "り"), ("ru", "る"), ("re", "れ"), ("ro", "ろ"),
+    ("wa", "わ"), ("wi", "うぃ"), ("we", "うぇ"), ("wo", "を"),
+    ("vu", "ゔ"), ("va", "ゔぁ"), ("vi", "ゔぃ"), ("ve", "ゔぇ"), ("vo", "ゔぉ"),
+    ("fa", "ふぁ"), ("fi", "ふぃ"), ("fe", "ふぇ"), ("fo", "ふぉ"),
+    ("kya", "きゃ"), ("kyu", "きゅ"), ("kyo", "きょ"),
+    ("gya", "ぎゃ"), ("gyu", "ぎゅ"), ("gyo", "ぎょ"),
+    ("sha", "しゃ"), ("shu", "しゅ"), ("sho", "しょ"), ("she", "しぇ"),
+    ("sya", "しゃ"), ("syu", "しゅ"), ("syo", "しょ"),
+    ("ja", "じゃ"), ("ju", "じゅ"), ("jo", "じょ"), ("je", "じぇ"),
+    ("jya", "じゃ"), ("jyu", "じゅ"), ("jyo", "じょ"),
+    ("zya", "じゃ"), ("zyu", "じゅ"), ("zyo", "じょ"),
+    ("cha", "ちゃ"), ("chu", "ちゅ"), ("cho", "ちょ"), ("che", "ちぇ"),
+    ("tya", "ちゃ"), ("tyu", "ちゅ"), ("tyo", "ちょ"),
+    ("cya", "ちゃ"), ("cyu", "ちゅ"), ("cyo", "ちょ"),
+    ("dya", "ぢゃ"), ("dyu", "ぢゅ"), ("dyo", "ぢょ"),
+    ("nya", "にゃ"), ("nyu", "にゅ"), ("nyo", "にょ"),
+    ("hya", "ひゃ"), ("hyu", "ひゅ"), ("hyo", "ひょ"),
+    ("bya", "びゃ"), ("byu", "びゅ"), ("byo", "びょ"),
+    ("pya", "ぴゃ"), ("pyu", "ぴゅ"), ("pyo", "ぴょ"),
+    ("mya", "みゃ"), ("myu", "みゅ"), ("myo", "みょ"),
+    ("rya", "りゃ"), ("ryu", "りゅ"), ("ryo", "りょ"),
+    ("xa", "ぁ"), ("xi", "ぃ"), ("xu", "ぅ"), ("xe", "ぇ"), ("xo", "ぉ"),
+    ("la", "ぁ"), ("li", "ぃ"), ("lu", "ぅ"), ("le", "ぇ"), ("lo", "ぉ"),
+    ("xya", "ゃ"), ("xyu", "ゅ"), ("xyo", "ょ"),
+    ("lya", "ゃ"), ("lyu", "ゅ"), ("lyo", "ょ"),
+    ("xtu", "っ"), ("ltu", "っ"), ("xtsu", "っ"), ("ltsu", "っ"),
+    ("xwa", "ゎ"), ("lwa", "ゎ"),
+    ("-", "ー"),
+];
+
+fn is_vowel(c: u8) -> bool {
+    matches!(c, b'a' | b'i' | b'u' | b'e' | b'o')
+}
+
+/// Convert romaji as typed with an IME to hiragana. Besides Hepburn this
+/// takes the wapuro spellings (si, zi, tu), nn and n' for ん, a lone n
+/// before a consonant or at the end for ん, and a doubled consonant for っ.
+/// Returns None if some of the input is not romaji.
+pub fn from_romaji(s: &str) -> Option<String> {
+    let s = s.to_ascii_lowercase();
+    let b = s.as_bytes();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < b.len() {
+        let next = b.get(i + 1).copied();
+        if b[i] == b'n' && !next.is_some_and(|c| is_vowel(c) || c == b'y') {
+            out.push('ん');
+            // nn is ん unless the second n starts the next syllable
+            i += match next {
+                Some(b'\'') => 2,
+                Some(b'n') if !b.get(i + 2).is_some_and(|&c| is_vowel(c) || c == b'y') => 2,
+                _ => 1,
+            };
+            continue;
+        }
+        // a doubled consonant, or tch as in matcha
+        if b[i].is_ascii_alphabetic()
+            && !is_vowel(b[i])
+            && (next == Some(b[i]) || (b[i] == b't' && next == Some(b'c')))
+        {
+            out.push('っ');
+            i += 1;
+            continue;
+        }
+        let (romaji, kana) = (1..=4)
+            .rev()
+            .filter_map(|n| s.get(i..i + n))
+            .find_map(|r| ROMAJI.iter().find(|(k, _)| *k == r))?;
+        out.push_str(kana);
+        i += romaji.len();
+    }
+    Some(out)
+}
+
+/// How kana are spelled in romaji
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RomajiStyle {
+    /// Hepburn, e.g. shi, chi, tsu and fu, as used in dictionaries
+    Hepburn,
+    /// The wapuro spellings typed with an IME, e.g. si, ti, tu and hu,
+    /// which [`from_romaji`] reads back to the same kana
+    Wapuro,
+}
+
+/// Spellings of each style which aren't the first for their kana in
+/// [`ROMAJI`]
+#[rustfmt::skip]
+const HEPBURN: &[(&str, &str)] = &[
+    ("ji", "ぢ"), ("zu", "づ"), ("ja", "ぢゃ"), ("ju", "ぢゅ"), ("jo", "ぢょ"),
+];
+#[rustfmt::skip]
+const WAPURO: &[(&str, &str)] = &[
+    ("si", "し"), ("zi", "じ"), ("ti", "ち"), ("tu", "つ"), ("hu", "ふ"),
+    ("sya", "しゃ"), ("syu", "しゅ"), ("syo", "しょ"),
+    ("zya", "じゃ"), ("zyu", "じゅ"), ("zyo", "じょ"),
+    ("tya", "ちゃ"), ("tyu", "ちゅ"), ("tyo", "ちょ"),
+];
+
+/// The romaji of the kana at `i`, taking a small ゃ, ゅ or ょ after it
+/// too, with how many characters it covers
+fn syllable(chars: &[char], i: usize, style: RomajiStyle) -> Option<(usize, &'static str)> {
+    let overrides = match style {
+        RomajiStyle::Hepburn => HEPBURN,
+        RomajiStyle::Wapuro => WAPURO,
+    };
+    (1..=2)
+        .rev()
+        .filter(|n| i + n <= chars.len())
+        .find_map(|n| {
+            let kana: String = chars[i..i + n].iter().collect();
+            let (romaji, _) = overrides.iter().chain(ROMAJI).find(|(_, k)| *k == kana)?;
+            Some((n, *romaji))
+        })
+}
+
+/// Convert kana to romaji in the given style, leaving everything else
+/// such as kanji as is. ん is written n, or n' before a vowel or y, and
+/// っ doubles the consonant after it (tch in Hepburn). Long vowels are
+/// spelled out, as in kyouto, and ー is kept as -.
+pub fn to_romaji(s: &str, style: RomajiStyle) -> String {
+    let chars: Vec<char> = to_hiragana(s).chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let next = syllable(&chars, i + 1, style).map(|(_, r)| r.as_bytes()[0]);
+        match chars[i] {
+            'ん' => {
+                out.push('n');
+                if next.is_some_and(|c| is_vowel(c) || c == b'y') {
+                    out.push('\'');
+                }
+                i += 1;
+                continue;
+            }
+            'っ' => {
+                let doubled =
+                    next.filter(|c| c.is_ascii_alphabetic() && !is_vowel(*c) && *c != b'n');
+                if let Some(c) = doubled {
+                    out.push(match (style, c) {
+                        (RomajiStyle::Hepburn, b'c') => 't',
+                        _ => c as char,
+                    });
+                    i += 1;
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        match syllable(&chars, i, style) {
+            Some((n, romaji)) => {
+                out.push_str(romaji);
+                i += n;
+            }
+            None => {
+                out.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+impl Script {
+    /// Classify a written word. The long vowel mark is used with either
+    /// kana so it counts as both.
+    pub fn of(text: &str) -> Script {
+        let chars = || text.chars().filter(|c| *c != 'ー');
+
+        if text.chars().any(is_kanji) {
+            match chars().all(|c| is_kanji(c) || is_kana(c)) {
+                true => Script::Kanji,
+                false => Script::Mixed,
+            }
+        } else if text.is_empty() {
+            Script::Mixed
+        } else if chars().all(is_hiragana) {
+            Script::Kana
+        } else if chars().all(is_katakana) {
+            Script::Katakana
+        } else {
+            Script::Mixed
+        }
+    }
+}
+
+#[test]
+fn test_convert() {
+    assert_eq!(to_hiragana("カイ"), "かい");
+    assert_eq!(to_katakana("かい"), "カイ");
+    assert_eq!(to_hiragana("コーヒー"), "こーひー");
+    assert_eq!(to_katakana("あ.げる"), "ア.ゲル");
+    assert_eq!(to_hiragana("水ヴァ"), "水ゔぁ");
+}
+
+#[test]
+fn test_from_romaji() {
+    for (romaji, kana) in [
+        ("kai", "かい"),
+        ("mizu", "みず"),
+        ("shi", "し"),
+        ("si", "し"),
+        ("zi", "じ"),
+        ("tu", "つ"),
+        ("hu", "ふ"),
+        ("kanji", "かんじ"),
+        ("kanzi", "かんじ"),
+        ("konnichiha", "こんにちは"),
+        ("konnnichiha", "こんにちは"),
+        ("kan'i", "かんい"),
+        ("kani", "かに"),
+        ("honya", "ほにゃ"),
+        ("hon'ya", "ほんや"),
+        ("hon", "ほん"),
+        ("honn", "ほん"),
+        ("gakkou", "がっこう"),
+        ("matcha", "まっちゃ"),
+        ("kyouto", "きょうと"),
+        ("KOHI-", "こひー"),
+        ("xtu", "っ"),
+    ] {
+        assert_eq!(from_romaji(romaji).as_deref(), Some(kana), "{}", romaji);
+    }
+    assert_eq!(from_romaji("qa"), None);
+    assert_eq!(from_romaji("k"), None);
+    assert_eq!(from_romaji("みず"), None);
+}
+
+#[test]
+fn test_to_romaji() {
+    use RomajiStyle::{Hepburn, Wapuro};
+
+    for (kana, hepburn, wapuro) in [
+        ("しんぶん", "shinbun", "sinbun"),
+        ("きょうと", "kyouto", "kyouto"),
+        ("まっちゃ", "matcha", "mattya"),
+        ("がっこう", "gakkou", "gakkou"),
+        ("かんい", "kan'i", "kan'i"),
+        ("ほんや", "hon'ya", "hon'ya"),
+        ("ちず", "chizu", "tizu"),
+        ("はなぢ", "hanaji", "hanadi"),
+        ("ふじさん", "fujisan", "huzisan"),
+        ("コーヒー", "ko-hi-", "ko-hi-"),
+        ("あっ", "axtu", "axtu"),
+        ("水のみ", "水nomi", "水nomi"),
+    ] {
+        assert_eq!(to_romaji(kana, Hepburn), hepburn, "{}", kana);
+        assert_eq!(to_romaji(kana, Wapuro), wapuro, "{}", kana);
+        // wapuro reads back to the same kana
+        if kana.chars().all(is_kana) {
+            assert_eq!(from_romaji(wapuro), Some(to_hiragana(kana)), "{}", kana);
+        }
+    }
+}
+
+#[test]
+fn test_script() {
+    assert_eq!(Script::of("食べる"), Script::Kanji);
+    assert_eq!(Script::of("時々"), Script::Kanji);
+    assert_eq!(Script::of("一ヶ月"), Script::Kanji);
+    assert_eq!(Script::of("ありがとう"), Script::Kana);
+    assert_eq!(Script::of("コーヒー"), Script::Katakana);
+    assert_eq!(Script::of("ｺｰﾋｰ"), Script::Katakana);
+    assert_eq!(Script::of("Ｔシャツ"), Script::Mixed);
+    assert_eq!(Script::of("ＣＤ"), Script::Mixed);
+    assert_eq!(Script::of("ぬいぐるみネコ"), Script::Mixed);
+    assert_eq!(Script::of("Ｘ線"), Script::Mixed);
+}
@@ -0,0 +1,522 @@
+//! Fields may be added to these structs in any release, so outside this
+//! crate they are built with [`Kanji::builder`], [`Info::builder`] and
+//! [`References::builder`] rather than struct literals.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::query::{FourCorner, ShDesc, Skip};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct Kanji {
+    /// The character itself in UTF8 coding.
+    pub literal: char,
+    pub info: Info,
+    pub references: References,
+    /// The "on" Japanese reading of the kanji, in katakana.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_readings: Vec<String>,
+    /// The "kun" Japanese reading of the kanji, usually in hiragana.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kun_readings: Vec<String>,
+    /// The meaning associated with the kanji. (in English)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub meanings: Vec<String>,
+    /// Japanese readings that are now only associated with names.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nanoris: Vec<String>,
+    /// Other forms of the kanji, usually shinjitai/kyuujitai pairs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<char>,
+    /// Codes for looking up the kanji by its shape, such as SKIP.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query: Vec<QueryCode>,
+    /// The most common JMdict words written with the kanji, most common
+    /// first. Filled in when JMdict is populated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_words: Vec<Word>,
+    /// Kanji which look alike, most alike first. Filled in when similar
+    /// kanji are populated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub similar: Vec<char>,
+    /// The number of the kanji in each dictionary numbering its entries,
+    /// by dictionary, e.g. heisig6 or nelson_c, including rtk and klc.
+    /// Dictionaries are listed in order with an indexed sort on these.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dict_index: BTreeMap<String, u32>,
+}
+
+/// A code for finding a kanji by its shape
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct QueryCode {
+    /// The kind of code: skip, sh_desc, four_corner or deroo.
+    pub qc_type: String,
+    /// The code itself, e.g. 1-4-3 for SKIP.
+    pub code: String,
+    /// Set when the code is a common misclassification rather than the
+    /// correct code, to the kind of mistake: posn, stroke_count,
+    /// stroke_and_posn or stroke_diff.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub misclass: Option<String>,
+}
+
+impl QueryCode {
+    pub fn new(qc_type: impl Into<String>, code: impl Into<String>) -> Self {
+        QueryCode {
+            qc_type: qc_type.into(),
+            code: code.into(),
+            misclass: None,
+        }
+    }
+}
+
+impl From<Skip> for QueryCode {
+    fn from(skip: Skip) -> Self {
+        QueryCode::new("skip", skip.to_string())
+    }
+}
+
+impl From<FourCorner> for QueryCode {
+    fn from(code: FourCorner) -> Self {
+        QueryCode::new("four_corner", code.to_string())
+    }
+}
+
+impl From<ShDesc> for QueryCode {
+    fn from(code: ShDesc) -> Self {
+        QueryCode::new("sh_desc", code.to_string())
+    }
+}
+
+/// A JMdict word as listed under a kanji
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Word {
+    /// The sequence number of the JMdict entry
+    pub seq: u32,
+    pub word: String,
+    pub reading: String,
+    /// The first English gloss of the entry
+    pub gloss: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct References {
+    /// Unicode 4.0 - hex coding (4 or 5 hexadecimal digits)
+    pub ucs: String,
+    /// JIS X 0208-1997 - kuten coding (nn-nn)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jis208: Option<String>,
+    /// JIS X 0212-1990 - kuten coding (nn-nn)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jis212: Option<String>,
+    /// JIS X 0213-2000 - kuten coding (p-nn-nn)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jis213: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtk: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub klc: Option<u32>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct Info {
+    /// The radical number, in the range 1 to 214.
+    /// based on the system first used in the KangXi Zidian.
+    pub radical: u32,
+    /// The radical number, in the range 1 to 214.
+    /// as used in the Nelson "Modern Japanese-English Character Dictionary"
+    pub radical_n: u32,
+    /// The stroke count of the kanji, including the radical.
+    pub stroke_count: u32,
+    /// The kanji grade level. 1 through 6 indicates a Kyouiku kanji
+    /// and the grade in which the kanji is taught in Japanese schools.
+    /// 8 indicates it is one of the remaining Jouyou Kanji to be learned
+    /// in junior high school. 9 indicates it is a Jinmeiyou (for use
+    /// in names) kanji which in addition to the Jouyou kanji are approved
+    /// for use in family name registers and other official documents. 10
+    /// also indicates a Jinmeiyou kanji which is a variant of a
+    /// Jouyou kanji. [G]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grade: Option<u32>,
+    /// A frequency-of-use ranking. The 2,500 most-used characters have a
+    /// ranking; those characters that lack this field are not ranked. The
+    /// frequency is a number from 1 to 2,500 that expresses the relative
+    /// frequency of occurrence of a character in modern Japanese. This is
+    /// based on a survey in newspapers, so it is biassed towards kanji
+    /// used in newspaper articles. The discrimination between the less
+    /// frequently used kanji is not strong. (Actually there are 2,501
+    /// kanji ranked as there was a tie.)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub freq: Option<u32>,
+    /// The (former) Japanese Language Proficiency test level for this kanji.
+    /// Values range from 1 (most advanced) to 4 (most elementary). This field
+    /// does not appear for kanji that were not required for any JLPT level.
+    /// Note that the JLPT test levels changed in 2010, with a new 5-level
+    /// system (N1 to N5) being introduced. No official kanji lists are
+    /// available for the new levels. The new levels are regarded as
+    /// being similar to the old levels except that the old level 2 is
+    /// now divided between N2 and N3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jlpt: Option<u32>,
+    /// Estimate of the new JLPT value from various sources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jlptn: Option<u32>,
+}
+
+impl Kanji {
+    pub fn builder(literal: char, info: Info, references: References) -> KanjiBuilder {
+        KanjiBuilder(Kanji {
+            literal,
+            info,
+            references,
+            on_readings: vec![],
+            kun_readings: vec![],
+            meanings: vec![],
+            nanoris: vec![],
+            variants: vec![],
+            query: vec![],
+            top_words: vec![],
+            similar: vec![],
+            dict_index: BTreeMap::new(),
+        })
+    }
+
+    pub fn literal(&self) -> char {
+        self.literal
+    }
+
+    pub fn info(&self) -> &Info {
+        &self.info
+    }
+
+    pub fn references(&self) -> &References {
+        &self.references
+    }
+
+    pub fn on_readings(&self) -> &[String] {
+        &self.on_readings
+    }
+
+    pub fn kun_readings(&self) -> &[String] {
+        &self.kun_readings
+    }
+
+    pub fn meanings(&self) -> &[String] {
+        &self.meanings
+    }
+
+    pub fn nanoris(&self) -> &[String] {
+        &self.nanoris
+    }
+
+    pub fn variants(&self) -> &[char] {
+        &self.variants
+    }
+
+    pub fn query(&self) -> &[QueryCode] {
+        &self.query
+    }
+
+    pub fn top_words(&self) -> &[Word] {
+        &self.top_words
+    }
+
+    pub fn similar(&self) -> &[char] {
+        &self.similar
+    }
+
+    pub fn dict_index(&self) -> &BTreeMap<String, u32> {
+        &self.dict_index
+    }
+
+    /// The SKIP code of the kanji, leaving out misclassifications
+    pub fn skip(&self) -> Option<Skip> {
+        self.codes("skip").next()
+    }
+
+    /// The SKIP codes the kanji is commonly misclassified under
+    pub fn skip_misclass(&self) -> Vec<Skip> {
+        self.query
+            .iter()
+            .filter(|q| q.qc_type == "skip" && q.misclass.is_some())
+            .filter_map(|q| q.code.parse().ok())
+            .collect()
+    }
+
+    /// The Four Corner codes of the kanji, of which there can be several
+    pub fn four_corner(&self) -> Vec<FourCorner> {
+        self.codes("four_corner").collect()
+    }
+
+    /// The Spahn and Hadamitzky descriptor of the kanji
+    pub fn sh_desc(&self) -> Option<ShDesc> {
+        self.codes("sh_desc").next()
+    }
+
+    /// The correctly classified codes of a kind which parse
+    fn codes<'a, T: FromStr + 'a>(&'a self, qc_type: &'a str) -> impl Iterator<Item = T> + 'a {
+        self.query
+            .iter()
+            .filter(move |q| q.qc_type == qc_type && q.misclass.is_none())
+            .filter_map(|q| q.code.parse().ok())
+    }
+}
+
+/// Builds a [`Kanji`], with every list empty unless set
+#[derive(Clone, Debug)]
+pub struct KanjiBuilder(Kanji);
+
+impl KanjiBuilder {
+    pub fn on_readings(mut self, on_readings: Vec<String>) -> Self {
+        self.0.on_readings = on_readings;
+        self
+    }
+
+    pub fn kun_readings(mut self, kun_readings: Vec<String>) -> Self {
+        self.0.kun_readings = kun_readings;
+        self
+    }
+
+    pub fn meanings(mut self, meanings: Vec<String>) -> Self {
+        self.0.meanings = meanings;
+        self
+    }
+
+    pub fn nanoris(mut self, nanoris: Vec<String>) -> Self {
+        self.0.nanoris = nanoris;
+        self
+    }
+
+    pub fn variants(mut self, variants: Vec<char>) -> Self {
+        self.0.variants = variants;
+        self
+    }
+
+    pub fn query(mut self, query: Vec<QueryCode>) -> Self {
+        self.0.query = query;
+        self
+    }
+
+    pub fn top_words(mut self, top_words: Vec<Word>) -> Self {
+        self.0.top_words = top_words;
+        self
+    }
+
+    pub fn similar(mut self, similar: Vec<char>) -> Self {
+        self.0.similar = similar;
+        self
+    }
+
+    pub fn dict_index(mut self, dict_index: BTreeMap<String, u32>) -> Self {
+        self.0.dict_index = dict_index;
+        self
+    }
+
+    pub fn build(self) -> Kanji {
+        self.0
+    }
+}
+
+impl References {
+    /// `ucs` is the hex codepoint of the kanji, e.g. 4e9c
+    pub fn builder(ucs: impl Into<String>) -> ReferencesBuilder {
+        ReferencesBuilder(References {
+            ucs: ucs.into(),
+            jis208: None,
+            jis212: None,
+            jis213: None,
+            rtk: None,
+            klc: None,
+        })
+    }
+
+    pub fn ucs(&self) -> &str {
+        &self.ucs
+    }
+
+    pub fn jis208(&self) -> Option<&str> {
+        self.jis208.as_deref()
+    }
+
+    pub fn jis212(&self) -> Option<&str> {
+        self.jis212.as_deref()
+    }
+
+    pub fn jis213(&self) -> Option<&str> {
+        self.jis213.as_deref()
+    }
+
+    pub fn rtk(&self) -> Option<u32> {
+        self.rtk
+    }
+
+    pub fn klc(&self) -> Option<u32> {
+        self.klc
+    }
+}
+
+/// Builds [`References`], with every optional reference unset unless set
+#[derive(Clone, Debug)]
+pub struct ReferencesBuilder(References);
+
+impl ReferencesBuilder {
+    pub fn jis208(mut self, jis208: impl Into<Option<String>>) -> Self {
+        self.0.jis208 = jis208.into();
+        self
+    }
+
+    pub fn jis212(mut self, jis212: impl Into<Option<String>>) -> Self {
+        self.0.jis212 = jis212.into();
+        self
+    }
+
+    pub fn jis213(mut self, jis213: impl Into<Option<String>>) -> Self {
+        self.0.jis213 = jis213.into();
+        self
+    }
+
+    pub fn rtk(mut self, rtk: impl Into<Option<u32>>) -> Self {
+        self.0.rtk = rtk.into();
+        self
+    }
+
+    pub fn klc(mut self, klc: impl Into<Option<u32>>) -> Self {
+        self.0.klc = klc.into();
+        self
+    }
+
+    pub fn build(self) -> References {
+        self.0
+    }
+}
+
+impl Info {
+    /// The Nelson radical defaults to the classical one
+    pub fn builder(radical: u32, stroke_count: u32) -> InfoBuilder {
+        InfoBuilder(Info {
+            radical,
+            radical_n: radical,
+            stroke_count,
+            grade: None,
+            freq: None,
+            jlpt: None,
+            jlptn: None,
+        })
+    }
+
+    pub fn radical(&self) -> u32 {
+        self.radical
+    }
+
+    pub fn radical_n(&self) -> u32 {
+        self.radical_n
+    }
+
+    pub fn stroke_count(&self) -> u32 {
+        self.stroke_count
+    }
+
+    pub fn grade(&self) -> Option<u32> {
+        self.grade
+    }
+
+    pub fn freq(&self) -> Option<u32> {
+        self.freq
+    }
+
+    pub fn jlpt(&self) -> Option<u32> {
+        self.jlpt
+    }
+
+    pub fn jlptn(&self) -> Option<u32> {
+        self.jlptn
+    }
+}
+
+/// Builds an [`Info`], with every optional field unset unless set
+#[derive(Clone, Debug)]
+pub struct InfoBuilder(Info);
+
+impl InfoBuilder {
+    pub fn radical_n(mut self, radical_n: u32) -> Self {
+        self.0.radical_n = radical_n;
+        self
+    }
+
+    pub fn grade(mut self, grade: impl Into<Option<u32>>) -> Self {
+        self.0.grade = grade.into();
+        self
+    }
+
+    pub fn freq(mut self, freq: impl Into<Option<u32>>) -> Self {
+        self.0.freq = freq.into();
+        self
+    }
+
+    pub fn jlpt(mut self, jlpt: impl Into<Option<u32>>) -> Self {
+        self.0.jlpt = jlpt.into();
+        self
+    }
+
+    pub fn jlptn(mut self, jlptn: impl Into<Option<u32>>) -> Self {
+        self.0.jlptn = jlptn.into();
+        self
+    }
+
+    pub fn build(self) -> Info {
+        self.0
+    }
+}
+
+#[test]
+fn test_builder() {
+    let info = Info::builder(7, 7).jlptn(1).build();
+    let references = References::builder("4e9c").rtk(1616).build();
+    let kanji = Kanji::builder('亜', info, references)
+        .meanings(vec!["Asia".into()])
+        .build();
+
+    assert_eq!(kanji.literal(), '亜');
+    assert_eq!(kanji.info().radical_n(), 7);
+    assert_eq!(kanji.info().jlptn(), Some(1));
+    assert_eq!(kanji.info().grade(), None);
+    assert_eq!(kanji.references().rtk(), Some(1616));
+    assert_eq!(kanji.meanings(), ["Asia"]);
+    assert!(kanji.on_readings().is_empty());
+}
+
+#[test]
+fn test_query_codes() {
+    let skip = QueryCode {
+        misclass: Some("posn".into()),
+        ..QueryCode::new("skip", "2-4-3")
+    };
+    let kanji = Kanji::builder(
+        '亜',
+        Info::builder(7, 7).build(),
+        References::builder("4e9c").build(),
+    )
+    .query(vec![
+        skip,
+        QueryCode::new("skip", "4-7-1"),
+        QueryCode::new("sh_desc", "0a7.14"),
+        QueryCode::new("four_corner", "1010.6"),
+        QueryCode::new("four_corner", "1071.6"),
+        QueryCode::new("deroo", "1715"),
+    ])
+    .build();
+
+    let correct = kanji.skip().unwrap();
+    assert_eq!(correct.to_string(), "4-7-1");
+    assert_eq!(QueryCode::from(correct), kanji.query()[1]);
+    assert_eq!(kanji.skip_misclass()[0].category, 2);
+    assert_eq!(kanji.sh_desc().unwrap().index, 14);
+    assert_eq!(
+        kanji.four_corner(),
+        [FourCorner([1, 0, 1, 0, 6]), FourCorner([1, 0, 7, 1, 6])]
+    );
+}
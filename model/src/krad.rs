@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+/// The radicals a kanji is composed of, as listed in the KRADFILEs
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Decomposition {
+    pub kanji: char,
+    pub radicals: Vec<char>,
+}
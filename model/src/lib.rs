@@ -0,0 +1,18 @@
+//! The documents populate writes and the backend serves, shared so both
+//! always agree on their shape.
+
+pub mod changelog;
+pub mod entry;
+pub mod example;
+pub mod gloss;
+pub mod hash;
+pub mod kana;
+pub mod kanji;
+pub mod krad;
+pub mod meta;
+pub mod name;
+pub mod namespace;
+pub mod provenance;
+pub mod query;
+pub mod store;
+pub mod strokes;
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// The kind of meta document the schema version is recorded as
+pub const SCHEMA_KIND: &str = "schema_version";
+
+/// The version of the stored documents this build reads and writes.
+/// Bumped with every migration in `populate migrate`.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The version of the stored documents, kept in the "meta" collection.
+/// Databases written before versioning have none, which counts as 0.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SchemaVersion {
+    pub kind: String,
+    pub version: u32,
+    /// RFC 3339 timestamp of when the version was recorded
+    pub updated: String,
+}
+
+/// The kind of meta document the kanjidic header is recorded as
+pub const KANJIDIC_KIND: &str = "kanjidic";
+
+/// The header of the kanjidic file last populated, so clients can tell
+/// when their copy of the data is stale
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct KanjidicMeta {
+    pub kind: String,
+    pub file_version: u32,
+    /// In the format YYYY-NN
+    pub database_version: String,
+    /// In the format YYYY-MM-DD
+    pub date_of_creation: String,
+    /// The number of entries stored
+    pub entries: u64,
+    /// RFC 3339 timestamp of when populate wrote the entries
+    pub populated: String,
+}
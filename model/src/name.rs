@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::entry::{Kanji, Reading};
+
+/// An entry of JMnedict, the dictionary of proper names
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Name {
+    /// A unique numeric sequence number for each entry
+    pub ent_seq: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub kanji: Vec<Kanji>,
+    pub readings: Vec<Reading>,
+    pub translations: Vec<Translation>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Translation {
+    /// The kinds of name, e.g. "family or surname" or "place name"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub name_types: Vec<String>,
+    /// The romanized name or its meaning
+    pub details: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub xrefs: Vec<String>,
+}
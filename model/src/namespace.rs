@@ -0,0 +1,36 @@
+//! Data namespaces, which let separate datasets such as a staging
+//! snapshot live alongside production. Each namespace is a separate
+//! database named after it.
+
+/// The database used when no namespace is given
+pub const DEFAULT_DATABASE: &str = "kanjisho";
+
+/// The longest namespace name accepted
+pub const MAX_LEN: usize = 32;
+
+/// Whether a string can be used as a namespace name
+pub fn is_valid(namespace: &str) -> bool {
+    !namespace.is_empty()
+        && namespace.len() <= MAX_LEN
+        && namespace
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+/// The database holding the data of a namespace
+pub fn database_name(namespace: Option<&str>) -> String {
+    match namespace {
+        Some(ns) => format!("{}_{}", DEFAULT_DATABASE, ns),
+        None => DEFAULT_DATABASE.to_owned(),
+    }
+}
+
+#[test]
+fn test_database_name() {
+    assert_eq!(database_name(None), "kanjisho");
+    assert_eq!(database_name(Some("staging")), "kanjisho_staging");
+    assert!(is_valid("kanjidic_2022"));
+    assert!(!is_valid("a.b"));
+    assert!(!is_valid(""));
+    assert!(!is_valid("$cmd"));
+}
@@ -0,0 +1,26 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Where a piece of data came from
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Source {
+    /// The name of the source, e.g. kanjidic2 or a reference list file.
+    pub name: String,
+    /// The version of the source, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// The sources the fields of a kanji entry were taken from. Stored
+/// alongside the kanjidic collection rather than in the entries themselves.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Provenance {
+    pub literal: char,
+    /// The source of every field not listed in `fields`.
+    pub default: Source,
+    /// Fields merged in from other sources, keyed by their path in the
+    /// entry, e.g. "info.jlptn".
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, Source>,
+}
@@ -0,0 +1,223 @@
+//! The shape codes kanji are looked up by, parsed from and written back
+//! to the forms KANJIDIC uses in [`QueryCode`](crate::kanji::QueryCode).
+
+use std::{fmt, str::FromStr};
+
+/// A code which isn't in the form of its kind
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryCodeError {
+    pub qc_type: &'static str,
+    pub code: String,
+}
+
+impl fmt::Display for QueryCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {} code {:?}", self.qc_type, self.code)
+    }
+}
+
+impl std::error::Error for QueryCodeError {}
+
+fn invalid(qc_type: &'static str, code: &str) -> QueryCodeError {
+    QueryCodeError {
+        qc_type,
+        code: code.to_owned(),
+    }
+}
+
+/// Halpern's SKIP code, n-nn-nn, e.g. 1-4-3
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Skip {
+    /// The pattern of the kanji: 1 left-right, 2 up-down, 3 enclosure or
+    /// 4 solid
+    pub category: u8,
+    /// The strokes in the first part, or the whole kanji for category 4
+    pub strokes1: u8,
+    /// The strokes in the second part, or the kind of solid kanji for
+    /// category 4
+    pub strokes2: u8,
+}
+
+impl FromStr for Skip {
+    type Err = QueryCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |p: &str| {
+            (!p.is_empty() && p.len() <= 2 && p.bytes().all(|b| b.is_ascii_digit()))
+                .then(|| p.parse().ok())
+                .flatten()
+        };
+        let parts: Option<Vec<u8>> = s.split('-').map(number).collect();
+        match parts.as_deref() {
+            Some(&[category @ 1..=4, strokes1, strokes2]) => Ok(Skip {
+                category,
+                strokes1,
+                strokes2,
+            }),
+            _ => Err(invalid("skip", s)),
+        }
+    }
+}
+
+impl fmt::Display for Skip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}-{}", self.category, self.strokes1, self.strokes2)
+    }
+}
+
+/// The Four Corner code, nnnn.n, as its five digits, e.g. 0040.1 is
+/// `[0, 0, 4, 0, 1]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FourCorner(pub [u8; 5]);
+
+impl FourCorner {
+    /// The shapes of the top left, top right, bottom left and bottom
+    /// right corners
+    pub fn corners(&self) -> [u8; 4] {
+        [self.0[0], self.0[1], self.0[2], self.0[3]]
+    }
+
+    /// The shape just above the bottom right corner, which tells apart
+    /// kanji with the same corners
+    pub fn extra(&self) -> u8 {
+        self.0[4]
+    }
+}
+
+impl FromStr for FourCorner {
+    type Err = QueryCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let b = s.as_bytes();
+        let digit = |i: usize| b[i].is_ascii_digit().then(|| b[i] - b'0');
+        let digits = (b.len() == 6 && b[4] == b'.')
+            .then(|| Some([digit(0)?, digit(1)?, digit(2)?, digit(3)?, digit(5)?]))
+            .flatten();
+        digits
+            .map(FourCorner)
+            .ok_or_else(|| invalid("four_corner", s))
+    }
+}
+
+impl fmt::Display for FourCorner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e] = self.0;
+        write!(f, "{}{}{}{}.{}", a, b, c, d, e)
+    }
+}
+
+/// The descriptor of The Kanji Dictionary by Spahn and Hadamitzky,
+/// nxnn.n, e.g. 3k11.2
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShDesc {
+    /// The strokes in the identifying radical
+    pub radical_strokes: u8,
+    /// The radical among those with as many strokes, a to z
+    pub radical: char,
+    /// The strokes besides the radical
+    pub other_strokes: u8,
+    /// The position of the kanji among those with the same radical and
+    /// strokes
+    pub index: u16,
+}
+
+impl FromStr for ShDesc {
+    type Err = QueryCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let split = s.find(|c: char| !c.is_ascii_digit())?;
+            let (radical_strokes, rest) = s.split_at(split);
+            let radical = rest.chars().next().filter(char::is_ascii_lowercase)?;
+            let (other_strokes, index) = rest[1..].split_once('.')?;
+            fn number<T: FromStr>(p: &str) -> Option<T> {
+                match p.bytes().all(|b| b.is_ascii_digit()) {
+                    true => p.parse().ok(),
+                    false => None,
+                }
+            }
+            Some(ShDesc {
+                radical_strokes: number(radical_strokes)?,
+                radical,
+                other_strokes: number(other_strokes)?,
+                index: number(index)?,
+            })
+        };
+        parse().ok_or_else(|| invalid("sh_desc", s))
+    }
+}
+
+impl fmt::Display for ShDesc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}.{}",
+            self.radical_strokes, self.radical, self.other_strokes, self.index
+        )
+    }
+}
+
+/// Check that a code is in the form of its kind. Kinds without a typed
+/// form, such as deroo, are always valid.
+pub fn validate(qc_type: &str, code: &str) -> Result<(), QueryCodeError> {
+    match qc_type {
+        "skip" => code.parse::<Skip>().map(drop),
+        "four_corner" => code.parse::<FourCorner>().map(drop),
+        "sh_desc" => code.parse::<ShDesc>().map(drop),
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn test_skip() {
+    let skip: Skip = "1-4-3".parse().unwrap();
+    assert_eq!(
+        skip,
+        Skip {
+            category: 1,
+            strokes1: 4,
+            strokes2: 3
+        }
+    );
+    assert_eq!(skip.to_string(), "1-4-3");
+    assert_eq!("2-10-12".parse::<Skip>().unwrap().strokes2, 12);
+    for code in ["5-1-1", "1-4", "1-4-3-2", "1--3", "1-+4-3", "1-400-3", ""] {
+        assert!(code.parse::<Skip>().is_err(), "{}", code);
+    }
+}
+
+#[test]
+fn test_four_corner() {
+    let code: FourCorner = "0040.1".parse().unwrap();
+    assert_eq!(code, FourCorner([0, 0, 4, 0, 1]));
+    assert_eq!(code.corners(), [0, 0, 4, 0]);
+    assert_eq!(code.extra(), 1);
+    assert_eq!(code.to_string(), "0040.1");
+    for code in ["0040", "00401", "0040.12", "a040.1", "００40.1"] {
+        assert!(code.parse::<FourCorner>().is_err(), "{}", code);
+    }
+}
+
+#[test]
+fn test_sh_desc() {
+    let code: ShDesc = "0a7.14".parse().unwrap();
+    assert_eq!(
+        code,
+        ShDesc {
+            radical_strokes: 0,
+            radical: 'a',
+            other_strokes: 7,
+            index: 14
+        }
+    );
+    assert_eq!(code.to_string(), "0a7.14");
+    assert_eq!("3k11.2".parse::<ShDesc>().unwrap().other_strokes, 11);
+    for code in ["3k11", "3K11.2", "k11.2", "3k.2", "3k11.+2", "3ｋ11.2"] {
+        assert!(code.parse::<ShDesc>().is_err(), "{}", code);
+    }
+    assert_eq!(
+        validate("sh_desc", "3k11").unwrap_err().to_string(),
+        "invalid sh_desc code \"3k11\""
+    );
+    assert!(validate("deroo", "1234").is_ok());
+}
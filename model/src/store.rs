@@ -0,0 +1,164 @@
+//! A compact read-only file of kanji entries which can be memory-mapped
+//! and searched without a database.
+//!
+//! The layout is a header, an index sorted by literal, and the entries
+//! encoded as MessagePack:
+//!
+//! ```text
+//! magic "KJSB" | version: u32 | count: u32
+//! count * (literal: u32, offset: u32, len: u32)
+//! entries...
+//! ```
+//!
+//! All integers are little endian and offsets are relative to the start
+//! of the entries.
+
+use std::{fs::File, io, path::Path};
+
+use memmap2::Mmap;
+
+use crate::kanji::Kanji;
+
+const MAGIC: &[u8; 4] = b"KJSB";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 12;
+const INDEX_ENTRY_LEN: usize = 12;
+
+/// Encode entries into the store format
+pub fn write(entries: &[Kanji]) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+    let mut sorted: Vec<&Kanji> = entries.iter().collect();
+    sorted.sort_by_key(|k| k.literal);
+
+    let mut index = vec![];
+    let mut data = vec![];
+    for k in sorted {
+        let offset = data.len() as u32;
+        rmp_serde::encode::write_named(&mut data, k)?;
+        index.extend((k.literal as u32).to_le_bytes());
+        index.extend(offset.to_le_bytes());
+        index.extend((data.len() as u32 - offset).to_le_bytes());
+    }
+
+    let mut out = Vec::with_capacity(HEADER_LEN + index.len() + data.len());
+    out.extend(MAGIC);
+    out.extend(VERSION.to_le_bytes());
+    out.extend((entries.len() as u32).to_le_bytes());
+    out.extend(index);
+    out.extend(data);
+    Ok(out)
+}
+
+/// A store backed by any byte buffer
+pub struct Store<B> {
+    buf: B,
+    count: usize,
+}
+
+/// A store backed by a memory-mapped file
+pub type MmapStore = Store<Mmap>;
+
+impl MmapStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // populate replaces the file by renaming a new one over it (see
+        // `parse::source::Dir::write`), so a mapped file never changes
+        let map = unsafe { Mmap::map(&file)? };
+        Store::new(map)
+    }
+}
+
+impl<B: AsRef<[u8]>> Store<B> {
+    /// Validate the header and index of a buffer
+    pub fn new(buf: B) -> io::Result<Self> {
+        let bytes = buf.as_ref();
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(invalid("not a kanji store"));
+        }
+        if read_u32(bytes, 4) != VERSION {
+            return Err(invalid("unsupported kanji store version"));
+        }
+        let count = read_u32(bytes, 8) as usize;
+        if bytes.len() < HEADER_LEN + count * INDEX_ENTRY_LEN {
+            return Err(invalid("truncated kanji store"));
+        }
+
+        Ok(Store { buf, count })
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Every literal in the store, in order
+    pub fn literals(&self) -> impl Iterator<Item = char> + '_ {
+        (0..self.count).filter_map(|i| char::from_u32(self.index_entry(i).0))
+    }
+
+    /// Look up the entry for a literal
+    pub fn get(&self, literal: char) -> io::Result<Option<Kanji>> {
+        let key = literal as u32;
+        let (mut lo, mut hi) = (0, self.count);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let (l, offset, len) = self.index_entry(mid);
+            match l.cmp(&key) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return self.decode(offset, len).map(Some),
+            }
+        }
+        Ok(None)
+    }
+
+    fn index_entry(&self, i: usize) -> (u32, usize, usize) {
+        let bytes = self.buf.as_ref();
+        let at = HEADER_LEN + i * INDEX_ENTRY_LEN;
+        (
+            read_u32(bytes, at),
+            read_u32(bytes, at + 4) as usize,
+            read_u32(bytes, at + 8) as usize,
+        )
+    }
+
+    fn decode(&self, offset: usize, len: usize) -> io::Result<Kanji> {
+        let start = HEADER_LEN + self.count * INDEX_ENTRY_LEN + offset;
+        let record = self
+            .buf
+            .as_ref()
+            .get(start..start + len)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated entry"))?;
+        rmp_serde::from_slice(record).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+#[test]
+fn test_round_trip() {
+    use crate::kanji::{Info, References};
+
+    let kanji = |literal: char, stroke_count| {
+        let info = Info::builder(1, stroke_count).jlptn(5).build();
+        let references = References::builder(format!("{:x}", literal as u32)).build();
+        Kanji::builder(literal, info, references)
+            .meanings(vec!["one".into()])
+            .build()
+    };
+
+    let entries = [kanji('日', 4), kanji('一', 1), kanji('月', 4)];
+    let store = Store::new(write(&entries).unwrap()).unwrap();
+
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.literals().collect::<String>(), "一日月");
+    assert_eq!(store.get('日').unwrap(), Some(entries[0].clone()));
+    assert_eq!(store.get('火').unwrap(), None);
+    assert!(Store::new(b"nope".to_vec()).is_err());
+}
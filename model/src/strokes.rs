@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// The strokes of a kanji in the order they are written, from KanjiVG
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Strokes {
+    pub literal: char,
+    pub strokes: Vec<Stroke>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Stroke {
+    /// The kind of stroke as a CJK stroke character, e.g. ㇐
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// SVG path data in a 109 by 109 box
+    pub path: String,
+}
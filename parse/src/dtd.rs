@@ -0,0 +1,488 @@
+//! The parts of the DTD roxmltree leaves out. It only reads the internal
+//! subset, and only for entities: attributes left out of an element are
+//! missing even when an ATTLIST declaration gives them a default, and
+//! entities declared in an external subset are unknown.
+
+use std::{borrow::Cow, collections::HashMap, io};
+
+use roxmltree::Node;
+
+use crate::DataSource;
+
+/// The namespace of the xml: prefix
+const XML_NS: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Default attribute values by element and attribute name
+#[derive(Debug, Default)]
+pub struct Defaults(HashMap<(String, String), String>);
+
+impl Defaults {
+    /// Read every default and #FIXED value from the ATTLIST declarations
+    /// of the document's internal subset
+    pub fn parse(text: &str) -> Self {
+        let mut out = HashMap::new();
+        let Some(start) = text.find("<!DOCTYPE") else {
+            return Defaults(out);
+        };
+        let subset = &text[start..];
+        let subset = &subset[..subset_end(subset).unwrap_or(subset.len())];
+
+        for decl in subset.split("<!ATTLIST").skip(1) {
+            let decl = &decl[..find_unquoted(decl, b'>').unwrap_or(decl.len())];
+            let mut tokens = Tokens(decl);
+            let Some(element) = tokens.next() else {
+                continue;
+            };
+            while let Some(name) = tokens.next() {
+                let Some(mut default) = tokens.next().and_then(|_| tokens.next()) else {
+                    break;
+                };
+                if default == "#FIXED" {
+                    default = tokens.next().unwrap_or_default();
+                }
+                if let Some(value) = literal(default) {
+                    out.insert((element.into(), name.into()), value.into());
+                }
+            }
+        }
+
+        Defaults(out)
+    }
+
+    /// The declared default of an attribute
+    pub fn get(&self, element: &str, attribute: &str) -> Option<&str> {
+        self.0
+            .get(&(element.to_owned(), attribute.to_owned()))
+            .map(String::as_str)
+    }
+
+    /// The value of an attribute of a node, or its declared default. Names
+    /// may have the xml: prefix.
+    pub fn attribute<'a>(&'a self, node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+        let value = match name.strip_prefix("xml:") {
+            Some(local) => node.attribute((XML_NS, local)),
+            None => node.attribute(name),
+        };
+        value.or_else(|| self.get(node.tag_name().name(), name))
+    }
+}
+
+/// Decides how the external DTD subset a document references is loaded
+pub trait EntityResolver {
+    /// The text of the DTD with the given identifiers, or None to leave
+    /// it unresolved
+    fn resolve(&self, public: Option<&str>, system: &str) -> io::Result<Option<String>>;
+}
+
+/// Leaves every external subset unresolved
+pub struct NoResolver;
+
+impl EntityResolver for NoResolver {
+    fn resolve(&self, _: Option<&str>, _: &str) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// Resolves system identifiers as files of a data source, e.g. a local
+/// copy of the DTD next to the dictionary
+pub struct SourceResolver<'a>(pub &'a dyn DataSource);
+
+impl EntityResolver for SourceResolver<'_> {
+    fn resolve(&self, _: Option<&str>, system: &str) -> io::Result<Option<String>> {
+        match self.0.read_to_string(system) {
+            Ok(text) => Ok(Some(text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Move the external subset of a document into its internal subset, so
+/// the entities and defaults it declares are seen by the parser. The text
+/// is returned unchanged when there is no external subset or the resolver
+/// leaves it unresolved.
+pub fn inline_external<'a>(
+    text: &'a str,
+    resolver: &dyn EntityResolver,
+) -> io::Result<Cow<'a, str>> {
+    let Some(start) = text.find("<!DOCTYPE") else {
+        return Ok(Cow::Borrowed(text));
+    };
+    let rest = &text[start + "<!DOCTYPE".len()..];
+    let mut tokens = Tokens(rest);
+    let (Some(name), Some(keyword)) = (tokens.next(), tokens.next()) else {
+        return Ok(Cow::Borrowed(text));
+    };
+    let unquote = |t: &'a str| t.get(1..t.len().saturating_sub(1)).unwrap_or_default();
+    let (public, system) = match keyword {
+        "SYSTEM" => (None, tokens.next().map(unquote)),
+        "PUBLIC" => (tokens.next().map(unquote), tokens.next().map(unquote)),
+        _ => return Ok(Cow::Borrowed(text)),
+    };
+    let Some(system) = system else {
+        return Ok(Cow::Borrowed(text));
+    };
+    let Some(external) = resolver.resolve(public, system)? else {
+        return Ok(Cow::Borrowed(text));
+    };
+    // a text declaration may only start the external subset
+    let external = match external.trim_start().strip_prefix("<?xml") {
+        Some(decl) => decl[decl.find("?>").map_or(0, |i| i + 2)..].to_owned(),
+        None => external,
+    };
+
+    let after = tokens.0.trim_start();
+    let (internal, end) = match after.strip_prefix('[') {
+        Some(subset) => match subset_end(subset) {
+            Some(i) => (&subset[..i], &subset[i + 1..]),
+            None => return Ok(Cow::Borrowed(text)),
+        },
+        None => ("", after),
+    };
+    let Some(end) = end.trim_start().strip_prefix('>') else {
+        return Ok(Cow::Borrowed(text));
+    };
+
+    // the internal subset comes first so its declarations take precedence
+    Ok(Cow::Owned(format!(
+        "{}<!DOCTYPE {} [{}\n{}]>{}",
+        &text[..start],
+        name,
+        internal,
+        external,
+        end
+    )))
+}
+
+/// How deeply parameter entities may reference each other
+const MAX_DEPTH: usize = 16;
+/// How many bytes expanding parameter entities may add to a document
+const MAX_EXPANSION: usize = 1 << 20;
+
+/// Why the parameter entities of a document could not be expanded
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The entity references itself, directly or through others
+    Recursive(String),
+    /// The expansion grew past the size limit
+    TooLarge,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Recursive(name) => write!(f, "parameter entity %{}; is recursive", name),
+            Error::TooLarge => write!(f, "parameter entities expand past {} bytes", MAX_EXPANSION),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The end of the internal subset starting at `s`, skipping brackets in
+/// quoted values and comments
+fn subset_end(s: &str) -> Option<usize> {
+    find_unquoted(s, b']')
+}
+
+/// The first `c` in `s` outside quoted values and comments
+fn find_unquoted(s: &str, c: u8) -> Option<usize> {
+    let mut i = 0;
+    while i < s.len() {
+        let rest = &s[i..];
+        if rest.starts_with("<!--") {
+            i += rest.find("-->")? + 3;
+            continue;
+        }
+        match rest.as_bytes()[0] {
+            q @ (b'"' | b'\'') => i += rest[1..].find(q as char)? + 2,
+            b if b == c => return Some(i),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Expand the parameter entities of the internal subset, which roxmltree
+/// rejects. Their declarations are removed and every reference to them,
+/// between or within declarations, is replaced by its value. References
+/// to undeclared entities are left for the parser to report.
+pub fn expand_parameter_entities(text: &str) -> Result<Cow<'_, str>, Error> {
+    let Some(start) = text.find("<!DOCTYPE") else {
+        return Ok(Cow::Borrowed(text));
+    };
+    let Some(open) = text[start..].find('[').map(|i| start + i + 1) else {
+        return Ok(Cow::Borrowed(text));
+    };
+    let Some(close) = subset_end(&text[open..]).map(|i| open + i) else {
+        return Ok(Cow::Borrowed(text));
+    };
+    let subset = &text[open..close];
+
+    // the first declaration of a name binds
+    let mut entities: HashMap<&str, &str> = HashMap::new();
+    let mut rest = String::with_capacity(subset.len());
+    let mut last = 0;
+    for (i, _) in subset.match_indices("<!ENTITY") {
+        if i < last {
+            continue;
+        }
+        let mut tokens = Tokens(&subset[i + "<!ENTITY".len()..]);
+        let (Some("%"), Some(name), Some(value)) = (tokens.next(), tokens.next(), tokens.next())
+        else {
+            continue;
+        };
+        // external entities, declared with SYSTEM or PUBLIC, are kept
+        // along with their references
+        let Some(value) = literal(value) else {
+            continue;
+        };
+        let Some(end) = tokens.0.find('>') else {
+            continue;
+        };
+        entities.entry(name).or_insert(value);
+        rest.push_str(&subset[last..i]);
+        last = subset.len() - tokens.0.len() + end + 1;
+    }
+    if entities.is_empty() {
+        return Ok(Cow::Borrowed(text));
+    }
+    rest.push_str(&subset[last..]);
+
+    let mut budget = MAX_EXPANSION;
+    let expanded = expand(&rest, &entities, &mut vec![], &mut budget)?;
+    Ok(Cow::Owned(format!(
+        "{}{}{}",
+        &text[..open],
+        expanded,
+        &text[close..]
+    )))
+}
+
+/// Replace the references in `s`, with `stack` the entities being expanded
+fn expand<'a>(
+    s: &str,
+    entities: &HashMap<&'a str, &'a str>,
+    stack: &mut Vec<&'a str>,
+    budget: &mut usize,
+) -> Result<String, Error> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('%') {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let entity = after
+            .find(';')
+            .and_then(|end| entities.get_key_value(&after[..end]));
+        let Some((&name, &value)) = entity else {
+            out.push('%');
+            rest = after;
+            continue;
+        };
+        if stack.contains(&name) || stack.len() >= MAX_DEPTH {
+            return Err(Error::Recursive(name.into()));
+        }
+        stack.push(name);
+        let value = expand(value, entities, stack, budget)?;
+        stack.pop();
+        *budget = budget.checked_sub(value.len()).ok_or(Error::TooLarge)?;
+        out.push_str(&value);
+        rest = &after[name.len() + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// The tokens of a declaration: names, keywords, quoted values with their
+/// quotes and parenthesized enumerations
+struct Tokens<'a>(&'a str);
+
+/// The value of a quoted literal token, or None for keywords and
+/// unterminated quotes
+fn literal(token: &str) -> Option<&str> {
+    let quote = token.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    match token.len() >= 2 && token.ends_with(quote) {
+        true => Some(&token[1..token.len() - 1]),
+        false => None,
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let s = self.0.trim_start();
+        let end = match s.chars().next()? {
+            q @ ('"' | '\'') => s[1..].find(q).map_or(s.len(), |i| i + 2),
+            '(' => s.find(')').map_or(s.len(), |i| i + 1),
+            _ => s
+                .find(|c: char| c.is_whitespace() || c == '(')
+                .unwrap_or(s.len()),
+        };
+        self.0 = &s[end..];
+        Some(&s[..end])
+    }
+}
+
+#[test]
+fn test_defaults() {
+    let text = r#"<?xml version="1.0"?>
+<!DOCTYPE JMdict [
+<!ELEMENT gloss (#PCDATA | pri)*>
+<!ATTLIST gloss xml:lang CDATA "eng">
+<!ATTLIST gloss
+    g_gend CDATA #IMPLIED
+    g_type CDATA #IMPLIED
+    >
+<!ATTLIST lsource ls_type (full|part) 'full' ls_wasei CDATA #FIXED "n">
+<!ATTLIST bad>
+]>
+<JMdict><gloss xml:lang="ger">Arbeit</gloss><gloss>work</gloss><lsource/></JMdict>"#;
+    let defaults = Defaults::parse(text);
+    assert_eq!(defaults.get("gloss", "xml:lang"), Some("eng"));
+    assert_eq!(defaults.get("gloss", "g_type"), None);
+    assert_eq!(defaults.get("lsource", "ls_type"), Some("full"));
+    assert_eq!(defaults.get("lsource", "ls_wasei"), Some("n"));
+
+    let doc = roxmltree::Document::parse_with_options(
+        text,
+        roxmltree::ParsingOptions { allow_dtd: true },
+    )
+    .unwrap();
+    let nodes: Vec<_> = doc.root_element().children().collect();
+    assert_eq!(defaults.attribute(nodes[0], "xml:lang"), Some("ger"));
+    assert_eq!(defaults.attribute(nodes[1], "xml:lang"), Some("eng"));
+    assert_eq!(defaults.attribute(nodes[2], "ls_type"), Some("full"));
+
+    assert!(Defaults::parse("<a/>").get("a", "b").is_none());
+
+    // the subset ends at its closing bracket, not a quoted ]>
+    let quoted = r#"<!DOCTYPE a [
+<!ATTLIST a b CDATA "x]>y">
+<!ATTLIST a c CDATA "z">
+]><a/>"#;
+    let defaults = Defaults::parse(quoted);
+    assert_eq!(defaults.get("a", "b"), Some("x]>y"));
+    assert_eq!(defaults.get("a", "c"), Some("z"));
+
+    // an unterminated default is skipped
+    for text in [
+        r#"<!DOCTYPE a [<!ATTLIST a b CDATA ""#,
+        "<!DOCTYPE a [<!ATTLIST a b CDATA 'x",
+    ] {
+        assert_eq!(Defaults::parse(text).get("a", "b"), None);
+    }
+}
+
+#[test]
+fn test_inline_external() {
+    use crate::source::Embedded;
+
+    let files = Embedded(&[(
+        "JMdict.dtd",
+        b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!ENTITY n \"noun\">\n<!ENTITY v \"verb\">",
+    )]);
+    let text = r#"<?xml version="1.0"?>
+<!DOCTYPE JMdict SYSTEM "JMdict.dtd" [<!ENTITY v "verb (internal)">]>
+<JMdict><pos>&n;</pos><pos>&v;</pos></JMdict>"#;
+
+    let inlined = inline_external(text, &SourceResolver(&files)).unwrap();
+    let opt = roxmltree::ParsingOptions { allow_dtd: true };
+    let doc = roxmltree::Document::parse_with_options(&inlined, opt).unwrap();
+    let pos: Vec<_> = doc
+        .root_element()
+        .children()
+        .filter_map(|n| n.text())
+        .collect();
+    assert_eq!(pos, ["noun", "verb (internal)"]);
+
+    // a bracket quoted in the internal subset doesn't end it
+    let quoted = text.replace("(internal)", "[internal]");
+    let inlined = inline_external(&quoted, &SourceResolver(&files)).unwrap();
+    let doc = roxmltree::Document::parse_with_options(&inlined, opt).unwrap();
+    let pos: Vec<_> = doc
+        .root_element()
+        .children()
+        .filter_map(|n| n.text())
+        .collect();
+    assert_eq!(pos, ["noun", "verb [internal]"]);
+
+    // unresolved, the document is left for the parser to reject
+    assert!(matches!(
+        inline_external(text, &NoResolver),
+        Ok(Cow::Borrowed(_))
+    ));
+    let missing = text.replace("JMdict.dtd", "other.dtd");
+    assert!(matches!(
+        inline_external(&missing, &SourceResolver(&files)),
+        Ok(Cow::Borrowed(_))
+    ));
+    let public = r#"<!DOCTYPE a PUBLIC "-//EDRDG//JMdict//EN" "JMdict.dtd"><a>&n;</a>"#;
+    let inlined = inline_external(public, &SourceResolver(&files)).unwrap();
+    assert!(roxmltree::Document::parse_with_options(&inlined, opt).is_ok());
+}
+
+#[test]
+fn test_expand_parameter_entities() {
+    let text = r#"<?xml version="1.0"?>
+<!DOCTYPE JMdict [
+<!ENTITY % text "CDATA">
+<!ENTITY % lang "xml:lang %text; 'eng'">
+<!ENTITY % nouns "<!ENTITY n 'noun'>">
+<!-- a comment with ] in it -->
+%nouns;
+<!ATTLIST gloss %lang;>
+<!ENTITY pct "100%">
+]>
+<JMdict><gloss>&n; &pct;</gloss></JMdict>"#;
+
+    let expanded = expand_parameter_entities(text).unwrap();
+    assert!(!expanded.contains("<!ENTITY %"));
+    let opt = roxmltree::ParsingOptions { allow_dtd: true };
+    let doc = roxmltree::Document::parse_with_options(&expanded, opt).unwrap();
+    let gloss = doc.root_element().first_child().unwrap();
+    assert_eq!(gloss.text(), Some("noun 100%"));
+    assert_eq!(
+        Defaults::parse(&expanded).get("gloss", "xml:lang"),
+        Some("eng")
+    );
+
+    let plain = "<!DOCTYPE a [<!ENTITY n 'noun'>]><a>&n;</a>";
+    assert!(matches!(
+        expand_parameter_entities(plain),
+        Ok(Cow::Borrowed(_))
+    ));
+
+    let external = r#"<!DOCTYPE a [<!ENTITY % x SYSTEM "x.dtd"> %x;]><a/>"#;
+    assert!(matches!(
+        expand_parameter_entities(external),
+        Ok(Cow::Borrowed(_))
+    ));
+    let mixed = r#"<!DOCTYPE a [<!ENTITY % x PUBLIC "-//X//EN" "x.dtd"><!ENTITY % y "<!ENTITY n 'noun'>"> %x; %y;]><a/>"#;
+    assert_eq!(
+        expand_parameter_entities(mixed).unwrap(),
+        r#"<!DOCTYPE a [<!ENTITY % x PUBLIC "-//X//EN" "x.dtd"> %x; <!ENTITY n 'noun'>]><a/>"#
+    );
+
+    let recursive = "<!DOCTYPE a [<!ENTITY % a '%b;'><!ENTITY % b '%a;'> %a;]><a/>";
+    assert_eq!(
+        expand_parameter_entities(recursive).map(|_| ()),
+        Err(Error::Recursive("a".into()))
+    );
+
+    let laughs = format!(
+        "<!DOCTYPE a [<!ENTITY % l0 '{}'>{}%l9;]><a/>",
+        "lol".repeat(100),
+        (1..10)
+            .map(|i| format!(
+                "<!ENTITY % l{} '{}'>",
+                i,
+                format!("%l{};", i - 1).repeat(10)
+            ))
+            .collect::<String>()
+    );
+    assert_eq!(
+        expand_parameter_entities(&laughs).map(|_| ()),
+        Err(Error::TooLarge)
+    );
+}
@@ -0,0 +1,103 @@
+//! Decoding dictionary files which are not UTF-8. Older Japanese
+//! dictionaries are often EUC-JP or Shift_JIS, and files saved on Windows
+//! may be UTF-16. Without the `encoding` feature only UTF-8 is read.
+
+use std::io;
+
+/// The encoding named by the XML declaration at the start of a file
+#[cfg(feature = "encoding")]
+fn declared_encoding(bytes: &[u8]) -> Option<&'static encoding_rs::Encoding> {
+    let head = &bytes[..bytes.len().min(256)];
+    let decl = head.strip_prefix(b"<?xml")?;
+    let decl = &decl[..decl.windows(2).position(|w| w == b"?>")?];
+    let at = decl.windows(8).position(|w| w == b"encoding")?;
+    let rest = &decl[at + 8..];
+    let start = rest.iter().position(|b| *b == b'"' || *b == b'\'')?;
+    let value = &rest[start + 1..];
+    let end = value.iter().position(|b| *b == rest[start])?;
+    encoding_rs::Encoding::for_label(&value[..end])
+}
+
+/// Decode an XML file to UTF-8, by its byte order mark or else its XML
+/// declaration, defaulting to UTF-8. A byte order mark is dropped.
+pub fn decode_xml(bytes: Vec<u8>) -> io::Result<String> {
+    #[cfg(feature = "encoding")]
+    {
+        let (encoding, bom) = match encoding_rs::Encoding::for_bom(&bytes) {
+            Some((encoding, len)) => (encoding, len),
+            None => (declared_encoding(&bytes).unwrap_or(encoding_rs::UTF_8), 0),
+        };
+        if encoding != encoding_rs::UTF_8 {
+            return encoding
+                .decode_without_bom_handling_and_without_replacement(&bytes[bom..])
+                .map(|s| s.into_owned())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid {} text", encoding.name()),
+                    )
+                });
+        }
+    }
+
+    let bom = if bytes.starts_with(b"\xEF\xBB\xBF") {
+        3
+    } else {
+        0
+    };
+    String::from_utf8(bytes[bom..].to_vec())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Whether an XML file starting with `head` is UTF-8, so that it can be
+/// read as it is rather than decoded first
+pub fn is_utf8(head: &[u8]) -> bool {
+    #[cfg(feature = "encoding")]
+    {
+        let encoding = match encoding_rs::Encoding::for_bom(head) {
+            Some((encoding, _)) => encoding,
+            None => declared_encoding(head).unwrap_or(encoding_rs::UTF_8),
+        };
+        if encoding != encoding_rs::UTF_8 {
+            return false;
+        }
+    }
+
+    !head.starts_with(b"\xFF\xFE") && !head.starts_with(b"\xFE\xFF")
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn test_is_utf8() {
+    assert!(is_utf8(b"<?xml version=\"1.0\"?><a/>"));
+    assert!(is_utf8(
+        b"\xEF\xBB\xBF<?xml version='1.0' encoding='utf-8'?><a/>"
+    ));
+    assert!(is_utf8(b"<a/>"));
+    assert!(!is_utf8(b"<?xml version=\"1.0\" encoding=\"EUC-JP\"?><a/>"));
+    assert!(!is_utf8(b"\xFF\xFE<\0a\0/\0>\0"));
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn test_decode_xml() {
+    let utf8 = "<?xml version=\"1.0\"?><a>水</a>";
+    assert_eq!(decode_xml(utf8.into()).unwrap(), utf8);
+    let with_bom = [b"\xEF\xBB\xBF".as_slice(), utf8.as_bytes()].concat();
+    assert_eq!(decode_xml(with_bom).unwrap(), utf8);
+
+    let euc = "<?xml version=\"1.0\" encoding=\"EUC-JP\"?><a>水</a>";
+    let (bytes, ..) = encoding_rs::EUC_JP.encode(euc);
+    assert_eq!(decode_xml(bytes.into_owned()).unwrap(), euc);
+
+    let sjis = "<?xml version='1.0' encoding='Shift_JIS'?><a>漢字</a>";
+    let (bytes, ..) = encoding_rs::SHIFT_JIS.encode(sjis);
+    assert_eq!(decode_xml(bytes.into_owned()).unwrap(), sjis);
+
+    let mut utf16 = vec![0xFF, 0xFE];
+    utf16.extend(utf8.encode_utf16().flat_map(|u| u.to_le_bytes()));
+    assert_eq!(decode_xml(utf16).unwrap(), utf8);
+
+    let broken = b"<?xml version=\"1.0\" encoding=\"EUC-JP\"?><a>\xFF\xFF</a>".to_vec();
+    assert!(decode_xml(broken).is_err());
+}
@@ -1,13 +1,18 @@
 use roxmltree::{Document, Node, ParsingOptions};
 
+use crate::{dtd::Defaults, PROGRESS_INTERVAL};
+
 pub struct JMdict<'a> {
     doc: Document<'a>,
+    /// Attribute defaults declared in the DTD
+    defaults: Defaults,
 }
 
 /// Entries consist of kanji elements, reading elements,
 /// general information and sense elements. Each entry must have at
 /// least one reading element and one sense element. Others are optional.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Entry {
     /// A unique numeric sequence number for each entry
     pub ent_seq: u32,
@@ -28,6 +33,7 @@ pub struct Entry {
 /// fields. Synonyms are not included; they may be indicated in the
 /// cross-reference field associated with the sense element.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Kanji {
     /// This element will contain a word or short phrase in Japanese
     /// which is written using at least one non-kana character (usually kanji,
@@ -46,23 +52,23 @@ pub struct Kanji {
     /// is used. This field is intended for use either by applications which
     /// want to concentrate on entries of a particular priority, or to
     /// generate subset files.
-    /// 
+    ///
     /// The current values in this field are:
     ///   - news1/2: appears in the "wordfreq" file compiled by Alexandre Girardi
     ///     from the Mainichi Shimbun. (See the Monash ftp archive for a copy.)
     ///     Words in the first 12,000 in that file are marked "news1" and words
     ///     in the second 12,000 are marked "news2".
-    /// 
+    ///
     ///   - ichi1/2: appears in the "Ichimango goi bunruishuu", Senmon Kyouiku
     ///     Publishing, Tokyo, 1998. (The entries marked "ichi2" were
     ///     demoted from ichi1 because they were observed to have low
     ///     frequencies in the WWW and newspapers.)
-    /// 
+    ///
     ///   - spec1 and spec2: a small number of words use this marker when they
     ///     are detected as being common, but are not included in other lists.
-    /// 
+    ///
     ///   - gai1/2: common loanwords, based on the wordfreq file.
-    /// 
+    ///
     ///   - nfxx: this is an indicator of frequency-of-use ranking in the
     ///     wordfreq file. "xx" is the number of the set of 500 words in which
     ///     the entry can be found, with "01" assigned to the first 500, "02"
@@ -83,6 +89,7 @@ pub struct Kanji {
 /// kanji element, i.e. in the case of a word or phrase written
 /// entirely in kana, these elements will define the entry.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Reading {
     /// This element content is restricted to kana and related
     /// characters such as chouon and kurikaeshi. Kana usage will be
@@ -113,6 +120,7 @@ pub struct Reading {
 /// are several distinctly different meanings of the word, multiple
 /// sense elements will be employed.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Sense {
     /// This element, if present, indicate that the sense is restricted
     /// to the lexeme represented by the keb.
@@ -162,6 +170,7 @@ pub struct Sense {
 /// than English, the language is indicated by the xml:lang attribute.
 /// The element value (if any) is the source word or phrase.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Lang {
     pub lsource: String,
     /// The xml:lang attribute defines the language(s) from which
@@ -186,6 +195,7 @@ pub struct Lang {
 /// Japanese word. This element would normally be present, however it
 /// may be omitted in entries which are purely for a cross-reference.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Gloss {
     pub gloss: String,
     /// The xml:lang attribute defines the target language of the
@@ -196,37 +206,56 @@ pub struct Gloss {
     /// The g_type attribute specifies that the gloss is of a particular
     /// type, e.g. "lit" (literal), "fig" (figurative), "expl" (explanation).
     pub g_type: Option<String>,
+    /// Marks highlighting particular glosses with a pri element inside the
+    /// gloss. The DTD allows it though no current entry uses it.
+    pub pri: Vec<String>,
 }
 
-
-
 impl<'a> JMdict<'a> {
-    pub fn entries(self: &'a Self) -> impl Iterator<Item = Entry> + 'a {
-        return self
-            .doc
+    /// The date the file was created (YYYY-MM-DD), from the comment
+    /// starting the entries
+    pub fn created(&self) -> Option<&str> {
+        self.doc
+            .root_element()
+            .children()
+            .find_map(|n| n.text()?.trim().strip_prefix("JMdict created:"))
+            .map(str::trim)
+    }
+
+    pub fn entries(&'a self) -> impl Iterator<Item = Entry> + 'a {
+        self.doc
             .root_element()
             .children()
             .filter(|n| n.is_element())
-            .map(parse_entry);
+            .enumerate()
+            .map(|(i, n)| {
+                if (i + 1) % PROGRESS_INTERVAL == 0 {
+                    tracing::debug!(entries = i + 1, "parsing jmdict");
+                }
+                parse_entry(n, &self.defaults)
+            })
     }
 }
 
-
-pub fn parse<'a>(text: &'a str) -> JMdict<'a> {
+pub fn parse(text: &str) -> JMdict<'_> {
+    let _span = tracing::info_span!("parse_xml", dict = "jmdict", bytes = text.len()).entered();
     let opt = ParsingOptions { allow_dtd: true };
     let doc = Document::parse_with_options(text, opt).expect("failed to parse");
 
-    return JMdict { doc };
+    let defaults = Defaults::parse(text);
+
+    JMdict { doc, defaults }
 }
 
-fn parse_entry(node: Node) -> Entry {
+fn parse_entry(node: Node, defaults: &Defaults) -> Entry {
     let mut e = Entry::default();
 
     for n in node.children().filter(|n| n.is_element()) {
         match n.tag_name().name() {
             "ent_seq" => e.ent_seq = get_num(n.text()),
             "k_ele" => e.k_ele.push(parse_k_ele(n)),
-
+            "r_ele" => e.r_ele.push(parse_r_ele(n)),
+            "sense" => e.sense.push(parse_sense(n, defaults)),
             tag => println!("Warning: unexpected tag name {}", tag),
         }
     }
@@ -234,38 +263,219 @@ fn parse_entry(node: Node) -> Entry {
     e
 }
 
+pub(crate) fn parse_k_ele(node: Node) -> Kanji {
+    let mut k = Kanji::default();
+
+    for n in node.children().filter(|n| n.is_element()) {
+        match n.tag_name().name() {
+            "keb" => k.keb = get_text(n.text()),
+            "ke_inf" => push_text(&mut k.ke_inf, n.text()),
+            "ke_pri" => push_text(&mut k.ke_pri, n.text()),
+            tag => println!("Warning: unexpected tag name in k_ele: {}", tag),
+        }
+    }
+
+    k
+}
+
+pub(crate) fn parse_r_ele(node: Node) -> Reading {
+    let mut r = Reading::default();
+
+    for n in node.children().filter(|n| n.is_element()) {
+        match n.tag_name().name() {
+            "reb" => r.reb = get_text(n.text()),
+            "re_nokanji" => r.re_nokanji = true,
+            "re_restr" => push_text(&mut r.re_restr, n.text()),
+            "re_inf" => push_text(&mut r.re_inf, n.text()),
+            "re_pri" => push_text(&mut r.re_pri, n.text()),
+            tag => println!("Warning: unexpected tag name in r_ele: {}", tag),
+        }
+    }
+
+    r
+}
+
+fn parse_sense(node: Node, defaults: &Defaults) -> Sense {
+    let mut s = Sense::default();
 
-fn parse_k_ele(node: Node) -> Kanji {
     for n in node.children().filter(|n| n.is_element()) {
         match n.tag_name().name() {
-            "grade" => entry.grade = Some(get_num(n.text())),
-            "stroke_count" => entry.stroke_count.push(get_num(n.text())),
-            "variant" => entry.variant.push(Variant {
-                variant: get_text(n.text()),
-                var_type: get_text(n.attribute("var_type")),
+            "stagk" => push_text(&mut s.stagk, n.text()),
+            "stagr" => push_text(&mut s.stagr, n.text()),
+            "pos" => push_text(&mut s.pos, n.text()),
+            "xref" => push_text(&mut s.xref, n.text()),
+            "ant" => push_text(&mut s.ant, n.text()),
+            "field" => push_text(&mut s.field, n.text()),
+            "misc" => push_text(&mut s.misc, n.text()),
+            "s_inf" => push_text(&mut s.s_inf, n.text()),
+            "dial" => push_text(&mut s.dial, n.text()),
+            "lsource" => s.lsource.push(Lang {
+                // the source word may be omitted
+                lsource: get_optional_text(n.text()).unwrap_or_default(),
+                lang: get_lang(n, defaults),
+                ls_type: n.attribute("ls_type") == Some("part"),
+                ls_wasei: n.attribute("ls_wasei") == Some("y"),
+            }),
+            "gloss" => s.gloss.push(Gloss {
+                gloss: direct_text(n).unwrap_or_default(),
+                lang: get_lang(n, defaults),
+                g_type: get_optional_text(n.attribute("g_type")),
+                pri: n
+                    .children()
+                    .filter(|c| c.has_tag_name("pri"))
+                    .filter_map(|c| get_optional_text(c.text()))
+                    .collect(),
             }),
-            "freq" => entry.freq = Some(get_num(n.text())),
-            "rad_name" => entry.rad_name.push(get_text(n.text())),
-            "jlpt" => entry.jlpt = Some(get_num(n.text())),
-            tag => println!("Warning: unexpected tag name in misc: {}", tag),
-        };
+            tag => println!("Warning: unexpected tag name in sense: {}", tag),
+        }
     }
+
+    s
 }
 
+/// The xml:lang attribute of a node, defaulting to the DTD's default or
+/// English when the DTD has none
+fn get_lang(node: Node, defaults: &Defaults) -> String {
+    get_optional_text(defaults.attribute(node, "xml:lang")).unwrap_or_else(|| "eng".into())
+}
 
 // TODO these should probably all be falliable
-fn get_text(s: Option<&str>) -> String {
-    get_optional_text(s).expect("no text")
+pub(crate) fn get_text(s: Option<&str>) -> String {
+    s.map(|s| s.trim().into()).expect("no text")
 }
 
-fn get_num(s: Option<&str>) -> u32 {
+pub(crate) fn get_num(s: Option<&str>) -> u32 {
     get_optional_num(s).expect("no number")
 }
 
+/// Trimmed text, with empty text such as `<ke_inf></ke_inf>` as `None`
 fn get_optional_text(s: Option<&str>) -> Option<String> {
-    s.map(|s| s.trim().into())
+    s.map(str::trim).filter(|s| !s.is_empty()).map(Into::into)
+}
+
+/// The text directly inside a mixed content element, leaving out the text
+/// of its child elements, which `text()` would stop at
+fn direct_text(node: Node) -> Option<String> {
+    let text: String = node
+        .children()
+        .filter(|c| c.is_text())
+        .filter_map(|c| c.text())
+        .collect();
+    get_optional_text(Some(&text))
+}
+
+/// Add the text of a list element, skipping empty ones
+pub(crate) fn push_text(list: &mut Vec<String>, s: Option<&str>) {
+    list.extend(get_optional_text(s));
 }
 
 fn get_optional_num(s: Option<&str>) -> Option<u32> {
     s.map(|s| s.trim().parse().expect("failed to parse"))
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_parse() {
+    use crate::{source::FIXTURES, DataSource};
+
+    let text = FIXTURES.read_to_string("JMdict_e.xml").unwrap();
+    let dict = parse(&text);
+    assert_eq!(dict.created(), Some("2024-05-30"));
+    let entries: Vec<_> = dict.entries().collect();
+    assert_eq!(entries.len(), 3);
+
+    let e = &entries[0];
+    assert_eq!(e.ent_seq, 1000225);
+    assert_eq!(e.k_ele[0].keb, "明白");
+    assert_eq!(e.k_ele[0].ke_pri, ["ichi1", "news1", "nf10"]);
+    assert_eq!(e.k_ele[1].ke_inf, ["ateji (phonetic) reading"]);
+    assert_eq!(e.r_ele[0].reb, "あからさま");
+    assert_eq!(e.r_ele[1].re_restr, ["明白"]);
+    assert_eq!(
+        e.sense[0].pos,
+        ["adjectival nouns or quasi-adjectives (keiyodoshi)"]
+    );
+    assert_eq!(e.sense[0].gloss[0].gloss, "plain");
+    assert_eq!(e.sense[0].gloss[0].lang, "eng");
+    assert_eq!(e.sense[1].stagk, ["明白"]);
+
+    let e = &entries[1];
+    assert!(e.k_ele.is_empty());
+    assert!(e.r_ele[0].re_nokanji);
+    assert_eq!(e.sense[0].lsource[0].lsource, "Arbeit");
+    assert_eq!(e.sense[0].lsource[0].lang, "ger");
+    assert!(!e.sense[0].lsource[0].ls_type);
+    assert_eq!(e.sense[0].gloss[1].g_type.as_deref(), Some("lit"));
+    assert_eq!(e.sense[0].xref, ["バイト・1"]);
+    assert_eq!(e.sense[0].misc, ["abbreviation"]);
+
+    let e = &entries[2];
+    assert!(e.sense[0].lsource[0].ls_type);
+    assert!(e.sense[0].lsource[0].ls_wasei);
+    assert_eq!(e.sense[0].lsource[0].lsource, "");
+    assert_eq!(e.sense[0].dial, ["Kansai-ben"]);
+    assert_eq!(e.sense[0].ant, ["嫌い"]);
+}
+
+#[test]
+fn test_empty_text() {
+    let text = r#"<JMdict><entry>
+<ent_seq>1</ent_seq>
+<k_ele><keb>水</keb><ke_inf></ke_inf><ke_pri> </ke_pri><ke_pri>ichi1</ke_pri></k_ele>
+<r_ele><reb>みず</reb><re_restr/></r_ele>
+<sense><pos></pos><gloss g_type="">water</gloss></sense>
+</entry></JMdict>"#;
+
+    let e = parse(text).entries().next().unwrap();
+    assert!(e.k_ele[0].ke_inf.is_empty());
+    assert_eq!(e.k_ele[0].ke_pri, ["ichi1"]);
+    assert!(e.r_ele[0].re_restr.is_empty());
+    assert!(e.sense[0].pos.is_empty());
+    assert_eq!(e.sense[0].gloss[0].g_type, None);
+}
+
+#[test]
+fn test_entities() {
+    // JMdict codes its information fields as entities of the internal DTD
+    let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE JMdict [
+<!ENTITY ateji "ateji (phonetic) reading">
+]>
+<JMdict>
+<entry>
+<ent_seq>1000225</ent_seq>
+<k_ele><keb>明白</keb><ke_inf>&ateji;</ke_inf></k_ele>
+</entry>
+</JMdict>"#;
+    let dict = parse(text);
+    let entries: Vec<Entry> = dict.entries().collect();
+    assert_eq!(entries[0].k_ele[0].ke_inf, ["ateji (phonetic) reading"]);
+
+    // nested expansion is bounded, so a billion laughs is rejected
+    let mut dtd = String::from("<!ENTITY lol0 \"lol\">");
+    for i in 1..10 {
+        let value = format!("&lol{};", i - 1).repeat(10);
+        dtd += &format!("<!ENTITY lol{} \"{}\">", i, value);
+    }
+    let laughs = format!("<!DOCTYPE lolz [{}]><lolz>&lol9;</lolz>", dtd);
+    let e = Document::parse_with_options(&laughs, ParsingOptions { allow_dtd: true }).unwrap_err();
+    assert!(
+        matches!(e, roxmltree::Error::EntityReferenceLoop(_)),
+        "{}",
+        e
+    );
+}
+
+#[test]
+fn test_mixed_content() {
+    let text = r#"<JMdict><entry>
+<ent_seq>1</ent_seq>
+<r_ele><reb>みず</reb></r_ele>
+<sense><gloss>cold <pri>ichi1</pri>water</gloss><gloss><pri>news1</pri></gloss></sense>
+</entry></JMdict>"#;
+
+    let e = parse(text).entries().next().unwrap();
+    assert_eq!(e.sense[0].gloss[0].gloss, "cold water");
+    assert_eq!(e.sense[0].gloss[0].pri, ["ichi1"]);
+    assert_eq!(e.sense[0].gloss[1].gloss, "");
+    assert_eq!(e.sense[0].gloss[1].pri, ["news1"]);
+}
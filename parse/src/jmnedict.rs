@@ -0,0 +1,109 @@
+//! JMnedict, the dictionary of Japanese proper names. It shares the kanji
+//! and reading elements of JMdict, but in place of senses each entry has
+//! translations giving the kind of name and its romanization or meaning.
+
+use roxmltree::{Document, Node, ParsingOptions};
+
+use crate::{
+    jmdict::{get_num, get_text, parse_k_ele, parse_r_ele, push_text, Kanji, Reading},
+    PROGRESS_INTERVAL,
+};
+
+pub struct JMnedict<'a> {
+    doc: Document<'a>,
+}
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Entry {
+    /// A unique numeric sequence number for each entry
+    pub ent_seq: u32,
+    pub k_ele: Vec<Kanji>,
+    pub r_ele: Vec<Reading>,
+    pub trans: Vec<Translation>,
+}
+
+/// The translation of a name, in place of the senses of JMdict
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Translation {
+    /// The kind of name, e.g. a surname or place name
+    pub name_type: Vec<String>,
+    /// Related entries, by their keb or reb
+    pub xref: Vec<String>,
+    /// The romanized name or its meaning
+    pub trans_det: Vec<String>,
+}
+
+impl<'a> JMnedict<'a> {
+    pub fn entries(&'a self) -> impl Iterator<Item = Entry> + 'a {
+        self.doc
+            .root_element()
+            .children()
+            .filter(|n| n.is_element())
+            .enumerate()
+            .map(|(i, n)| {
+                if (i + 1) % PROGRESS_INTERVAL == 0 {
+                    tracing::debug!(entries = i + 1, "parsing jmnedict");
+                }
+                parse_entry(n)
+            })
+    }
+}
+
+pub fn parse(text: &str) -> JMnedict<'_> {
+    let _span = tracing::info_span!("parse_xml", dict = "jmnedict", bytes = text.len()).entered();
+    let opt = ParsingOptions { allow_dtd: true };
+    let doc = Document::parse_with_options(text, opt).expect("failed to parse");
+
+    JMnedict { doc }
+}
+
+fn parse_entry(node: Node) -> Entry {
+    let mut e = Entry::default();
+
+    for n in node.children().filter(|n| n.is_element()) {
+        match n.tag_name().name() {
+            "ent_seq" => e.ent_seq = get_num(n.text()),
+            "k_ele" => e.k_ele.push(parse_k_ele(n)),
+            "r_ele" => e.r_ele.push(parse_r_ele(n)),
+            "trans" => e.trans.push(parse_trans(n)),
+            tag => println!("Warning: unexpected tag name {}", tag),
+        }
+    }
+
+    e
+}
+
+fn parse_trans(node: Node) -> Translation {
+    let mut t = Translation::default();
+
+    for n in node.children().filter(|n| n.is_element()) {
+        match n.tag_name().name() {
+            "name_type" => push_text(&mut t.name_type, n.text()),
+            "xref" => push_text(&mut t.xref, n.text()),
+            "trans_det" => t.trans_det.push(get_text(n.text())),
+            tag => println!("Warning: unexpected tag name in trans: {}", tag),
+        }
+    }
+
+    t
+}
+
+#[test]
+fn test_parse() {
+    use crate::{source::FIXTURES, DataSource};
+
+    let text = FIXTURES.read_to_string("JMnedict.xml").unwrap();
+    let dict = parse(&text);
+    let entries: Vec<_> = dict.entries().collect();
+    assert_eq!(entries.len(), 2);
+
+    let e = &entries[1];
+    assert_eq!(e.ent_seq, 5327549);
+    assert_eq!(e.k_ele[0].keb, "田中");
+    assert_eq!(e.r_ele[1].reb, "でんちゅう");
+    assert_eq!(e.trans[0].name_type, ["family or surname", "place name"]);
+    assert_eq!(e.trans[0].trans_det, ["Tanaka"]);
+    assert_eq!(e.trans[1].xref, ["田中角栄"]);
+}
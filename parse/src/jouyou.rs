@@ -0,0 +1,117 @@
+//! The table of jouyou kanji from Wikipedia, as exported to CSV. Columns
+//! are found by their header so the export can include extra ones. The
+//! expected headers are "New" (or "Kanji"), "Old" and "Grade".
+
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    /// The kanji in its current (shinjitai) form
+    pub kanji: char,
+    /// Traditional (kyuujitai) forms of the kanji, if any
+    pub old: Vec<char>,
+    /// The grade using the kanjidic numbering: 1 through 6 for kyouiku
+    /// kanji and 8 for those taught in secondary school
+    pub grade: Option<u32>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Csv(csv::Error),
+    MissingColumn(&'static str),
+    /// A row without a kanji, by line number
+    NoKanji(u64),
+}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        Error::Csv(e)
+    }
+}
+
+/// Parse the CSV export of the table
+pub fn parse(text: &str) -> Result<Vec<Entry>, Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let headers = reader.headers()?.clone();
+    let column = |names: &[&str], name: &'static str| {
+        headers
+            .iter()
+            .position(|h| names.iter().any(|n| h.trim().eq_ignore_ascii_case(n)))
+            .ok_or(Error::MissingColumn(name))
+    };
+    let kanji_col = column(&["New", "Kanji"], "New")?;
+    let old_col = column(&["Old"], "Old")?;
+    let grade_col = column(&["Grade"], "Grade")?;
+
+    let mut entries = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |p| p.line());
+        let field = |i| record.get(i).unwrap_or_default();
+
+        entries.push(Entry {
+            kanji: ideographs(field(kanji_col))
+                .next()
+                .ok_or(Error::NoKanji(line))?,
+            old: ideographs(field(old_col)).collect(),
+            grade: parse_grade(field(grade_col)),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Map each kanji to its grade
+pub fn grade_mapping(entries: &[Entry]) -> HashMap<char, u32> {
+    entries
+        .iter()
+        .filter_map(|e| e.grade.map(|g| (e.kanji, g)))
+        .collect()
+}
+
+fn parse_grade(s: &str) -> Option<u32> {
+    match s.trim() {
+        "S" | "s" => Some(8),
+        g => g.parse().ok().filter(|g| (1..=6).contains(g)),
+    }
+}
+
+/// The CJK ideographs of a cell, skipping footnote markers and the like
+fn ideographs(s: &str) -> impl Iterator<Item = char> + '_ {
+    s.chars()
+        .filter(|c| matches!(*c as u32, 0x3400..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x3FFFF))
+}
+
+#[test]
+fn test_parse() {
+    let text = "\
+No.,New,Old,Radical,Strokes,Grade,Year added,English meaning,Readings
+1,亜,亞,二,7,S,,\"sub-, Asia\",ア
+2,哀,,口,9,S,,\"pathetic, grief\",\"アイ, あわ.れ, あわ.れむ\"
+3,愛[1],,心,13,4,,love,アイ
+4,悪,惡,心,11,3,,bad,\"アク, オ, わる.い\"
+";
+    let entries = parse(text).unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(
+        entries[0],
+        Entry {
+            kanji: '亜',
+            old: vec!['亞'],
+            grade: Some(8),
+        }
+    );
+    assert_eq!(entries[2].kanji, '愛');
+    assert!(entries[2].old.is_empty());
+
+    let grades = grade_mapping(&entries);
+    assert_eq!(grades[&'悪'], 3);
+
+    assert!(matches!(
+        parse("Kanji,Strokes\n亜,7\n"),
+        Err(Error::MissingColumn("Old"))
+    ));
+}
@@ -1,5 +1,9 @@
+use std::num::IntErrorKind;
+
 use roxmltree::{Document, Node, ParsingOptions};
 
+use crate::PROGRESS_INTERVAL;
+
 /// This module is based off of the DTD of the XML-format kanji file
 /// combining information from the KANJIDIC and KANJD212 files. This
 /// struct aims to reproduce the KANJIDIC format to the fullest with
@@ -25,21 +29,23 @@ pub struct Kanjidic<'a> {
 
 /// The single header element will contain identification information
 /// about the version of the file
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Header {
     /// This field denotes the version of kanjidic2 structure, as more
     /// than one version may exist.
-    file_version: u32,
+    pub file_version: u32,
     /// The version of the file, in the format YYYY-NN, where NN will be
     /// a number starting with 01 for the first version released in a
     /// calendar year, then increasing for each version in that year.
-    database_version: String,
+    pub database_version: String,
     /// The date the file was created in international format (YYYY-MM-DD).
-    date_of_creation: String,
+    pub date_of_creation: String,
 }
 
 /// A Kanji entry in the dictionary
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Kanji {
     /// The character itself in UTF8 coding.
     pub literal: char,
@@ -93,7 +99,8 @@ pub struct Kanji {
 
 /// The codepoint element states the code of the character in the various
 /// character set standards.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Codepoint {
     /// The cp_value contains the codepoint of the character in a particular
     /// standard. The standard will be identified in the cp_type attribute.
@@ -107,24 +114,26 @@ pub struct Codepoint {
     pub cp_type: String,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Radical {
     /// The radical number, in the range 1 to 214. The particular
     /// classification type is stated in the rad_type attribute.
     pub rad_value: u32,
     /// The rad_type attribute states the type of radical classification.
     ///  * classical - based on the system first used in the KangXi Zidian.
-    ///      The Shibano "JIS Kanwa Jiten" is used as the reference source.
+    ///    The Shibano "JIS Kanwa Jiten" is used as the reference source.
     ///  * nelson_c - as used in the Nelson "Modern Japanese-English
-    ///      Character Dictionary" (i.e. the Classic, not the New Nelson).
-    ///      This will only be used where Nelson reclassified the kanji.
+    ///    Character Dictionary" (i.e. the Classic, not the New Nelson).
+    ///    This will only be used where Nelson reclassified the kanji.
     pub rad_type: String,
 }
 
 /// Either a cross-reference code to another kanji, usually regarded as a
 /// variant, or an alternative indexing code for the current kanji.
 /// The type of variant is given in the var_type attribute.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Variant {
     pub variant: String,
     /// The var_type attribute indicates the type of variant code. The current
@@ -132,8 +141,8 @@ pub struct Variant {
     ///  * jis208 - in JIS X 0208 - kuten coding
     ///  * jis212 - in JIS X 0212 - kuten coding
     ///  * jis213 - in JIS X 0213 - kuten coding
-    ///      (most of the above relate to "shinjitai/kyuujitai"
-    ///      alternative character glyphs)
+    ///    (most of the above relate to "shinjitai/kyuujitai"
+    ///    alternative character glyphs)
     ///  * deroo - De Roo number - numeric
     ///  * njecd - Halpern NJECD index number - numeric
     ///  * s_h - The Kanji Dictionary (Spahn & Hadamitzky) - descriptor
@@ -146,7 +155,8 @@ pub struct Variant {
 /// This element contains the index numbers and similar unstructured
 /// information such as page numbers in a number of published dictionaries,
 /// and instructional books on kanji.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DicRef {
     /// Each dic_ref contains an index number. The particular dictionary,
     /// etc. is defined by the dr_type attribute.
@@ -155,46 +165,46 @@ pub struct DicRef {
     /// The dr_type defines the dictionary or reference book, etc. to which
     /// dic_ref element applies. The initial allocation is:
     ///  * nelson_c - "Modern Reader's Japanese-English Character Dictionary",
-    ///      edited by Andrew Nelson (now published as the "Classic"
-    ///      Nelson).
+    ///    edited by Andrew Nelson (now published as the "Classic"
+    ///    Nelson).
     ///  * nelson_n - "The New Nelson Japanese-English Character Dictionary",
-    ///      edited by John Haig.
+    ///    edited by John Haig.
     ///  * halpern_njecd - "New Japanese-English Character Dictionary",
-    ///      edited by Jack Halpern.
+    ///    edited by Jack Halpern.
     ///  * halpern_kkd - "Kodansha Kanji Dictionary", (2nd Ed. of the NJECD)
-    ///      edited by Jack Halpern.
+    ///    edited by Jack Halpern.
     ///  * halpern_kkld - "Kanji Learners Dictionary" (Kodansha) edited by
-    ///      Jack Halpern.
+    ///    Jack Halpern.
     ///  * halpern_kkld_2ed - "Kanji Learners Dictionary" (Kodansha), 2nd edition
-    ///      (2013) edited by Jack Halpern.
+    ///    (2013) edited by Jack Halpern.
     ///  * heisig - "Remembering The Kanji" by James Heisig.
     ///  * heisig6 - "Remembering The Kanji, Sixth Ed." by James Heisig.
     ///  * gakken - "A New Dictionary of Kanji Usage" (Gakken)
     ///  * oneill_names - "Japanese Names", by P.G. O'Neill.
     ///  * oneill_kk - "Essential Kanji" by P.G. O'Neill.
     ///  * moro - "Daikanwajiten" compiled by Morohashi. For some kanji two
-    ///      additional attributes are used: m_vol: the volume of the
-    ///      dictionary in which the kanji is found, and m_page: the page
-    ///      number in the volume.
+    ///    additional attributes are used: m_vol: the volume of the
+    ///    dictionary in which the kanji is found, and m_page: the page
+    ///    number in the volume.
     ///  * henshall - "A Guide To Remembering Japanese Characters" by
-    ///      Kenneth G. Henshall.
+    ///    Kenneth G. Henshall.
     ///  * sh_kk - "Kanji and Kana" by Spahn and Hadamitzky.
     ///  * sh_kk2 - "Kanji and Kana" by Spahn and Hadamitzky (2011 edition).
     ///  * sakade - "A Guide To Reading and Writing Japanese" edited by
-    ///      Florence Sakade.
+    ///    Florence Sakade.
     ///  * jf_cards - Japanese Kanji Flashcards, by Max Hodges and
-    ///      Tomoko Okazaki. (Series 1)
+    ///    Tomoko Okazaki. (Series 1)
     ///  * henshall3 - "A Guide To Reading and Writing Japanese" 3rd
-    ///      edition, edited by Henshall, Seeley and De Groot.
+    ///    edition, edited by Henshall, Seeley and De Groot.
     ///  * tutt_cards - Tuttle Kanji Cards, compiled by Alexander Kask.
     ///  * crowley - "The Kanji Way to Japanese Language Power" by
-    ///      Dale Crowley.
+    ///    Dale Crowley.
     ///  * kanji_in_context - "Kanji in Context" by Nishiguchi and Kono.
     ///  * busy_people - "Japanese For Busy People" vols I-III, published
-    ///      by the AJLT. The codes are the volume.chapter.
+    ///    by the AJLT. The codes are the volume.chapter.
     ///  * kodansha_compact - the "Kodansha Compact Kanji Guide".
     ///  * maniette - codes from Yves Maniette's "Les Kanjis dans la tete"
-    ///      French adaptation of Heisig.
+    ///    French adaptation of Heisig.
     pub dr_type: String,
     /// See above under "moro".
     pub m_vol: Option<u32>,
@@ -205,7 +215,8 @@ pub struct DicRef {
 /// These codes contain information relating to the glyph, and can be used
 /// for finding a required kanji. The type of code is defined by the
 /// qc_type attribute.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct QueryCode {
     /// The q_code contains the actual query-code value, according to the
     /// qc_type attribute.
@@ -213,28 +224,28 @@ pub struct QueryCode {
     /// The qc_type attribute defines the type of query code. The current values
     /// are:
     ///  * skip - Halpern's SKIP (System of Kanji Indexing by Patterns)
-    ///      code. The format is n-nn-nn. See the KANJIDIC documentation
-    ///      for a description of the code and restrictions on the
-    ///      commercial use of this data. [P] There are also
-    ///      a number of misclassification codes, indicated by the
-    ///      "skip_misclass" attribute.
+    ///    code. The format is n-nn-nn. See the KANJIDIC documentation
+    ///    for a description of the code and restrictions on the
+    ///    commercial use of this data. [P] There are also
+    ///    a number of misclassification codes, indicated by the
+    ///    "skip_misclass" attribute.
     ///  * sh_desc - the descriptor codes for The Kanji Dictionary (Tuttle
-    ///      1996) by Spahn and Hadamitzky. They are in the form nxnn.n,
-    ///      e.g. 3k11.2, where the kanji has 3 strokes in the
-    ///      identifying radical, it is radical "k" in the SH
-    ///      classification system, there are 11 other strokes, and it is
-    ///      the 2nd kanji in the 3k11 sequence. (I am very grateful to
-    ///      Mark Spahn for providing the list of these descriptor codes
-    ///      for the kanji in this file.) [I]
+    ///    1996) by Spahn and Hadamitzky. They are in the form nxnn.n,
+    ///    e.g. 3k11.2, where the kanji has 3 strokes in the
+    ///    identifying radical, it is radical "k" in the SH
+    ///    classification system, there are 11 other strokes, and it is
+    ///    the 2nd kanji in the 3k11 sequence. (I am very grateful to
+    ///    Mark Spahn for providing the list of these descriptor codes
+    ///    for the kanji in this file.) [I]
     ///  * four_corner - the "Four Corner" code for the kanji. This is a code
-    ///      invented by Wang Chen in 1928. See the KANJIDIC documentation
-    ///      for an overview of the Four Corner System. [Q]
+    ///    invented by Wang Chen in 1928. See the KANJIDIC documentation
+    ///    for an overview of the Four Corner System. [Q]
     ///  * deroo - the codes developed by the late Father Joseph De Roo, and
-    ///      published in his book "2001 Kanji" (Bonjinsha). Fr De Roo
-    ///      gave his permission for these codes to be included. [DR]
+    ///    published in his book "2001 Kanji" (Bonjinsha). Fr De Roo
+    ///    gave his permission for these codes to be included. [DR]
     ///  * misclass - a possible misclassification of the kanji according
-    ///      to one of the code types. (See the "Z" codes in the KANJIDIC
-    ///      documentation for more details.)
+    ///    to one of the code types. (See the "Z" codes in the KANJIDIC
+    ///    documentation for more details.)
     pub qc_type: String,
     /// The values of this attribute indicate the type if
     /// misclassification:
@@ -249,13 +260,15 @@ pub struct QueryCode {
 /// in several languages. The readings and meanings are grouped to enable
 /// the handling of the situation where the meaning is differentiated by
 /// reading. [T1]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ReadingMeaning {
     pub reading: Vec<Reading>,
     pub meaning: Vec<Meaning>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Reading {
     /// The reading element contains the reading or pronunciation
     /// of the kanji.
@@ -263,23 +276,24 @@ pub struct Reading {
     /// The r_type attribute defines the type of reading in the reading
     /// element. The current values are:
     ///  * pinyin - the modern PinYin romanization of the Chinese reading
-    ///      of the kanji. The tones are represented by a concluding
-    ///      digit. [Y]
+    ///    of the kanji. The tones are represented by a concluding
+    ///    digit. [Y]
     ///  * korean_r - the romanized form of the Korean reading(s) of the
-    ///      kanji. The readings are in the (Republic of Korea) Ministry
-    ///      of Education style of romanization. [W]
+    ///    kanji. The readings are in the (Republic of Korea) Ministry
+    ///    of Education style of romanization. [W]
     ///  * korean_h - the Korean reading(s) of the kanji in hangul.
     ///  * vietnam - the Vietnamese readings supplied by Minh Chau Pham.
     ///  * ja_on - the "on" Japanese reading of the kanji, in katakana.
     ///  * ja_kun - the "kun" Japanese reading of the kanji, usually in
-    ///      hiragana.
-    ///      Where relevant the okurigana is also included separated by a
-    ///      ".". Readings associated with prefixes and suffixes are
-    ///      marked with a "-".
+    ///    hiragana.
+    ///    Where relevant the okurigana is also included separated by a
+    ///    ".". Readings associated with prefixes and suffixes are
+    ///    marked with a "-".
     pub r_type: String,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Meaning {
     /// The meaning associated with the kanji.
     pub meaning: String,
@@ -289,188 +303,645 @@ pub struct Meaning {
     pub m_lang: String,
 }
 
+/// An entry that could not be parsed
+#[derive(Debug)]
+pub struct ParseError {
+    /// The literal of the entry, if it could be read
+    pub literal: Option<char>,
+    /// The byte offset of the entry, or of the header, in the file
+    pub offset: usize,
+    /// The line of the element that failed, starting at 1
+    pub line: u32,
+    /// The column of the element that failed, starting at 1
+    pub column: u32,
+    /// The tags open at the element that failed, outermost first
+    pub path: Vec<String>,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorKind {
+    /// The entry has no literal element, or it is empty
+    NoLiteral,
+    /// The file does not start with a header element
+    NoHeader,
+    /// An element or attribute is missing its text
+    MissingText(String),
+    /// The text of an element or attribute is not a number
+    InvalidNumber(String, String),
+    /// The text of an element or attribute is a number too large to store
+    NumberOutOfRange(String, String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.literal {
+            Some(c) => write!(f, "entry {} ", c)?,
+            None if self.path.get(1).map(String::as_str) == Some("header") => write!(f, "header ")?,
+            None => write!(f, "entry ")?,
+        }
+        write!(
+            f,
+            "at line {}, column {} in {}: ",
+            self.line,
+            self.column,
+            self.path.join(" > ")
+        )?;
+        match &self.kind {
+            ErrorKind::NoLiteral => write!(f, "no literal"),
+            ErrorKind::NoHeader => write!(f, "no header"),
+            ErrorKind::MissingText(name) => write!(f, "no text in {}", name),
+            ErrorKind::InvalidNumber(name, text) => {
+                write!(f, "invalid number {:?} in {}", text, name)
+            }
+            ErrorKind::NumberOutOfRange(name, text) => {
+                write!(
+                    f,
+                    "number {:?} in {} is larger than {}",
+                    text,
+                    name,
+                    u32::MAX
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+type Result<T> = std::result::Result<T, ErrorKind>;
+
 impl<'a> Kanjidic<'a> {
-    pub fn header(self: &Self) -> Header {
+    /// The header, or why it could not be read
+    pub fn header(&self) -> std::result::Result<Header, ParseError> {
         let mut h = Header::default();
 
-        let node = self
-            .doc
-            .root_element()
+        let root = self.doc.root_element();
+        let node = root
             .children()
-            .filter(|n| n.is_element())
-            .next()
-            .expect("no header node");
+            .find(|n| n.is_element())
+            .filter(|n| n.has_tag_name("header"))
+            .ok_or_else(|| parse_error(root, root, ErrorKind::NoHeader, None))?;
 
         for n in node.children() {
-            match n.tag_name().name() {
-                "file_version" => h.file_version = get_num(n.text()),
-                "database_version" => h.database_version = get_text(n.text()),
-                "date_of_creation" => h.date_of_creation = get_text(n.text()),
-                _ => (),
+            let name = n.tag_name().name();
+            match name {
+                "file_version" => get_num(name, n.text()).map(|v| h.file_version = v),
+                "database_version" => get_text(name, n.text()).map(|v| h.database_version = v),
+                "date_of_creation" => get_text(name, n.text()).map(|v| h.date_of_creation = v),
+                _ => Ok(()),
             }
+            .map_err(|kind| parse_error(node, n, kind, None))?;
         }
 
-        return h;
+        Ok(h)
+    }
+
+    /// Every entry, panicking on the first malformed one
+    pub fn entries(&'a self) -> impl Iterator<Item = Kanji> + 'a {
+        self.try_entries()
+            .map(|k| k.unwrap_or_else(|e| panic!("failed to parse {}", e)))
     }
 
-    pub fn entries(self: &'a Self) -> impl Iterator<Item = Kanji> + 'a {
-        return self
-            .doc
+    /// Every entry, so that callers can skip or report malformed ones
+    pub fn try_entries(
+        &'a self,
+    ) -> impl Iterator<Item = std::result::Result<Kanji, ParseError>> + 'a {
+        self.doc
             .root_element()
             .children()
             .filter(|n| n.is_element())
             // first element is the header
             .skip(1)
-            .map(parse_entry);
+            .enumerate()
+            .map(|(i, n)| {
+                if (i + 1) % PROGRESS_INTERVAL == 0 {
+                    tracing::debug!(entries = i + 1, "parsing kanjidic");
+                }
+                parse_entry(n)
+            })
     }
 }
 
-pub fn parse<'a>(text: &'a str) -> Kanjidic<'a> {
+pub fn parse(text: &str) -> Kanjidic<'_> {
+    let _span = tracing::info_span!("parse_xml", dict = "kanjidic", bytes = text.len()).entered();
     let opt = ParsingOptions { allow_dtd: true };
     let doc = Document::parse_with_options(text, opt).expect("failed to parse");
 
-    return Kanjidic { doc };
+    Kanjidic { doc }
+}
+
+fn parse_entry(node: Node) -> std::result::Result<Kanji, ParseError> {
+    let literal = node
+        .children()
+        .find(|n| n.has_tag_name("literal"))
+        .and_then(|n| n.text())
+        .and_then(|t| t.trim().chars().next());
+
+    let kanji = parse_fields(node).and_then(|k| match literal {
+        Some(literal) => Ok(Kanji { literal, ..k }),
+        None => Err((node, ErrorKind::NoLiteral)),
+    });
+
+    kanji.map_err(|(section, kind)| parse_error(node, section, kind, literal))
+}
+
+/// Place an error in `section` of the element `node`
+fn parse_error(node: Node, section: Node, kind: ErrorKind, literal: Option<char>) -> ParseError {
+    let at = locate(section, &kind);
+    let pos = at.document().text_pos_at(at.range().start);
+    let mut path: Vec<String> = at
+        .ancestors()
+        .filter(|n| n.is_element())
+        .map(|n| n.tag_name().name().to_owned())
+        .collect();
+    path.reverse();
+    ParseError {
+        literal,
+        offset: node.range().start,
+        line: pos.row,
+        column: pos.col,
+        path,
+        kind,
+    }
+}
+
+/// Find the element an error is about within the section it occurred in,
+/// or the section itself when the error names an attribute
+fn locate<'a, 'input>(section: Node<'a, 'input>, kind: &ErrorKind) -> Node<'a, 'input> {
+    let found = match kind {
+        ErrorKind::NoLiteral | ErrorKind::NoHeader => None,
+        ErrorKind::MissingText(name) => section
+            .descendants()
+            .find(|n| n.has_tag_name(name.as_str()) && get_optional_text(n.text()).is_none()),
+        ErrorKind::InvalidNumber(name, text) | ErrorKind::NumberOutOfRange(name, text) => {
+            section.descendants().find(|n| {
+                n.has_tag_name(name.as_str()) && n.text().map(str::trim) == Some(text.as_str())
+            })
+        }
+    };
+    found.unwrap_or(section)
 }
 
-fn parse_entry(node: Node) -> Kanji {
+/// Parse every section of an entry, giving the section an error occurred in
+fn parse_fields<'a, 'input>(
+    node: Node<'a, 'input>,
+) -> std::result::Result<Kanji, (Node<'a, 'input>, ErrorKind)> {
     let mut k = Kanji::default();
 
     for n in node.children().filter(|n| n.is_element()) {
         match n.tag_name().name() {
-            "literal" => parse_literal(n, &mut k),
+            // read up front to give errors context
+            "literal" => Ok(()),
             "codepoint" => parse_codepoint(n, &mut k),
             "radical" => parse_radical(n, &mut k),
             "misc" => parse_misc(n, &mut k),
             "dic_number" => parse_dic_number(n, &mut k),
             "query_code" => parse_query_code(n, &mut k),
             "reading_meaning" => parse_reading_meaning(n, &mut k),
-            tag => println!("Warning: unexpected tag name {}", tag),
+            tag => {
+                println!("Warning: unexpected tag name {}", tag);
+                Ok(())
+            }
         }
+        .map_err(|kind| (n, kind))?;
     }
 
-    k
-}
-
-fn parse_literal(node: Node, entry: &mut Kanji) {
-    let text = node.text().expect("failed to get text").trim();
-    let c = text.chars().next().expect("no first char");
-    entry.literal = c
+    Ok(k)
 }
 
-fn parse_codepoint(node: Node, entry: &mut Kanji) {
+fn parse_codepoint(node: Node, entry: &mut Kanji) -> Result<()> {
     entry.codepoint = node
         .children()
         .filter(|n| n.is_element())
-        .map(|n| Codepoint {
-            cp_value: get_text(n.text()),
-            cp_type: get_text(n.attribute("cp_type")),
+        .map(|n| {
+            Ok(Codepoint {
+                cp_value: get_text("cp_value", n.text())?,
+                cp_type: get_text("cp_type", n.attribute("cp_type"))?,
+            })
         })
-        .collect();
+        .collect::<Result<_>>()?;
+    Ok(())
 }
 
-fn parse_radical(node: Node, entry: &mut Kanji) {
+fn parse_radical(node: Node, entry: &mut Kanji) -> Result<()> {
     entry.radical = node
         .children()
         .filter(|n| n.is_element())
-        .map(|n| Radical {
-            rad_value: get_num(n.text()),
-            rad_type: get_text(n.attribute("rad_type")),
+        .map(|n| {
+            Ok(Radical {
+                rad_value: get_num("rad_value", n.text())?,
+                rad_type: get_text("rad_type", n.attribute("rad_type"))?,
+            })
         })
-        .collect();
+        .collect::<Result<_>>()?;
+    Ok(())
 }
 
-fn parse_misc(node: Node, entry: &mut Kanji) {
+fn parse_misc(node: Node, entry: &mut Kanji) -> Result<()> {
     for n in node.children().filter(|n| n.is_element()) {
-        match n.tag_name().name() {
-            "grade" => entry.grade = Some(get_num(n.text())),
-            "stroke_count" => entry.stroke_count.push(get_num(n.text())),
+        let name = n.tag_name().name();
+        match name {
+            "grade" => entry.grade = Some(get_num(name, n.text())?),
+            "stroke_count" => entry.stroke_count.push(get_num(name, n.text())?),
             "variant" => entry.variant.push(Variant {
-                variant: get_text(n.text()),
-                var_type: get_text(n.attribute("var_type")),
+                variant: get_text(name, n.text())?,
+                var_type: get_text("var_type", n.attribute("var_type"))?,
             }),
-            "freq" => entry.freq = Some(get_num(n.text())),
-            "rad_name" => entry.rad_name.push(get_text(n.text())),
-            "jlpt" => entry.jlpt = Some(get_num(n.text())),
+            "freq" => entry.freq = Some(get_num(name, n.text())?),
+            "rad_name" => entry.rad_name.push(get_text(name, n.text())?),
+            "jlpt" => entry.jlpt = Some(get_num(name, n.text())?),
             tag => println!("Warning: unexpected tag name in misc: {}", tag),
         };
     }
+    Ok(())
 }
 
-fn parse_dic_number(node: Node, entry: &mut Kanji) {
+fn parse_dic_number(node: Node, entry: &mut Kanji) -> Result<()> {
     entry.dic_number = node
         .children()
         .filter(|n| n.is_element())
-        .map(|n| DicRef {
-            dic_ref: get_text(n.text()),
-            dr_type: get_text(n.attribute("dr_type")),
-            m_vol: get_optional_num(n.attribute("m_vol")),
-            m_page: get_optional_num(n.attribute("m_page")),
+        .map(|n| {
+            Ok(DicRef {
+                dic_ref: get_text("dic_ref", n.text())?,
+                dr_type: get_text("dr_type", n.attribute("dr_type"))?,
+                m_vol: get_optional_num("m_vol", n.attribute("m_vol"))?,
+                m_page: get_optional_num("m_page", n.attribute("m_page"))?,
+            })
         })
-        .collect();
+        .collect::<Result<_>>()?;
+    Ok(())
 }
 
-fn parse_query_code(node: Node, entry: &mut Kanji) {
+fn parse_query_code(node: Node, entry: &mut Kanji) -> Result<()> {
     entry.quecy_code = node
         .children()
         .filter(|n| n.is_element())
-        .map(|n| QueryCode {
-            q_code: get_text(n.text()),
-            qc_type: get_text(n.attribute("qc_type")),
-            skip_misclass: get_optional_text(n.attribute("skip_misclass")),
+        .map(|n| {
+            Ok(QueryCode {
+                q_code: get_text("q_code", n.text())?,
+                qc_type: get_text("qc_type", n.attribute("qc_type"))?,
+                skip_misclass: get_optional_text(n.attribute("skip_misclass")),
+            })
         })
-        .collect();
+        .collect::<Result<_>>()?;
+    Ok(())
 }
 
-fn parse_reading_meaning(node: Node, entry: &mut Kanji) {
+fn parse_reading_meaning(node: Node, entry: &mut Kanji) -> Result<()> {
     for n in node.children().filter(|n| n.is_element()) {
-        match n.tag_name().name() {
-            "rmgroup" => parse_rmgroup(n, entry),
-            "nanori" => entry.nanori.push(get_text(n.text())),
+        let name = n.tag_name().name();
+        match name {
+            "rmgroup" => parse_rmgroup(n, entry)?,
+            "nanori" => entry.nanori.push(get_text(name, n.text())?),
             tag => println!("Warning: unexpected tag name in reading_meaning: {}", tag),
         };
     }
+    Ok(())
 }
 
-fn parse_rmgroup(node: Node, entry: &mut Kanji) {
+fn parse_rmgroup(node: Node, entry: &mut Kanji) -> Result<()> {
     let mut group = ReadingMeaning::default();
 
     for n in node.children().filter(|n| n.is_element()) {
-        match n.tag_name().name() {
+        let name = n.tag_name().name();
+        match name {
             "reading" => group.reading.push(Reading {
-                reading: get_text(n.text()),
-                r_type: get_text(n.attribute("r_type")),
+                reading: get_text(name, n.text())?,
+                r_type: get_text("r_type", n.attribute("r_type"))?,
             }),
             "meaning" => group.meaning.push(Meaning {
-                meaning: get_text(n.text()),
+                meaning: get_text(name, n.text())?,
                 m_lang: get_optional_text(n.attribute("m_lang")).unwrap_or("en".into()),
             }),
             tag => println!("Warning: unexpected tag name in reading_meaning: {}", tag),
         };
     }
     entry.rmgroup.push(group);
+    Ok(())
 }
 
-// TODO these should probably all be falliable
-fn get_text(s: Option<&str>) -> String {
-    get_optional_text(s).expect("no text")
+fn get_text(name: &str, s: Option<&str>) -> Result<String> {
+    get_optional_text(s).ok_or_else(|| ErrorKind::MissingText(name.into()))
 }
 
-fn get_num(s: Option<&str>) -> u32 {
-    get_optional_num(s).expect("no number")
+fn get_num(name: &str, s: Option<&str>) -> Result<u32> {
+    get_optional_num(name, s)?.ok_or_else(|| ErrorKind::MissingText(name.into()))
 }
 
 fn get_optional_text(s: Option<&str>) -> Option<String> {
     s.map(|s| s.trim().into())
 }
 
-fn get_optional_num(s: Option<&str>) -> Option<u32> {
-    s.map(|s| s.trim().parse().expect("failed to parse"))
+fn get_optional_num(name: &str, s: Option<&str>) -> Result<Option<u32>> {
+    s.map(|s| {
+        let s = s.trim();
+        s.parse()
+            .map_err(|e: std::num::ParseIntError| match e.kind() {
+                IntErrorKind::PosOverflow => ErrorKind::NumberOutOfRange(name.into(), s.into()),
+                _ => ErrorKind::InvalidNumber(name.into(), s.into()),
+            })
+    })
+    .transpose()
+}
+
+/// How much is read from a stream at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Why a streamed entry could not be read
+#[derive(Debug)]
+pub enum StreamError {
+    Io(std::io::Error),
+    /// The entry is not well-formed XML
+    Xml(roxmltree::Error),
+    /// The header is missing or malformed
+    Header(ParseError),
+    Entry(ParseError),
+}
+
+impl std::fmt::Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Io(e) => write!(f, "failed to read: {}", e),
+            StreamError::Xml(e) => write!(f, "malformed entry: {}", e),
+            StreamError::Header(e) | StreamError::Entry(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+impl From<std::io::Error> for StreamError {
+    fn from(e: std::io::Error) -> Self {
+        StreamError::Io(e)
+    }
+}
+
+/// Entries read from a UTF-8 kanjidic file one at a time. Only the entry
+/// being parsed is held in memory, rather than the whole file and a DOM
+/// of it, which is several times its size.
+///
+/// Each entry is parsed as a document of its own, so entities declared in
+/// the DTD can't be used within entries. Kanjidic uses none.
+pub struct Stream<R> {
+    reader: R,
+    /// Read but not yet parsed
+    buf: Vec<u8>,
+    /// How far `buf` was searched without finding what was looked for
+    scanned: usize,
+    /// The position of the start of `buf` in the file
+    offset: usize,
+    line: u32,
+    column: u32,
+    header: Header,
+}
+
+/// Find a byte string in `haystack`, starting the search at `from`
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + from)
+}
+
+impl<R: std::io::Read> Stream<R> {
+    /// Read more of the file, returning false at its end
+    fn fill(&mut self) -> std::io::Result<bool> {
+        let len = self.buf.len();
+        self.buf.resize(len + CHUNK_SIZE, 0);
+        let n = self.reader.read(&mut self.buf[len..])?;
+        self.buf.truncate(len + n);
+        Ok(n > 0)
+    }
+
+    /// Read until `needle` is in the buffer, returning where it starts
+    fn read_until(&mut self, needle: &[u8]) -> std::io::Result<Option<usize>> {
+        loop {
+            if let Some(i) = find(&self.buf, needle, self.scanned) {
+                self.scanned = 0;
+                return Ok(Some(i));
+            }
+            // the needle may have been cut off at the end of the buffer
+            self.scanned = self.buf.len().saturating_sub(needle.len() - 1);
+            if !self.fill()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Drop the start of the buffer, keeping track of where it is
+    fn consume(&mut self, n: usize) {
+        for &b in &self.buf[..n] {
+            match b {
+                b'\n' => (self.line, self.column) = (self.line + 1, 1),
+                // count characters rather than bytes, as roxmltree does
+                b if b & 0xC0 != 0x80 => self.column += 1,
+                _ => (),
+            }
+        }
+        self.offset += n;
+        self.buf.drain(..n);
+    }
+
+    /// The header of the file, read before any entry
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    fn next_entry(&mut self) -> std::result::Result<Option<Kanji>, StreamError> {
+        let Some(start) = self.read_until(b"<character")? else {
+            return Ok(None);
+        };
+        self.consume(start);
+        let end = match self.read_until(b"</character>")? {
+            Some(end) => end + "</character>".len(),
+            None => {
+                return Err(StreamError::Io(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "unterminated character element",
+                )))
+            }
+        };
+
+        let (offset, line, column) = (self.offset, self.line, self.column);
+        let parsed = std::str::from_utf8(&self.buf[..end])
+            .map_err(|e| StreamError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+            .and_then(|text| Document::parse(text).map_err(StreamError::Xml))
+            .and_then(|doc| {
+                parse_entry(doc.root_element()).map_err(|mut e| {
+                    // place the error in the file rather than the entry
+                    e.offset += offset;
+                    if e.line == 1 {
+                        e.column += column - 1;
+                    }
+                    e.line += line - 1;
+                    e.path.insert(0, "kanjidic2".into());
+                    StreamError::Entry(e)
+                })
+            });
+        self.consume(end);
+        parsed.map(Some)
+    }
+}
+
+impl<R: std::io::Read> Iterator for Stream<R> {
+    type Item = std::result::Result<Kanji, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+/// Read kanjidic from a reader one entry at a time, see [`Stream`]. The
+/// header is read right away.
+pub fn parse_stream<R: std::io::Read>(reader: R) -> std::result::Result<Stream<R>, StreamError> {
+    let mut stream = Stream {
+        reader,
+        buf: vec![],
+        scanned: 0,
+        offset: 0,
+        line: 1,
+        column: 1,
+        header: Header::default(),
+    };
+
+    // everything before the first entry, closed off, is a document with
+    // only the header in it. Without entries it is the whole file.
+    let first = stream.read_until(b"<character")?;
+    let end = first.unwrap_or(stream.buf.len());
+    let mut prolog = std::str::from_utf8(&stream.buf[..end])
+        .map_err(|e| StreamError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))?
+        .to_owned();
+    if first.is_some() {
+        prolog.push_str("</kanjidic2>");
+    }
+    let opt = ParsingOptions { allow_dtd: true };
+    let doc = Document::parse_with_options(&prolog, opt).map_err(StreamError::Xml)?;
+    stream.header = Kanjidic { doc }.header().map_err(StreamError::Header)?;
+    stream.consume(end);
+
+    Ok(stream)
 }
 
 #[test]
 fn test_parse() {
-    let text = super::read_file("kanjidic2.xml");
+    use crate::{source::FIXTURES, DataSource};
+
+    let text = FIXTURES.read_to_string("kanjidic2.xml").unwrap();
     for kanji in parse(&text).entries() {
         println!("{:?}", kanji);
     }
 }
+
+#[test]
+fn test_try_entries() {
+    let text = r#"<kanjidic2>
+<header><file_version>4</file_version></header>
+<character><literal>亜</literal><misc><stroke_count>7</stroke_count></misc></character>
+<character><literal>唖</literal><misc><stroke_count>x</stroke_count></misc></character>
+<character><misc><stroke_count>7</stroke_count></misc></character>
+<character><literal>娃</literal><misc><grade/></misc></character>
+<character><literal>阿</literal><misc><freq>4294967296</freq></misc></character>
+</kanjidic2>"#;
+
+    let entries: Vec<_> = parse(text).try_entries().collect();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[0].as_ref().unwrap().stroke_count, vec![7]);
+
+    let e = entries[1].as_ref().unwrap_err();
+    assert_eq!(e.literal, Some('唖'));
+    assert_eq!(
+        e.kind,
+        ErrorKind::InvalidNumber("stroke_count".into(), "x".into())
+    );
+    assert_eq!(text[e.offset..].find("<character>"), Some(0));
+    assert_eq!((e.line, e.column), (4, 38));
+    assert_eq!(e.path, ["kanjidic2", "character", "misc", "stroke_count"]);
+
+    let e = entries[2].as_ref().unwrap_err();
+    assert_eq!((e.literal, &e.kind), (None, &ErrorKind::NoLiteral));
+    assert_eq!((e.line, e.column), (5, 1));
+    assert_eq!(e.path, ["kanjidic2", "character"]);
+
+    let e = entries[3].as_ref().unwrap_err();
+    assert_eq!(e.kind, ErrorKind::MissingText("grade".into()));
+    assert_eq!(
+        e.to_string(),
+        "entry 娃 at line 6, column 38 in kanjidic2 > character > misc > grade: no text in grade"
+    );
+
+    let e = entries[4].as_ref().unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "entry 阿 at line 7, column 38 in kanjidic2 > character > misc > freq: number \"4294967296\" in freq is larger than 4294967295"
+    );
+}
+
+#[test]
+fn test_header() {
+    let text = "<kanjidic2>\n<header><file_version>x</file_version></header>\n</kanjidic2>";
+    let e = parse(text).header().unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "header at line 2, column 9 in kanjidic2 > header > file_version: invalid number \"x\" in file_version"
+    );
+
+    let e = parse("<kanjidic2><character/></kanjidic2>")
+        .header()
+        .unwrap_err();
+    assert_eq!(e.kind, ErrorKind::NoHeader);
+    assert!(matches!(
+        parse_stream("<kanjidic2></kanjidic2>".as_bytes()),
+        Err(StreamError::Header(_))
+    ));
+}
+
+#[test]
+fn test_parse_stream() {
+    use crate::{source::FIXTURES, DataSource};
+
+    let text = FIXTURES.read_to_string("kanjidic2.xml").unwrap();
+    let dom = parse(&text);
+    // reading a few bytes at a time makes elements straddle reads
+    struct Trickle<'a>(&'a [u8]);
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(7);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+    let mut stream = parse_stream(Trickle(text.as_bytes())).unwrap();
+    assert_eq!(stream.header(), &dom.header().unwrap());
+    let streamed: Vec<_> = stream.by_ref().map(|k| k.unwrap()).collect();
+    assert_eq!(streamed, dom.entries().collect::<Vec<_>>());
+
+    let text = r#"<kanjidic2>
+<header><file_version>4</file_version><database_version>2023-01</database_version></header>
+<character><literal>亜</literal><misc><stroke_count>7</stroke_count></misc></character>
+<character><literal>唖</literal><misc><stroke_count>x</stroke_count></misc></character>
+<character><literal>娃</literal><misc></character>
+</kanjidic2>"#;
+    let mut stream = parse_stream(text.as_bytes()).unwrap();
+    assert_eq!(stream.header().database_version, "2023-01");
+    assert_eq!(stream.next().unwrap().unwrap().literal, '亜');
+
+    // errors are placed the same as when parsing the whole file
+    match stream.next() {
+        Some(Err(StreamError::Entry(e))) => {
+            assert_eq!((e.line, e.column), (4, 38));
+            assert_eq!(text[e.offset..].find("<character>"), Some(0));
+            assert_eq!(e.path, ["kanjidic2", "character", "misc", "stroke_count"]);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(matches!(stream.next(), Some(Err(StreamError::Xml(_)))));
+    assert!(stream.next().is_none());
+
+    let empty = "<kanjidic2><header><file_version>4</file_version></header></kanjidic2>";
+    let mut stream = parse_stream(empty.as_bytes()).unwrap();
+    assert_eq!(stream.header().file_version, 4);
+    assert!(stream.next().is_none());
+}
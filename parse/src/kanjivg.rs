@@ -0,0 +1,105 @@
+//! The stroke order diagrams of KanjiVG, from the file combining every
+//! kanji into one `<kanjivg>` document. Each kanji has the SVG path of
+//! each of its strokes, in the order they are written.
+//!
+//! Variant forms, such as the Kaisho form of a kanji, have an id with a
+//! suffix after the code point and are skipped.
+
+use roxmltree::{Document, Node, ParsingOptions};
+
+/// The namespace of the `kvg:` attributes
+const NS: &str = "http://kanjivg.tagaini.net";
+
+#[derive(Debug, PartialEq)]
+pub struct Kanji {
+    pub literal: char,
+    pub strokes: Vec<Stroke>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Stroke {
+    /// The kind of stroke as a CJK stroke character, e.g. ㇐
+    pub kind: Option<String>,
+    /// The path data of the stroke, in a 109 by 109 box
+    pub path: String,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Xml(roxmltree::Error),
+    /// A kanji whose id isn't a code point, by id
+    BadId(String),
+}
+
+impl From<roxmltree::Error> for Error {
+    fn from(e: roxmltree::Error) -> Self {
+        Error::Xml(e)
+    }
+}
+
+/// The literal of a kanji id such as "kvg:kanji_04e00", or `None` for
+/// variants
+fn literal(id: &str) -> Result<Option<char>, Error> {
+    let bad = || Error::BadId(id.to_owned());
+    let code = id.strip_prefix("kvg:kanji_").ok_or_else(bad)?;
+    if code.contains('-') {
+        return Ok(None);
+    }
+    u32::from_str_radix(code, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .map(Some)
+        .ok_or_else(bad)
+}
+
+fn stroke(n: Node) -> Option<Stroke> {
+    Some(Stroke {
+        kind: n.attribute((NS, "type")).map(str::to_owned),
+        path: n.attribute("d")?.to_owned(),
+    })
+}
+
+/// Parse the combined KanjiVG file
+pub fn parse(text: &str) -> Result<Vec<Kanji>, Error> {
+    let opt = ParsingOptions { allow_dtd: true };
+    let doc = Document::parse_with_options(text, opt)?;
+
+    let mut out = vec![];
+    for n in doc
+        .root_element()
+        .children()
+        .filter(|n| n.has_tag_name("kanji"))
+    {
+        let Some(literal) = literal(n.attribute("id").unwrap_or_default())? else {
+            continue;
+        };
+        let strokes = n
+            .descendants()
+            .filter(|n| n.has_tag_name("path"))
+            .filter_map(stroke)
+            .collect();
+        out.push(Kanji { literal, strokes });
+    }
+    Ok(out)
+}
+
+#[test]
+fn test_parse() {
+    use crate::{source::FIXTURES, DataSource};
+
+    let text = FIXTURES.read_to_string("kanjivg.xml").unwrap();
+    let kanji = parse(&text).unwrap();
+    let literals: Vec<char> = kanji.iter().map(|k| k.literal).collect();
+    assert_eq!(literals, ['一', '二']);
+
+    let ni = &kanji[1].strokes;
+    assert_eq!(ni.len(), 2);
+    assert_eq!(ni[0].kind.as_deref(), Some("㇐"));
+    assert!(ni[0].path.starts_with("M27.5,28.51"));
+    assert_eq!(ni[1].kind, None);
+
+    assert!(matches!(
+        parse("<kanjivg><kanji id=\"kvg:kanji_zz\"/></kanjivg>"),
+        Err(Error::BadId(id)) if id == "kvg:kanji_zz"
+    ));
+}
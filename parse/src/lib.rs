@@ -1,19 +1,17 @@
+pub mod dtd;
+pub mod encoding;
 pub mod jmdict;
+pub mod jmnedict;
+pub mod jouyou;
 pub mod kanjidic;
+pub mod kanjivg;
+pub mod source;
+pub mod tanaka;
 
-fn data_path(file: &str) -> std::path::PathBuf {
-    [env!("CARGO_MANIFEST_DIR"), "../data", file]
-        .iter()
-        .collect()
-}
-
-pub fn read_file(filename: &str) -> String {
-    std::fs::read_to_string(data_path(filename)).unwrap()
-}
+pub use source::DataSource;
 
-pub fn write_file(filename: &str, data: &[u8]) {
-    std::fs::write(data_path(filename), data).unwrap()
-}
+/// How many entries are parsed or converted between progress reports
+pub const PROGRESS_INTERVAL: usize = 1000;
 
 pub mod util {
     use std::collections::HashMap;
@@ -35,7 +33,7 @@ pub mod util {
                 continue;
             }
 
-            if let Some(_) = m.insert(c, index) {
+            if m.insert(c, index).is_some() {
                 return Err(c);
             }
         }
@@ -61,7 +59,7 @@ pub mod util {
                     continue;
                 }
 
-                if let Some(_) = m.insert(c, grade) {
+                if m.insert(c, grade).is_some() {
                     return Err(c);
                 }
             }
@@ -70,7 +68,7 @@ pub mod util {
         Ok(m)
     }
 
-    fn char_iter<'a>(list: &'a str) -> impl Iterator<Item = char> + 'a {
+    fn char_iter(list: &str) -> impl Iterator<Item = char> + '_ {
         list.lines().flat_map(|l| l.chars())
     }
 }
@@ -0,0 +1,188 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// A place dictionary files can be read from, like the data directory
+/// filled by fetch.sh
+pub trait DataSource {
+    /// Read the raw bytes of a named file
+    fn read(&self, name: &str) -> io::Result<Vec<u8>>;
+
+    /// Open a named file to be read a little at a time. Unless the source
+    /// can do better the whole file is read up front.
+    fn open(&self, name: &str) -> io::Result<Box<dyn io::Read + '_>> {
+        Ok(Box::new(io::Cursor::new(self.read(name)?)))
+    }
+
+    /// Read a named file as UTF-8 text
+    fn read_to_string(&self, name: &str) -> io::Result<String> {
+        String::from_utf8(self.read(name)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Read a named XML file as text, decoding it from the encoding given
+    /// by its byte order mark or XML declaration
+    fn read_xml(&self, name: &str) -> io::Result<String> {
+        crate::encoding::decode_xml(self.read(name)?)
+    }
+}
+
+/// Files in a directory on the filesystem
+#[derive(Debug, Clone)]
+pub struct Dir {
+    root: PathBuf,
+}
+
+impl Dir {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Dir {
+            root: root.as_ref().to_owned(),
+        }
+    }
+
+    /// The directory named by `KANJISHO_DATA`, or `data` in the current
+    /// directory if unset
+    pub fn from_env() -> Self {
+        Dir::new(std::env::var("KANJISHO_DATA").unwrap_or_else(|_| "data".into()))
+    }
+
+    /// The full path of a named file
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    /// Write a named file into the directory, replacing it at once. The
+    /// data goes to a temporary file next to it which is renamed over it,
+    /// so readers such as a backend mapping the file never see it
+    /// truncated or half written.
+    pub fn write(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.path(name);
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(format!(".{}.tmp", std::process::id()));
+        let tmp = PathBuf::from(tmp);
+
+        let written = std::fs::File::create(&tmp).and_then(|mut f| {
+            f.write_all(data)?;
+            f.sync_all()
+        });
+        match written.and_then(|_| std::fs::rename(&tmp, &path)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl DataSource for Dir {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(self.path(name))
+    }
+
+    fn open(&self, name: &str) -> io::Result<Box<dyn io::Read + '_>> {
+        Ok(Box::new(std::fs::File::open(self.path(name))?))
+    }
+}
+
+/// Files compiled into the binary, mostly useful for test fixtures
+#[derive(Debug, Clone, Copy)]
+pub struct Embedded(pub &'static [(&'static str, &'static [u8])]);
+
+impl DataSource for Embedded {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        self.get(name).map(<[u8]>::to_vec)
+    }
+
+    fn open(&self, name: &str) -> io::Result<Box<dyn io::Read + '_>> {
+        Ok(Box::new(self.get(name)?))
+    }
+}
+
+impl Embedded {
+    fn get(&self, name: &str) -> io::Result<&'static [u8]> {
+        self.0
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, data)| *data)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, name.to_owned()))
+    }
+}
+
+/// Files fetched over HTTP relative to a base URL
+#[cfg(feature = "remote")]
+#[derive(Debug, Clone)]
+pub struct Remote {
+    base_url: String,
+}
+
+#[cfg(feature = "remote")]
+impl Remote {
+    pub fn new(base_url: &str) -> Self {
+        Remote {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+impl DataSource for Remote {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        let mut data = vec![];
+        io::Read::read_to_end(&mut self.open(name)?, &mut data)?;
+        Ok(data)
+    }
+
+    fn open(&self, name: &str) -> io::Result<Box<dyn io::Read + '_>> {
+        let url = format!("{}/{}", self.base_url, name);
+        let res = ureq::get(&url)
+            .call()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Ok(Box::new(res.into_reader()))
+    }
+}
+
+/// The fixture files used by the tests in this crate
+#[cfg(test)]
+pub(crate) const FIXTURES: Embedded = Embedded(&[
+    ("kanjidic2.xml", include_bytes!("../fixtures/kanjidic2.xml")),
+    ("JMdict_e.xml", include_bytes!("../fixtures/JMdict_e.xml")),
+    ("JMnedict.xml", include_bytes!("../fixtures/JMnedict.xml")),
+    ("kanjivg.xml", include_bytes!("../fixtures/kanjivg.xml")),
+    ("examples.utf", include_bytes!("../fixtures/examples.utf")),
+]);
+
+#[test]
+fn test_embedded() {
+    assert!(FIXTURES.read_to_string("kanjidic2.xml").is_ok());
+    let e = FIXTURES.read("missing.txt").unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::NotFound);
+
+    let mut opened = vec![];
+    io::Read::read_to_end(&mut FIXTURES.open("kanjidic2.xml").unwrap(), &mut opened).unwrap();
+    assert_eq!(opened, FIXTURES.read("kanjidic2.xml").unwrap());
+    assert!(FIXTURES.open("missing.txt").is_err());
+}
+
+#[test]
+fn test_write() {
+    let root = std::env::temp_dir().join(format!("kanjisho-write-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let dir = Dir::new(&root);
+
+    dir.write("a.txt", b"first").unwrap();
+    // an open handle keeps the old contents, as a mapping would
+    let mut old = std::fs::File::open(dir.path("a.txt")).unwrap();
+    dir.write("a.txt", b"second").unwrap();
+    assert_eq!(dir.read("a.txt").unwrap(), b"second");
+    let mut text = String::new();
+    io::Read::read_to_string(&mut old, &mut text).unwrap();
+    assert_eq!(text, "first");
+    // no temporary files are left behind
+    assert_eq!(std::fs::read_dir(&root).unwrap().count(), 1);
+
+    assert!(dir.write("missing/a.txt", b"").is_err());
+    assert_eq!(std::fs::read_dir(&root).unwrap().count(), 1);
+    std::fs::remove_dir_all(&root).unwrap();
+}
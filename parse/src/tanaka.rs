@@ -0,0 +1,159 @@
+//! The Tanaka Corpus of example sentences, in the examples.utf format
+//! distributed with the Tatoeba project. Each sentence is a pair of lines:
+//!
+//! ```text
+//! A: 彼は毎日日本語を勉強します。    He studies Japanese every day.#ID=4702_1284
+//! B: 彼(かれ)[01] は 毎日 日本語~ を 勉強(べんきょう){勉強します}~
+//! ```
+//!
+//! The A line has the Japanese and English sentences, separated by a
+//! tab, and their Tatoeba ids. The B line indexes the sentence by the
+//! dictionary form of its words, each optionally followed by its reading,
+//! its JMdict sense, the form used in the sentence and a `~` if the
+//! sentence is a good example of the word.
+
+#[derive(Debug, PartialEq)]
+pub struct Example {
+    /// The ids of the Japanese and English sentences, e.g. 4702_1284
+    pub id: String,
+    pub japanese: String,
+    pub english: String,
+    pub words: Vec<Word>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Word {
+    /// The dictionary form of the word
+    pub headword: String,
+    pub reading: Option<String>,
+    /// The sense of the JMdict entry used, from 1
+    pub sense: Option<u32>,
+    /// The word as written in the sentence, if it differs
+    pub form: Option<String>,
+    /// Whether the sentence was checked as a good example of the word
+    pub checked: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// A line which isn't part of an A and B pair, by line number
+    BadLine(usize),
+    /// A word of a B line which can't be read, by line number
+    BadWord(usize, String),
+}
+
+/// Take the part of `rest` between `open` and `close` if it starts with
+/// `open`
+fn delimited<'a>(rest: &mut &'a str, open: char, close: char) -> Option<Option<&'a str>> {
+    let Some(inner) = rest.strip_prefix(open) else {
+        return Some(None);
+    };
+    let (inner, after) = inner.split_once(close)?;
+    *rest = after;
+    Some(Some(inner))
+}
+
+/// Parse a word of a B line, such as 勉強(べんきょう){勉強します}~
+fn word(token: &str) -> Option<Word> {
+    let (rest, checked) = match token.strip_suffix('~') {
+        Some(rest) => (rest, true),
+        None => (token, false),
+    };
+    let end = rest.find(['(', '[', '{']).unwrap_or(rest.len());
+    let (headword, mut rest) = rest.split_at(end);
+    if headword.is_empty() {
+        return None;
+    }
+
+    let reading = delimited(&mut rest, '(', ')')?;
+    let sense = match delimited(&mut rest, '[', ']')? {
+        Some(sense) => Some(sense.parse().ok()?),
+        None => None,
+    };
+    let form = delimited(&mut rest, '{', '}')?;
+    if !rest.is_empty() {
+        return None;
+    }
+
+    Some(Word {
+        headword: headword.to_owned(),
+        reading: reading.map(str::to_owned),
+        sense,
+        form: form.map(str::to_owned),
+        checked,
+    })
+}
+
+/// Parse the A line of a sentence into its id, Japanese and English
+fn sentence(line: &str) -> Option<(&str, &str, &str)> {
+    let (text, id) = line.strip_prefix("A: ")?.rsplit_once("#ID=")?;
+    let (japanese, english) = text.split_once('\t')?;
+    Some((id, japanese, english))
+}
+
+/// Parse the examples file
+pub fn parse(text: &str) -> Result<Vec<Example>, Error> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim_end()))
+        .filter(|(_, l)| !l.is_empty());
+
+    let mut out = vec![];
+    while let Some((n, a)) = lines.next() {
+        let (id, japanese, english) = sentence(a).ok_or(Error::BadLine(n))?;
+        let (n, b) = lines.next().ok_or(Error::BadLine(n))?;
+        let b = b.strip_prefix("B: ").ok_or(Error::BadLine(n))?;
+
+        let words = b
+            .split_whitespace()
+            .map(|t| word(t).ok_or_else(|| Error::BadWord(n, t.to_owned())))
+            .collect::<Result<_, _>>()?;
+        out.push(Example {
+            id: id.to_owned(),
+            japanese: japanese.to_owned(),
+            english: english.to_owned(),
+            words,
+        });
+    }
+    Ok(out)
+}
+
+#[test]
+fn test_word() {
+    assert_eq!(
+        word("勉強(べんきょう){勉強します}~"),
+        Some(Word {
+            headword: "勉強".into(),
+            reading: Some("べんきょう".into()),
+            sense: None,
+            form: Some("勉強します".into()),
+            checked: true,
+        })
+    );
+    assert_eq!(word("になる[01]").unwrap().sense, Some(1));
+    assert_eq!(word("は").unwrap().headword, "は");
+    for token in ["(かれ)", "彼(かれ", "彼[a]", "彼{彼の}(かれ)"] {
+        assert_eq!(word(token), None, "{}", token);
+    }
+}
+
+#[test]
+fn test_parse() {
+    use crate::{source::FIXTURES, DataSource};
+
+    let text = FIXTURES.read_to_string("examples.utf").unwrap();
+    let examples = parse(&text).unwrap();
+    assert_eq!(examples.len(), 2);
+    assert_eq!(examples[1].id, "4702_1284");
+    assert_eq!(examples[1].japanese, "彼は毎日日本語を勉強します。");
+    assert_eq!(examples[1].english, "He studies Japanese every day.");
+    let headwords: Vec<&str> = examples[1].words.iter().map(|w| &w.headword[..]).collect();
+    assert_eq!(headwords, ["彼", "は", "毎日", "日本語", "を", "勉強"]);
+
+    assert_eq!(parse("A: 猫。\tA cat.#ID=1_2\n"), Err(Error::BadLine(1)));
+    assert_eq!(
+        parse("A: 猫。\tA cat.#ID=1_2\nB: 猫[x]\n"),
+        Err(Error::BadWord(2, "猫[x]".into()))
+    );
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+
+use parse::DataSource;
+use roxmltree::{Document, ParsingOptions};
+
+use crate::db;
+
+/// The files checked when none are named
+pub const FILES: &[&str] = &["kanjidic2.xml", "JMdict_e.xml"];
+
+/// How many elements of each tag a document has, or where it isn't well
+/// formed
+pub fn tag_counts(text: &str) -> Result<BTreeMap<String, usize>, roxmltree::Error> {
+    let opt = ParsingOptions { allow_dtd: true };
+    let doc = Document::parse_with_options(text, opt)?;
+
+    let mut counts = BTreeMap::new();
+    for n in doc.descendants().filter(|n| n.is_element()) {
+        *counts.entry(n.tag_name().name().to_owned()).or_default() += 1;
+    }
+    Ok(counts)
+}
+
+/// Check that each file is well formed XML and print how many elements of
+/// each tag it has, most common first. Returns whether every file is.
+pub fn check(data: &dyn DataSource, files: &[&str]) -> bool {
+    let mut ok = true;
+    for file in files {
+        let text = db::read_xml(data, file);
+        match tag_counts(&text) {
+            Ok(counts) => {
+                let total: usize = counts.values().sum();
+                println!("{}: well formed, {} elements", file, total);
+                let mut counts: Vec<_> = counts.into_iter().collect();
+                counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+                for (tag, count) in counts {
+                    println!("  {:>8} {}", count, tag);
+                }
+            }
+            Err(e) => {
+                println!("{}: {}", file, e);
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+#[test]
+fn test_tag_counts() {
+    let counts = tag_counts("<a><b/><b>text</b><c/></a>").unwrap();
+    assert_eq!(counts["a"], 1);
+    assert_eq!(counts["b"], 2);
+    assert_eq!(counts["c"], 1);
+
+    let e = tag_counts("<a>\n<b></a>").unwrap_err();
+    assert_eq!(e.pos().row, 2);
+
+    let fixtures =
+        parse::source::Dir::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../parse/fixtures"));
+    assert!(check(&fixtures, FILES));
+}
@@ -0,0 +1,98 @@
+//! An export of kanjidic as a file Anki imports as notes, one per kanji.
+//! The headers tell Anki the separator and column names so it only asks
+//! for the note type and deck, and the last column tags each note with
+//! its grade and JLPT level.
+
+use model::{kanji::Kanji, provenance::Provenance};
+use parse::source::Dir;
+
+use super::{
+    columns::{self, Column},
+    sink::Sink,
+};
+
+/// The file written
+const FILE: &str = "kanjidic.anki.tsv";
+/// The columns when none are chosen
+pub const FIELDS: &str = "literal,meanings,on,kun,strokes,jlpt";
+
+/// The tags of a kanji, e.g. `kanjisho grade1 jlpt5`
+fn tags(k: &Kanji) -> String {
+    let mut tags = vec!["kanjisho".to_owned()];
+    tags.extend(k.info.grade.map(|g| format!("grade{}", g)));
+    tags.extend(k.info.jlptn.map(|n| format!("jlpt{}", n)));
+    tags.join(" ")
+}
+
+/// Render the notes with their headers
+fn render(columns: &[Column], entries: &[Kanji]) -> String {
+    let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+    let mut out = format!(
+        "#separator:tab\n#html:false\n#columns:{}\ttags\n#tags column:{}\n",
+        names.join("\t"),
+        columns.len() + 1
+    );
+    for k in entries {
+        for c in columns {
+            out += &columns::quote(&c.render(k), '\t');
+            out.push('\t');
+        }
+        out += &tags(k);
+        out.push('\n');
+    }
+    out
+}
+
+pub struct AnkiSink {
+    columns: Vec<Column>,
+    entries: Vec<Kanji>,
+}
+
+impl AnkiSink {
+    pub fn new(columns: Option<&[Column]>) -> Self {
+        AnkiSink {
+            columns: columns::or_default(columns, FIELDS),
+            entries: vec![],
+        }
+    }
+}
+
+impl Sink for AnkiSink {
+    fn put_kanji(&mut self, kanji: &Kanji, _provenance: &Provenance) {
+        self.entries.push(kanji.clone());
+    }
+
+    fn finalize(&mut self, data: &Dir, version: &str) {
+        data.write(FILE, render(&self.columns, &self.entries).as_bytes())
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", FILE, e));
+        super::write_version(data, FILE, version);
+    }
+}
+
+#[test]
+fn test_render() {
+    use model::kanji::{Info, References};
+
+    let kanji = |literal, grade, meanings: &[&str]| {
+        Kanji::builder(
+            literal,
+            Info::builder(1, 4).grade(grade).build(),
+            References::builder("0").build(),
+        )
+        .meanings(meanings.iter().map(|m| m.to_string()).collect())
+        .build()
+    };
+    let entries = [
+        kanji('日', Some(1), &["day", "sun"]),
+        kanji('丂', None, &["\"breath\""]),
+    ];
+    let columns = columns::parse("literal,{meanings} ({strokes})").unwrap();
+
+    assert_eq!(
+        render(&columns, &entries),
+        "#separator:tab\n#html:false\n#columns:literal\t{meanings} ({strokes})\ttags\n\
+         #tags column:3\n\
+         日\tday, sun (4)\tkanjisho grade1\n\
+         丂\t\"\"\"breath\"\" (4)\"\tkanjisho\n"
+    );
+}
@@ -0,0 +1,24 @@
+use model::{kanji::Kanji, provenance::Provenance};
+use parse::source::Dir;
+
+use super::sink::Sink;
+
+/// The read-only store format served by the backend when it runs without
+/// a database
+#[derive(Default)]
+pub struct StoreSink {
+    entries: Vec<Kanji>,
+}
+
+impl Sink for StoreSink {
+    fn put_kanji(&mut self, kanji: &Kanji, _provenance: &Provenance) {
+        self.entries.push(kanji.clone());
+    }
+
+    fn finalize(&mut self, data: &Dir, version: &str) {
+        let out = model::store::write(&self.entries).expect("failed to encode entries");
+        data.write("kanjidic.bin", &out)
+            .expect("failed to write kanjidic.bin");
+        super::write_version(data, "kanjidic.bin", version);
+    }
+}
@@ -0,0 +1,184 @@
+//! The columns of flat exports with a row per kanji, chosen with
+//! `--fields=`. Each column is a field name such as `meanings`, or a
+//! template putting fields in braces, e.g. `{on} / {kun}`. Columns are
+//! separated by commas, so templates can't contain them.
+
+use std::borrow::Cow;
+
+use model::kanji::Kanji;
+
+/// A field of a kanji, with lists joined into one value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Literal,
+    Meanings,
+    On,
+    Kun,
+    /// The on readings followed by the kun readings
+    Readings,
+    Nanori,
+    Strokes,
+    Grade,
+    /// The new JLPT level
+    Jlpt,
+    Freq,
+}
+
+impl Field {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "literal" => Some(Field::Literal),
+            "meanings" => Some(Field::Meanings),
+            "on" => Some(Field::On),
+            "kun" => Some(Field::Kun),
+            "readings" => Some(Field::Readings),
+            "nanori" => Some(Field::Nanori),
+            "strokes" => Some(Field::Strokes),
+            "grade" => Some(Field::Grade),
+            "jlpt" => Some(Field::Jlpt),
+            "freq" => Some(Field::Freq),
+            _ => None,
+        }
+    }
+
+    /// The value of the field, empty when the kanji doesn't have it.
+    /// Meanings are joined with commas and readings with 、.
+    pub fn render(self, k: &Kanji) -> String {
+        let number = |n: Option<u32>| n.map_or(String::new(), |n| n.to_string());
+        match self {
+            Field::Literal => k.literal.to_string(),
+            Field::Meanings => k.meanings.join(", "),
+            Field::On => k.on_readings.join("、"),
+            Field::Kun => k.kun_readings.join("、"),
+            Field::Readings => [&k.on_readings[..], &k.kun_readings[..]]
+                .concat()
+                .join("、"),
+            Field::Nanori => k.nanoris.join("、"),
+            Field::Strokes => k.info.stroke_count.to_string(),
+            Field::Grade => number(k.info.grade),
+            Field::Jlpt => number(k.info.jlptn),
+            Field::Freq => number(k.info.freq),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+/// A column of an export
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    /// The column as given, used as its header
+    pub name: String,
+    parts: Vec<Part>,
+}
+
+impl Column {
+    /// Parse a field name or a template, failing on unknown fields and
+    /// unmatched braces
+    pub fn parse(spec: &str) -> Option<Self> {
+        if let Some(field) = Field::parse(spec) {
+            return Some(Column {
+                name: spec.to_owned(),
+                parts: vec![Part::Field(field)],
+            });
+        }
+
+        let mut parts = vec![];
+        let mut rest = spec;
+        while let Some(open) = rest.find(['{', '}']) {
+            let (text, tail) = rest.split_at(open);
+            let (name, tail) = tail.strip_prefix('{')?.split_once('}')?;
+            if !text.is_empty() {
+                parts.push(Part::Text(text.to_owned()));
+            }
+            parts.push(Part::Field(Field::parse(name)?));
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_owned()));
+        }
+        // a column of only text is most likely a misspelt field
+        parts
+            .iter()
+            .any(|p| matches!(p, Part::Field(_)))
+            .then(|| Column {
+                name: spec.to_owned(),
+                parts,
+            })
+    }
+
+    pub fn render(&self, k: &Kanji) -> String {
+        self.parts
+            .iter()
+            .map(|p| match p {
+                Part::Text(text) => text.clone(),
+                Part::Field(field) => field.render(k),
+            })
+            .collect()
+    }
+}
+
+/// Quote a value containing the separator, a quote or a line break,
+/// doubling its quotes
+pub fn quote(value: &str, separator: char) -> Cow<'_, str> {
+    if value.contains([separator, '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Parse the comma separated columns of `--fields=`
+pub fn parse(spec: &str) -> Option<Vec<Column>> {
+    spec.split(',').map(Column::parse).collect()
+}
+
+/// The chosen columns, or the given defaults of an export
+pub fn or_default(columns: Option<&[Column]>, default: &str) -> Vec<Column> {
+    columns.map_or_else(|| parse(default).unwrap(), <[Column]>::to_vec)
+}
+
+#[test]
+fn test_columns() {
+    use model::kanji::{Info, References};
+
+    let k = Kanji::builder(
+        '日',
+        Info::builder(1, 4).grade(1).jlptn(5).build(),
+        References::builder("65e5").build(),
+    )
+    .on_readings(vec!["ニチ".into(), "ジツ".into()])
+    .kun_readings(vec!["ひ".into(), "-び".into()])
+    .meanings(vec!["day".into(), "sun".into()])
+    .build();
+    let render = |spec| {
+        parse(spec)
+            .unwrap()
+            .iter()
+            .map(|c| c.render(&k))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        render("literal,meanings,on,kun,strokes,jlpt"),
+        ["日", "day, sun", "ニチ、ジツ", "ひ、-び", "4", "5"]
+    );
+    assert_eq!(
+        render("{literal} ({strokes}),readings,freq"),
+        ["日 (4)", "ニチ、ジツ、ひ、-び", ""]
+    );
+    assert_eq!(render("{on} / {kun}"), ["ニチ、ジツ / ひ、-び"]);
+    assert_eq!(parse("literal,{on}").unwrap()[1].name, "{on}");
+
+    assert_eq!(quote("day, sun", '\t'), "day, sun");
+    assert_eq!(quote("day, sun", ','), "\"day, sun\"");
+    assert_eq!(quote("\"sun\"\tday", '\t'), "\"\"\"sun\"\"\tday\"");
+
+    for spec in ["literal,radical", "{on", "on}", "{}", "reading", "literal,"] {
+        assert!(parse(spec).is_none(), "{}", spec);
+    }
+}
@@ -0,0 +1,87 @@
+//! An export of kanjidic as CSV for spreadsheets, a row per kanji after a
+//! header row naming the columns. Values are quoted as in RFC 4180 and
+//! rows end with CRLF.
+
+use model::{kanji::Kanji, provenance::Provenance};
+use parse::source::Dir;
+
+use super::{
+    columns::{self, Column},
+    sink::Sink,
+};
+
+/// The file written
+const FILE: &str = "kanjidic.csv";
+/// The columns when none are chosen
+pub const FIELDS: &str = "literal,grade,strokes,jlpt,freq,meanings,readings";
+
+/// A row of quoted values
+fn row<'a>(values: impl IntoIterator<Item = &'a str>) -> String {
+    let values: Vec<_> = values.into_iter().map(|v| columns::quote(v, ',')).collect();
+    values.join(",") + "\r\n"
+}
+
+pub struct CsvSink {
+    columns: Vec<Column>,
+    out: String,
+}
+
+impl CsvSink {
+    pub fn new(columns: Option<&[Column]>) -> Self {
+        let columns = columns::or_default(columns, FIELDS);
+        let out = row(columns.iter().map(|c| c.name.as_str()));
+        CsvSink { columns, out }
+    }
+}
+
+impl Sink for CsvSink {
+    fn put_kanji(&mut self, kanji: &Kanji, _provenance: &Provenance) {
+        let values: Vec<String> = self.columns.iter().map(|c| c.render(kanji)).collect();
+        self.out += &row(values.iter().map(String::as_str));
+    }
+
+    fn finalize(&mut self, data: &Dir, version: &str) {
+        data.write(FILE, self.out.as_bytes())
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", FILE, e));
+        super::write_version(data, FILE, version);
+    }
+}
+
+#[test]
+fn test_rows() {
+    use model::kanji::{Info, References};
+
+    let k = Kanji::builder(
+        '日',
+        Info::builder(1, 4).grade(1).freq(1).build(),
+        References::builder("65e5").build(),
+    )
+    .on_readings(vec!["ニチ".into()])
+    .kun_readings(vec!["ひ".into()])
+    .meanings(vec!["day".into(), "\"sun\"".into()])
+    .build();
+    let provenance = Provenance {
+        literal: '日',
+        default: model::provenance::Source {
+            name: "kanjidic2".into(),
+            version: None,
+        },
+        fields: Default::default(),
+    };
+
+    let mut sink = CsvSink::new(None);
+    sink.put_kanji(&k, &provenance);
+    assert_eq!(
+        sink.out,
+        "literal,grade,strokes,jlpt,freq,meanings,readings\r\n\
+         日,1,4,,1,\"day, \"\"sun\"\"\",ニチ、ひ\r\n"
+    );
+
+    let columns = columns::parse("literal,{literal}: {meanings}").unwrap();
+    let mut sink = CsvSink::new(Some(&columns));
+    sink.put_kanji(&k, &provenance);
+    assert_eq!(
+        sink.out,
+        "literal,{literal}: {meanings}\r\n日,\"日: day, \"\"sun\"\"\"\r\n"
+    );
+}
@@ -0,0 +1,55 @@
+use model::entry::{Entry, Kanji, Reading, Sense};
+use parse::jmdict;
+
+/// Convert a parsed JMdict entry into the format stored in the database,
+/// keeping only English glosses
+pub fn convert(e: jmdict::Entry) -> Entry {
+    Entry::builder(e.ent_seq)
+        .kanji(
+            e.k_ele
+                .into_iter()
+                .map(|k| Kanji {
+                    text: k.keb,
+                    info: k.ke_inf,
+                    priority: k.ke_pri,
+                })
+                .collect(),
+        )
+        .readings(
+            e.r_ele
+                .into_iter()
+                .map(|r| Reading {
+                    text: r.reb,
+                    no_kanji: r.re_nokanji,
+                    restrictions: r.re_restr,
+                    info: r.re_inf,
+                    priority: r.re_pri,
+                })
+                .collect(),
+        )
+        .senses(
+            e.sense
+                .into_iter()
+                .map(|s| Sense {
+                    pos: s.pos,
+                    glosses: s
+                        .gloss
+                        .into_iter()
+                        .filter(|g| g.lang.is_empty() || g.lang == "eng")
+                        .map(|g| g.gloss)
+                        .collect(),
+                    gloss_keys: vec![],
+                    kanji_restrictions: s.stagk,
+                    reading_restrictions: s.stagr,
+                    field: s.field,
+                    misc: s.misc,
+                    dialect: s.dial,
+                    info: s.s_inf,
+                    xrefs: s.xref,
+                    antonyms: s.ant,
+                })
+                .collect(),
+        )
+        .classify()
+        .build()
+}
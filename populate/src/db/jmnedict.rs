@@ -0,0 +1,41 @@
+use model::{
+    entry::{Kanji, Reading},
+    name::{Name, Translation},
+};
+use parse::jmnedict;
+
+/// Convert a parsed JMnedict entry into the format stored in the database
+pub fn convert(e: jmnedict::Entry) -> Name {
+    Name {
+        ent_seq: e.ent_seq,
+        kanji: e
+            .k_ele
+            .into_iter()
+            .map(|k| Kanji {
+                text: k.keb,
+                info: k.ke_inf,
+                priority: k.ke_pri,
+            })
+            .collect(),
+        readings: e
+            .r_ele
+            .into_iter()
+            .map(|r| Reading {
+                text: r.reb,
+                no_kanji: r.re_nokanji,
+                restrictions: r.re_restr,
+                info: r.re_inf,
+                priority: r.re_pri,
+            })
+            .collect(),
+        translations: e
+            .trans
+            .into_iter()
+            .map(|t| Translation {
+                name_types: t.name_type,
+                details: t.trans_det,
+                xrefs: t.xref,
+            })
+            .collect(),
+    }
+}
@@ -1,30 +1,100 @@
-use core::panic;
+use model::{entry::Entry, kanji::Kanji, provenance::Provenance};
+use parse::source::Dir;
 
-use backend::data::kanji::Kanji;
+use super::{
+    kanji::{load_kanjidic, Duplicates},
+    kanjidic2_json,
+    sink::Sink,
+};
 
-use parse::util;
+/// The JSON formats kanjidic can be written in
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// The converted entries as served by the backend, with provenance
+    #[default]
+    Dump,
+    /// The kanjidic2 format of jmdict-simplified, as kanjidic2.json
+    Kanjidic2Json,
+}
+
+impl Format {
+    /// Parse a format as given on the command line
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "dump" => Some(Format::Dump),
+            "kanjidic2-json" => Some(Format::Kanjidic2Json),
+            _ => None,
+        }
+    }
+}
 
-use super::kanji::convert;
+/// Write the kanjidic2 JSON of jmdict-simplified, which is made from the
+/// entries as parsed rather than converted
+pub fn export_kanjidic2(data: &Dir, duplicates: Duplicates) {
+    let converted = load_kanjidic(data, duplicates).unwrap_or_else(|e| panic!("{}", e));
+    let out = kanjidic2_json::export(&converted.header, &converted.parsed);
+    data.write("kanjidic2.json", &serde_json::to_vec(&out).unwrap())
+        .expect("failed to write kanjidic2.json");
+    super::write_version(data, "kanjidic2.json", &converted.version);
+}
 
-pub fn update_kanjidic() -> () {
-    let klc = util::index_mapping(&parse::read_file("klc.txt")).expect("something");
+/// Write the converted JMdict entries as jmdict.json, with their glosses
+/// in place rather than interned as in the database. With `raw`, the
+/// entries as parsed are also written to jmdict_raw.json. Both are
+/// versioned by the date JMdict was created, when it says.
+pub fn dump_jmdict(data: &Dir, raw: bool) {
+    let _span = tracing::info_span!("dump_jmdict").entered();
+    let text = super::read_xml(data, "JMdict_e.xml");
+    let jmdict = parse::jmdict::parse(&text);
+    let version = jmdict.created();
+    if version.is_none() {
+        tracing::warn!("JMdict_e.xml has no creation date, dumps are unversioned");
+    }
+    let parsed: Vec<_> = jmdict.entries().collect();
+    if raw {
+        data.write("jmdict_raw.json", &serde_json::to_vec(&parsed).unwrap())
+            .expect("failed to write jmdict_raw.json");
+        if let Some(version) = version {
+            super::write_version(data, "jmdict_raw.json", version);
+        }
+    }
 
-    let jlpt = util::grade_mapping(&[
-        &parse::read_file("n1.txt"),
-        &parse::read_file("n2.txt"),
-        &parse::read_file("n3.txt"),
-        &parse::read_file("n4.txt"),
-        &parse::read_file("n5.txt"),
-    ])
-    .expect("grade mapping");
+    let entries: Vec<Entry> = parsed.into_iter().map(super::jmdict::convert).collect();
+    data.write("jmdict.json", &serde_json::to_vec(&entries).unwrap())
+        .expect("failed to write jmdict.json");
+    if let Some(version) = version {
+        super::write_version(data, "jmdict.json", version);
+    }
+}
+
+/// The converted entries and their provenance as JSON arrays
+#[derive(Default)]
+pub struct DumpSink {
+    entries: Vec<Kanji>,
+    provenance: Vec<Provenance>,
+}
 
-    let text = parse::read_file("kanjidic2.xml");
+impl Sink for DumpSink {
+    fn put_kanji(&mut self, kanji: &Kanji, provenance: &Provenance) {
+        self.entries.push(kanji.clone());
+        self.provenance.push(provenance.clone());
+    }
 
-    let entries: Vec<Kanji> = parse::kanjidic::parse(&text)
-        .entries()
-        .map(|k| convert(&k, &jlpt, &klc).unwrap())
-        .collect();
+    fn finalize(&mut self, data: &Dir, version: &str) {
+        data.write(
+            "kanjidic.json",
+            serde_json::to_string(&self.entries).unwrap().as_bytes(),
+        )
+        .expect("failed to write kanjidic.json");
+        data.write(
+            "provenance.json",
+            serde_json::to_string(&self.provenance).unwrap().as_bytes(),
+        )
+        .expect("failed to write provenance.json");
 
-    parse::write_file("kanjidic.json", serde_json::to_string(&entries).unwrap().as_bytes());
-    
+        // served as the version of the dumps by the backend
+        for name in ["kanjidic.json", "provenance.json"] {
+            super::write_version(data, name, version);
+        }
+    }
 }
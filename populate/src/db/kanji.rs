@@ -1,15 +1,426 @@
-use std::{collections::HashMap, vec};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{BufRead, BufReader, Cursor, Read},
+};
 
-use backend::data::kanji;
-use parse::kanjidic;
+use model::{
+    kanji,
+    provenance::{Provenance, Source},
+    query,
+};
+use parse::{encoding, jouyou, kanjidic, util, DataSource, PROGRESS_INTERVAL};
+
+use super::read;
 
 #[derive(Debug)]
 pub enum Error {
     NoLiteral,
     NoStrokeCount(char),
     NoRadical(char),
-    BadRadical(char),
+    BadReference(char),
     NoUcs(char),
+    Duplicate(char),
+    Header(kanjidic::ParseError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::NoLiteral => write!(f, "entry without a literal"),
+            Error::NoStrokeCount(c) => write!(f, "{} has no stroke count", c),
+            Error::NoRadical(c) => write!(f, "{} has no classical radical", c),
+            Error::BadReference(c) => write!(f, "{} has a malformed dictionary reference", c),
+            Error::NoUcs(c) => write!(f, "{} has no unicode codepoint", c),
+            Error::Duplicate(c) => write!(f, "{} has more than one entry", c),
+            Error::Header(e) => write!(f, "bad kanjidic {}", e),
+        }
+    }
+}
+
+/// What to do when several kanjidic entries share a literal
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Duplicates {
+    /// Fail the whole load
+    Error,
+    /// Keep the first entry and drop the rest
+    #[default]
+    KeepFirst,
+    /// Combine the entries, preferring the first for single values
+    Merge,
+}
+
+impl Duplicates {
+    /// Parse a policy as given on the command line
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "error" => Some(Duplicates::Error),
+            "keep-first" => Some(Duplicates::KeepFirst),
+            "merge" => Some(Duplicates::Merge),
+            _ => None,
+        }
+    }
+}
+
+/// Resolve entries sharing a literal according to `policy`, keeping the
+/// order of first appearance. Returns the literals which had duplicates.
+fn dedupe(
+    dict: Vec<kanjidic::Kanji>,
+    policy: Duplicates,
+) -> Result<(Vec<kanjidic::Kanji>, Vec<char>), Error> {
+    let mut index: HashMap<char, usize> = HashMap::with_capacity(dict.len());
+    let mut out: Vec<kanjidic::Kanji> = Vec::with_capacity(dict.len());
+    let mut duplicates = vec![];
+    for k in dict {
+        let Some(&i) = index.get(&k.literal) else {
+            index.insert(k.literal, out.len());
+            out.push(k);
+            continue;
+        };
+        if !duplicates.contains(&k.literal) {
+            duplicates.push(k.literal);
+        }
+        match policy {
+            Duplicates::Error => return Err(Error::Duplicate(k.literal)),
+            Duplicates::KeepFirst => (),
+            Duplicates::Merge => merge_entry(&mut out[i], k),
+        }
+    }
+    Ok((out, duplicates))
+}
+
+/// Add the values of a duplicate entry missing from the first one
+fn merge_entry(into: &mut kanjidic::Kanji, other: kanjidic::Kanji) {
+    fn extend<T: PartialEq>(into: &mut Vec<T>, other: Vec<T>) {
+        for v in other {
+            if !into.contains(&v) {
+                into.push(v);
+            }
+        }
+    }
+
+    into.grade = into.grade.or(other.grade);
+    into.freq = into.freq.or(other.freq);
+    into.jlpt = into.jlpt.or(other.jlpt);
+    extend(&mut into.stroke_count, other.stroke_count);
+    extend(&mut into.rad_name, other.rad_name);
+    extend(&mut into.nanori, other.nanori);
+    extend(&mut into.codepoint, other.codepoint);
+    extend(&mut into.radical, other.radical);
+    extend(&mut into.variant, other.variant);
+    extend(&mut into.dic_number, other.dic_number);
+    extend(&mut into.quecy_code, other.quecy_code);
+    extend(&mut into.rmgroup, other.rmgroup);
+}
+
+/// A codepoint as (coding standard, normalized value)
+pub type Code = (String, String);
+
+/// Normalize a codepoint value so the same character coded in codepoint
+/// and variant elements compares equal
+fn normalize_code(cp_type: &str, value: &str) -> Option<String> {
+    match cp_type {
+        "ucs" => u32::from_str_radix(value, 16)
+            .ok()
+            .map(|n| format!("{:x}", n)),
+        "jis208" | "jis212" | "jis213" => {
+            let parts = value
+                .split('-')
+                .map(|p| p.parse::<u32>())
+                .collect::<Result<Vec<_>, _>>()
+                .ok()?;
+            match parts[..] {
+                [k, t] => Some(format!("1-{}-{}", k, t)),
+                [p, k, t] => Some(format!("{}-{}-{}", p, k, t)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Map the codepoints of every entry to their literal
+pub fn codepoint_mapping(entries: &[kanjidic::Kanji]) -> HashMap<Code, char> {
+    let mut m = HashMap::new();
+    for k in entries {
+        for c in &k.codepoint {
+            if let Some(value) = normalize_code(&c.cp_type, &c.cp_value) {
+                m.insert((c.cp_type.clone(), value), k.literal);
+            }
+        }
+    }
+    m
+}
+
+/// Resolve the variant references of an entry into literals. Unicode
+/// references always resolve, other codings only when the referenced
+/// kanji is in the dictionary.
+fn resolve_variants(k: &kanjidic::Kanji, codes: &HashMap<Code, char>) -> Vec<char> {
+    let mut variants = vec![];
+    for v in &k.variant {
+        let Some(value) = normalize_code(&v.var_type, &v.variant) else {
+            continue;
+        };
+        let c = codes
+            .get(&(v.var_type.clone(), value.clone()))
+            .copied()
+            .or_else(|| {
+                (v.var_type == "ucs")
+                    .then(|| {
+                        u32::from_str_radix(&value, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                    })
+                    .flatten()
+            });
+        if let Some(c) = c.filter(|c| *c != k.literal && !variants.contains(c)) {
+            variants.push(c);
+        }
+    }
+    variants
+}
+
+/// A list of values for a single field taken from outside of kanjidic
+pub struct ReferenceSource {
+    pub source: Source,
+    pub values: HashMap<char, u32>,
+}
+
+impl ReferenceSource {
+    pub fn new(name: &str, values: HashMap<char, u32>) -> Self {
+        ReferenceSource {
+            source: Source {
+                name: name.into(),
+                version: None,
+            },
+            values,
+        }
+    }
+}
+
+/// Everything merged into the kanjidic entries during conversion. Fields
+/// with several sources are listed in priority order.
+pub struct Sources {
+    pub kanjidic: Source,
+    pub jlpt: Vec<ReferenceSource>,
+    pub klc: Vec<ReferenceSource>,
+    /// Grades for kanji kanjidic has none for
+    pub grade: Vec<ReferenceSource>,
+    /// The jouyou table kanjidic is checked against, if available
+    pub jouyou: Vec<jouyou::Entry>,
+}
+
+/// Load the reference lists shipped in the data directory
+pub fn load_sources(data: &dyn DataSource, kanjidic: Source) -> Sources {
+    let klc = util::index_mapping(&read(data, "klc.txt")).expect("something");
+
+    let jlpt = util::grade_mapping(&[
+        &read(data, "n1.txt"),
+        &read(data, "n2.txt"),
+        &read(data, "n3.txt"),
+        &read(data, "n4.txt"),
+        &read(data, "n5.txt"),
+    ])
+    .expect("grade mapping");
+
+    // the jouyou table is optional since it isn't fetched with the rest
+    let jouyou = data
+        .read_to_string("jouyou.csv")
+        .map(|t| {
+            jouyou::parse(&t).unwrap_or_else(|e| panic!("failed to parse jouyou.csv: {:?}", e))
+        })
+        .unwrap_or_default();
+
+    Sources {
+        kanjidic,
+        jlpt: vec![ReferenceSource::new("jlpt-estimate", jlpt)],
+        klc: vec![ReferenceSource::new("klc", klc)],
+        grade: vec![ReferenceSource::new(
+            "wikipedia-jouyou",
+            jouyou::grade_mapping(&jouyou),
+        )],
+        jouyou,
+    }
+}
+
+/// The converted contents of the kanjidic file
+pub struct Converted {
+    /// The KANJIDIC database version, in the format YYYY-NN.
+    pub version: String,
+    /// The header of the kanjidic file
+    pub header: kanjidic::Header,
+    pub entries: Vec<kanji::Kanji>,
+    pub provenance: Vec<Provenance>,
+    /// The entries as parsed, before conversion
+    pub parsed: Vec<kanjidic::Kanji>,
+    /// The literals with more than one entry in the file
+    pub duplicates: Vec<char>,
+}
+
+/// Open kanjidic to be read an entry at a time. A UTF-8 file, as kanjidic
+/// is published, is streamed from the data source rather than held in
+/// memory; any other is decoded first.
+fn open_kanjidic(data: &dyn DataSource) -> Result<kanjidic::Stream<Box<dyn Read + '_>>, Error> {
+    const FILE: &str = "kanjidic2.xml";
+    fn failed(e: impl std::fmt::Display) -> ! {
+        panic!("failed to read {}: {}", FILE, e)
+    }
+
+    let mut reader = BufReader::new(data.open(FILE).unwrap_or_else(|e| failed(e)));
+    let head = reader.fill_buf().unwrap_or_else(|e| failed(e));
+    let reader: Box<dyn Read> = if encoding::is_utf8(head) {
+        Box::new(reader)
+    } else {
+        Box::new(Cursor::new(super::read_xml(data, FILE).into_bytes()))
+    };
+
+    match kanjidic::parse_stream(reader) {
+        Ok(stream) => Ok(stream),
+        Err(kanjidic::StreamError::Header(e)) => Err(Error::Header(e)),
+        Err(e) => failed(e),
+    }
+}
+
+/// Every entry of the stream, skipping malformed ones with a warning
+fn read_entries<R: Read>(stream: kanjidic::Stream<R>) -> Vec<kanjidic::Kanji> {
+    let mut dict = vec![];
+    for (i, k) in stream.enumerate() {
+        if (i + 1) % PROGRESS_INTERVAL == 0 {
+            tracing::debug!(entries = i + 1, "parsing kanjidic");
+        }
+        match k {
+            Ok(k) => dict.push(k),
+            Err(e @ (kanjidic::StreamError::Entry(_) | kanjidic::StreamError::Xml(_))) => {
+                println!("Warning: skipping {}", e)
+            }
+            Err(e) => panic!("failed to read kanjidic2.xml: {}", e),
+        }
+    }
+    dict
+}
+
+/// Read, parse and convert the kanjidic file, merging in every
+/// reference source. Entries sharing a literal are resolved by `policy`.
+pub fn load_kanjidic(data: &dyn DataSource, policy: Duplicates) -> Result<Converted, Error> {
+    let _span = tracing::info_span!("load_kanjidic").entered();
+    let stream = open_kanjidic(data)?;
+
+    let header = stream.header().clone();
+    let version = header.database_version.clone();
+    let sources = load_sources(
+        data,
+        Source {
+            name: "kanjidic2".into(),
+            version: Some(version.clone()),
+        },
+    );
+
+    let dict = read_entries(stream);
+    let (dict, duplicates) = dedupe(dict, policy)?;
+    if !duplicates.is_empty() {
+        let list: String = duplicates.iter().collect();
+        println!(
+            "Warning: {} literals have duplicate entries ({:?}): {}",
+            duplicates.len(),
+            policy,
+            list
+        );
+    }
+    let codes = codepoint_mapping(&dict);
+
+    for warning in jouyou_discrepancies(&dict, &sources.jouyou, &codes) {
+        println!("Warning: {}", warning);
+    }
+    for k in &dict {
+        for q in &k.quecy_code {
+            if let Err(e) = query::validate(&q.qc_type, &q.q_code) {
+                println!("Warning: {} has an {}", k.literal, e);
+            }
+        }
+    }
+
+    let mut entries = Vec::with_capacity(dict.len());
+    let mut provenance = Vec::with_capacity(dict.len());
+    for (i, chunk) in dict.chunks(PROGRESS_INTERVAL).enumerate() {
+        let _span =
+            tracing::info_span!("convert", from = i * PROGRESS_INTERVAL, of = dict.len()).entered();
+        for k in chunk {
+            let (e, p) = convert(k, &sources, &codes)?;
+            entries.push(e);
+            provenance.push(p);
+        }
+    }
+
+    Ok(Converted {
+        version,
+        header,
+        entries,
+        provenance,
+        parsed: dict,
+        duplicates,
+    })
+}
+
+/// Compare the grades and old forms in kanjidic against the jouyou
+/// table, describing every disagreement
+fn jouyou_discrepancies(
+    dict: &[kanjidic::Kanji],
+    jouyou: &[jouyou::Entry],
+    codes: &HashMap<Code, char>,
+) -> Vec<String> {
+    if jouyou.is_empty() {
+        return vec![];
+    }
+
+    let by_literal: HashMap<char, &kanjidic::Kanji> = dict.iter().map(|k| (k.literal, k)).collect();
+    let mut warnings = vec![];
+
+    for j in jouyou {
+        let Some(k) = by_literal.get(&j.kanji) else {
+            warnings.push(format!("{} is jouyou but missing from kanjidic", j.kanji));
+            continue;
+        };
+        if k.grade != j.grade {
+            warnings.push(format!(
+                "{} has grade {:?} in kanjidic but {:?} in the jouyou table",
+                j.kanji, k.grade, j.grade
+            ));
+        }
+        let variants = resolve_variants(k, codes);
+        for old in j.old.iter().filter(|o| !variants.contains(o)) {
+            warnings.push(format!(
+                "{} has old form {} in the jouyou table but not as a kanjidic variant",
+                j.kanji, old
+            ));
+        }
+    }
+
+    // kyouiku and secondary school grades should only be given to jouyou kanji
+    let listed: Vec<char> = jouyou.iter().map(|j| j.kanji).collect();
+    for k in dict {
+        if matches!(k.grade, Some(1..=8)) && !listed.contains(&k.literal) {
+            warnings.push(format!(
+                "{} has grade {:?} in kanjidic but is not in the jouyou table",
+                k.literal, k.grade
+            ));
+        }
+    }
+
+    warnings
+}
+
+/// Take the value for a literal from the first source which has one,
+/// recording which source it was under `field` in the provenance
+fn merge(
+    literal: char,
+    sources: &[ReferenceSource],
+    field: &str,
+    provenance: &mut Provenance,
+) -> Option<u32> {
+    let (value, source) = sources
+        .iter()
+        .find_map(|s| s.values.get(&literal).map(|v| (*v, &s.source)))?;
+    provenance.fields.insert(field.into(), source.clone());
+    Some(value)
 }
 
 /// Convert a Kanjidic entry into a backend Kanji entry
@@ -17,9 +428,9 @@ pub enum Error {
 /// elements and add missing information from other sources.
 pub fn convert(
     k: &kanjidic::Kanji,
-    jlpt: &HashMap<char, u32>,
-    klc: &HashMap<char, u32>,
-) -> Result<kanji::Kanji, Error> {
+    sources: &Sources,
+    codes: &HashMap<Code, char>,
+) -> Result<(kanji::Kanji, Provenance), Error> {
     if k.literal == char::default() {
         return Err(Error::NoLiteral);
     }
@@ -58,54 +469,285 @@ pub fn convert(
         .find(|d| d.dr_type == "heisig6")
         .map(|d| d.dic_ref.parse::<u32>())
         .map_or(Ok(None), |r| r.map(Some))
-        .map_err(|_e| Error::NoLiteral)?;
+        .map_err(|_e| Error::BadReference(k.literal))?;
 
-    Ok(kanji::Kanji {
+    let mut provenance = Provenance {
         literal: k.literal,
-        info: kanji::Info {
-            radical: classic,
-            radical_n: nelson.unwrap_or(classic),
-            stroke_count,
-            grade: k.grade,
-            freq: k.freq,
-            jlpt: k.jlpt,
-            jlptn: jlpt.get(&k.literal).copied(),
+        default: sources.kanjidic.clone(),
+        fields: BTreeMap::new(),
+    };
+    let jlptn = merge(k.literal, &sources.jlpt, "info.jlptn", &mut provenance);
+    let grade = k
+        .grade
+        .or_else(|| merge(k.literal, &sources.grade, "info.grade", &mut provenance));
+    let klc = merge(k.literal, &sources.klc, "references.klc", &mut provenance);
+
+    let info = kanji::Info::builder(classic, stroke_count)
+        .radical_n(nelson.unwrap_or(classic))
+        .grade(grade)
+        .freq(k.freq)
+        .jlpt(k.jlpt)
+        .jlptn(jlptn)
+        .build();
+    let dict_index = dict_index(k, rtk, klc);
+    let references = kanji::References::builder(ucs).rtk(rtk).klc(klc).build();
+
+    let kanji = kanji::Kanji::builder(k.literal, info, references)
+        .on_readings(readings(rmgroup, "ja_on"))
+        .kun_readings(readings(rmgroup, "ja_kun"))
+        .meanings(
+            rmgroup
+                .map(|g| {
+                    g.meaning
+                        .iter()
+                        .filter(|m| m.m_lang == "en")
+                        .map(|m| m.meaning.clone())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        )
+        .nanoris(k.nanori.clone())
+        .variants(resolve_variants(k, codes))
+        .dict_index(dict_index)
+        .query(
+            k.quecy_code
+                .iter()
+                .map(|q| kanji::QueryCode {
+                    qc_type: q.qc_type.clone(),
+                    code: q.q_code.clone(),
+                    misclass: q.skip_misclass.clone(),
+                })
+                .collect(),
+        )
+        .build();
+
+    Ok((kanji, provenance))
+}
+
+/// The number of a kanji in each dictionary with numeric references,
+/// along with the rtk and klc references
+fn dict_index(k: &kanjidic::Kanji, rtk: Option<u32>, klc: Option<u32>) -> BTreeMap<String, u32> {
+    let mut index = BTreeMap::new();
+    for d in &k.dic_number {
+        if let Ok(n) = d.dic_ref.parse() {
+            index.entry(d.dr_type.clone()).or_insert(n);
+        }
+    }
+    index.extend(rtk.map(|n| ("rtk".to_owned(), n)));
+    index.extend(klc.map(|n| ("klc".to_owned(), n)));
+    index
+}
+
+/// The readings of the given r_type in a reading/meaning group
+fn readings(rmgroup: Option<&kanjidic::ReadingMeaning>, r_type: &str) -> Vec<String> {
+    rmgroup
+        .map(|g| {
+            g.reading
+                .iter()
+                .filter(|r| r.r_type == r_type)
+                .map(|r| r.reading.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_resolve_variants() {
+    let entries = vec![
+        kanjidic::Kanji {
+            literal: '塩',
+            codepoint: vec![kanjidic::Codepoint {
+                cp_value: "1-38-81".into(),
+                cp_type: "jis208".into(),
+            }],
+            variant: vec![kanjidic::Variant {
+                variant: "9e7d".into(),
+                var_type: "ucs".into(),
+            }],
+            ..Default::default()
         },
-        references: kanji::References {
-            ucs,
-            jis208: None,
-            jis212: None,
-            jis213: None,
-            rtk,
-            klc: klc.get(&k.literal).copied(),
+        kanjidic::Kanji {
+            literal: '鹽',
+            variant: vec![kanjidic::Variant {
+                variant: "38-81".into(),
+                var_type: "jis208".into(),
+            }],
+            ..Default::default()
         },
-        on_readings: rmgroup
-            .map(|g| {
-                g.reading
-                    .iter()
-                    .filter(|r| r.r_type == "ja_on")
-                    .map(|r| r.reading.clone())
-                    .collect()
-            })
-            .unwrap_or_default(),
-        kun_readings: rmgroup
-            .map(|g| {
-                g.reading
-                    .iter()
-                    .filter(|r| r.r_type == "ja_kun")
-                    .map(|r| r.reading.clone())
-                    .collect()
-            })
-            .unwrap_or_default(),
-        meanings: rmgroup
-            .map(|g| {
-                g.meaning
-                    .iter()
-                    .filter(|m| m.m_lang == "en")
-                    .map(|m| m.meaning.clone())
-                    .collect()
-            })
-            .unwrap_or_default(),
-        nanoris: k.nanori.clone(),
-    })
+    ];
+    let codes = codepoint_mapping(&entries);
+    assert_eq!(resolve_variants(&entries[0], &codes), ['鹽']);
+    assert_eq!(resolve_variants(&entries[1], &codes), ['塩']);
+}
+
+#[test]
+fn test_jouyou_discrepancies() {
+    let dict = vec![
+        kanjidic::Kanji {
+            literal: '亜',
+            grade: Some(8),
+            ..Default::default()
+        },
+        kanjidic::Kanji {
+            literal: '悪',
+            grade: Some(3),
+            ..Default::default()
+        },
+        kanjidic::Kanji {
+            literal: '丑',
+            grade: Some(9),
+            ..Default::default()
+        },
+    ];
+    let jouyou = vec![
+        jouyou::Entry {
+            kanji: '亜',
+            old: vec![],
+            grade: Some(8),
+        },
+        jouyou::Entry {
+            kanji: '悪',
+            old: vec!['惡'],
+            grade: Some(2),
+        },
+        jouyou::Entry {
+            kanji: '愛',
+            old: vec![],
+            grade: Some(4),
+        },
+    ];
+
+    let warnings = jouyou_discrepancies(&dict, &jouyou, &codepoint_mapping(&dict));
+    assert_eq!(warnings.len(), 3, "{:?}", warnings);
+    assert!(warnings[0].starts_with("悪 has grade Some(3)"));
+    assert!(warnings[1].contains("old form 惡"));
+    assert!(warnings[2].starts_with("愛 is jouyou"));
+}
+
+#[test]
+fn test_merge() {
+    let sources = [
+        ReferenceSource::new("first", HashMap::from([('日', 5)])),
+        ReferenceSource::new("second", HashMap::from([('日', 4), ('亜', 1)])),
+    ];
+    let mut provenance = Provenance {
+        literal: '日',
+        default: Source {
+            name: "kanjidic2".into(),
+            version: None,
+        },
+        fields: BTreeMap::new(),
+    };
+
+    assert_eq!(merge('日', &sources, "a", &mut provenance), Some(5));
+    assert_eq!(merge('亜', &sources, "b", &mut provenance), Some(1));
+    assert_eq!(merge('月', &sources, "c", &mut provenance), None);
+    assert_eq!(provenance.fields["a"].name, "first");
+    assert_eq!(provenance.fields["b"].name, "second");
+    assert!(!provenance.fields.contains_key("c"));
+}
+
+#[test]
+fn test_dedupe() {
+    let dict = || {
+        vec![
+            kanjidic::Kanji {
+                literal: '日',
+                grade: Some(1),
+                nanori: vec!["あき".into()],
+                ..Default::default()
+            },
+            kanjidic::Kanji {
+                literal: '月',
+                ..Default::default()
+            },
+            kanjidic::Kanji {
+                literal: '日',
+                grade: Some(2),
+                freq: Some(1),
+                nanori: vec!["あき".into(), "か".into()],
+                ..Default::default()
+            },
+        ]
+    };
+
+    assert!(matches!(
+        dedupe(dict(), Duplicates::Error),
+        Err(Error::Duplicate('日'))
+    ));
+
+    let (kept, duplicates) = dedupe(dict(), Duplicates::KeepFirst).unwrap();
+    assert_eq!(duplicates, ['日']);
+    assert_eq!(kept.len(), 2);
+    assert_eq!(kept[0], dict()[0]);
+
+    let (merged, _) = dedupe(dict(), Duplicates::Merge).unwrap();
+    assert_eq!(merged[0].grade, Some(1));
+    assert_eq!(merged[0].freq, Some(1));
+    assert_eq!(merged[0].nanori, ["あき", "か"]);
+    assert_eq!(merged[1].literal, '月');
+
+    let mut unique = dict();
+    unique.pop();
+    let (_, duplicates) = dedupe(unique, Duplicates::Error).unwrap();
+    assert!(duplicates.is_empty());
+}
+
+#[test]
+fn test_dict_index() {
+    let dic_ref = |dr_type: &str, dic_ref: &str| kanjidic::DicRef {
+        dic_ref: dic_ref.into(),
+        dr_type: dr_type.into(),
+        ..Default::default()
+    };
+    let k = kanjidic::Kanji {
+        literal: '日',
+        dic_number: vec![
+            dic_ref("nelson_c", "2097"),
+            dic_ref("heisig6", "12"),
+            dic_ref("busy_people", "1.A"),
+            dic_ref("moro", "13733"),
+            dic_ref("moro", "13733A"),
+        ],
+        ..Default::default()
+    };
+
+    let index = dict_index(&k, Some(12), None);
+    assert_eq!(
+        index.into_iter().collect::<Vec<_>>(),
+        [
+            ("heisig6".to_owned(), 12),
+            ("moro".to_owned(), 13733),
+            ("nelson_c".to_owned(), 2097),
+            ("rtk".to_owned(), 12),
+        ]
+    );
+}
+
+#[test]
+fn test_open_kanjidic() {
+    use parse::source::Embedded;
+
+    let text = "<kanjidic2>
+<header><database_version>2023-01</database_version></header>
+<character><literal>亜</literal></character>
+<character><literal>唖</literal><misc><grade>x</grade></misc></character>
+<character><literal>娃</literal></character>
+</kanjidic2>";
+    let mut utf16 = vec![0xFF, 0xFE];
+    utf16.extend(text.encode_utf16().flat_map(|u| u.to_le_bytes()));
+    let utf16: &'static [u8] = Box::leak(utf16.into_boxed_slice());
+
+    for file in [text.as_bytes(), utf16] {
+        let files = Box::leak(Box::new([("kanjidic2.xml", file)]));
+        let source = Embedded(files);
+        let stream = open_kanjidic(&source).unwrap();
+        assert_eq!(stream.header().database_version, "2023-01");
+        let literals: String = read_entries(stream).iter().map(|k| k.literal).collect();
+        assert_eq!(literals, "亜娃");
+    }
+
+    const HEADLESS: Embedded =
+        Embedded(&[("kanjidic2.xml", b"<kanjidic2><character/></kanjidic2>")]);
+    assert!(matches!(open_kanjidic(&HEADLESS), Err(Error::Header(_))));
 }
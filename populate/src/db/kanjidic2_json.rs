@@ -0,0 +1,285 @@
+//! The kanjidic2 JSON format of the jmdict-simplified project, which
+//! existing JavaScript tooling reads. It mirrors the KANJIDIC XML closely,
+//! so it is built from the entries as parsed rather than converted.
+
+use std::collections::BTreeSet;
+
+use parse::kanjidic;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Kanjidic2 {
+    /// The version of the exporter
+    pub version: String,
+    /// The languages of the meanings
+    pub languages: Vec<String>,
+    pub dict_date: String,
+    pub file_version: u32,
+    pub database_version: String,
+    pub characters: Vec<Character>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Character {
+    pub literal: char,
+    pub codepoints: Vec<TypedValue<String>>,
+    pub radicals: Vec<TypedValue<u32>>,
+    pub misc: Misc,
+    pub dictionary_references: Vec<DictionaryReference>,
+    pub query_codes: Vec<QueryCode>,
+    pub reading_meaning: Option<ReadingMeaning>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypedValue<T> {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub value: T,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Misc {
+    pub grade: Option<u32>,
+    pub stroke_counts: Vec<u32>,
+    pub variants: Vec<TypedValue<String>>,
+    pub frequency: Option<u32>,
+    pub radical_names: Vec<String>,
+    pub jlpt_level: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DictionaryReference {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub morohashi: Option<Morohashi>,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Morohashi {
+    pub volume: Option<u32>,
+    pub page: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryCode {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub skip_misclassification: Option<String>,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadingMeaning {
+    pub groups: Vec<Group>,
+    pub nanori: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Group {
+    pub readings: Vec<Reading>,
+    pub meanings: Vec<Meaning>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reading {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Not read from KANJIDIC, always null
+    pub on_type: Option<String>,
+    /// Not read from KANJIDIC, always null
+    pub status: Option<String>,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Meaning {
+    pub lang: String,
+    pub value: String,
+}
+
+fn typed<T: Clone>(kind: &str, value: &T) -> TypedValue<T> {
+    TypedValue {
+        kind: kind.to_owned(),
+        value: value.clone(),
+    }
+}
+
+fn character(k: &kanjidic::Kanji) -> Character {
+    let groups: Vec<Group> = k
+        .rmgroup
+        .iter()
+        .map(|g| Group {
+            readings: g
+                .reading
+                .iter()
+                .map(|r| Reading {
+                    kind: r.r_type.clone(),
+                    on_type: None,
+                    status: None,
+                    value: r.reading.clone(),
+                })
+                .collect(),
+            meanings: g
+                .meaning
+                .iter()
+                .map(|m| Meaning {
+                    lang: m.m_lang.clone(),
+                    value: m.meaning.clone(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    Character {
+        literal: k.literal,
+        codepoints: k
+            .codepoint
+            .iter()
+            .map(|c| typed(&c.cp_type, &c.cp_value))
+            .collect(),
+        radicals: k
+            .radical
+            .iter()
+            .map(|r| typed(&r.rad_type, &r.rad_value))
+            .collect(),
+        misc: Misc {
+            grade: k.grade,
+            stroke_counts: k.stroke_count.clone(),
+            variants: k
+                .variant
+                .iter()
+                .map(|v| typed(&v.var_type, &v.variant))
+                .collect(),
+            frequency: k.freq,
+            radical_names: k.rad_name.clone(),
+            jlpt_level: k.jlpt,
+        },
+        dictionary_references: k
+            .dic_number
+            .iter()
+            .map(|d| DictionaryReference {
+                kind: d.dr_type.clone(),
+                morohashi: (d.m_vol.is_some() || d.m_page.is_some()).then_some(Morohashi {
+                    volume: d.m_vol,
+                    page: d.m_page,
+                }),
+                value: d.dic_ref.clone(),
+            })
+            .collect(),
+        query_codes: k
+            .quecy_code
+            .iter()
+            .map(|q| QueryCode {
+                kind: q.qc_type.clone(),
+                skip_misclassification: q.skip_misclass.clone(),
+                value: q.q_code.clone(),
+            })
+            .collect(),
+        reading_meaning: (!groups.is_empty() || !k.nanori.is_empty()).then(|| ReadingMeaning {
+            groups,
+            nanori: k.nanori.clone(),
+        }),
+    }
+}
+
+/// Build the export from the header and entries of a kanjidic file
+pub fn export(header: &kanjidic::Header, entries: &[kanjidic::Kanji]) -> Kanjidic2 {
+    let languages: BTreeSet<&str> = entries
+        .iter()
+        .flat_map(|k| &k.rmgroup)
+        .flat_map(|g| &g.meaning)
+        .map(|m| m.m_lang.as_str())
+        .collect();
+
+    Kanjidic2 {
+        version: env!("CARGO_PKG_VERSION").into(),
+        languages: languages.into_iter().map(str::to_owned).collect(),
+        dict_date: header.date_of_creation.clone(),
+        file_version: header.file_version,
+        database_version: header.database_version.clone(),
+        characters: entries.iter().map(character).collect(),
+    }
+}
+
+#[test]
+fn test_export() {
+    let header = kanjidic::Header {
+        file_version: 4,
+        database_version: "2022-318".into(),
+        date_of_creation: "2022-11-14".into(),
+    };
+    let k = kanjidic::Kanji {
+        literal: '亜',
+        grade: Some(8),
+        stroke_count: vec![7],
+        codepoint: vec![kanjidic::Codepoint {
+            cp_value: "4e9c".into(),
+            cp_type: "ucs".into(),
+        }],
+        dic_number: vec![
+            kanjidic::DicRef {
+                dic_ref: "43".into(),
+                dr_type: "nelson_c".into(),
+                ..Default::default()
+            },
+            kanjidic::DicRef {
+                dic_ref: "272".into(),
+                dr_type: "moro".into(),
+                m_vol: Some(1),
+                m_page: Some(525),
+            },
+        ],
+        rmgroup: vec![kanjidic::ReadingMeaning {
+            reading: vec![kanjidic::Reading {
+                reading: "ア".into(),
+                r_type: "ja_on".into(),
+            }],
+            meaning: vec![kanjidic::Meaning {
+                meaning: "Asia".into(),
+                m_lang: "en".into(),
+            }],
+        }],
+        ..Default::default()
+    };
+    let bare = kanjidic::Kanji {
+        literal: '丂',
+        ..Default::default()
+    };
+
+    let out = serde_json::to_value(export(&header, &[k, bare])).unwrap();
+    assert_eq!(out["languages"], serde_json::json!(["en"]));
+    assert_eq!(out["dictDate"], "2022-11-14");
+    assert_eq!(out["databaseVersion"], "2022-318");
+
+    let c = &out["characters"][0];
+    assert_eq!(
+        c["codepoints"][0],
+        serde_json::json!({ "type": "ucs", "value": "4e9c" })
+    );
+    assert_eq!(c["misc"]["strokeCounts"], serde_json::json!([7]));
+    assert_eq!(c["misc"]["jlptLevel"], serde_json::Value::Null);
+    assert_eq!(
+        c["dictionaryReferences"][0]["morohashi"],
+        serde_json::Value::Null
+    );
+    assert_eq!(
+        c["dictionaryReferences"][1]["morohashi"],
+        serde_json::json!({ "volume": 1, "page": 525 })
+    );
+    let group = &c["readingMeaning"]["groups"][0];
+    assert_eq!(group["readings"][0]["type"], "ja_on");
+    assert_eq!(
+        group["meanings"][0],
+        serde_json::json!({ "lang": "en", "value": "Asia" })
+    );
+    assert_eq!(
+        out["characters"][1]["readingMeaning"],
+        serde_json::Value::Null
+    );
+}
@@ -0,0 +1,120 @@
+//! Migrations of the stored documents between schema versions, run by
+//! `populate migrate`. The version reached is recorded in the "meta"
+//! collection, and the backend refuses to serve any other.
+
+use model::meta::{SchemaVersion, SCHEMA_KIND, SCHEMA_VERSION};
+use mongodb::{
+    bson::{doc, DateTime, Document},
+    options::ReplaceOptions,
+    sync::Database,
+};
+
+/// A step from the previous schema version to `version`
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub run: fn(&Database) -> mongodb::error::Result<()>,
+}
+
+/// Every migration, in order. The last is always at [`SCHEMA_VERSION`].
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "index kanjidic and provenance literals uniquely",
+        run: |database| {
+            super::mongo::unique_literal(&database.collection::<Document>("kanjidic"))?;
+            super::mongo::unique_literal(&database.collection::<Document>("provenance"))
+        },
+    },
+    Migration {
+        version: 2,
+        description: "number kanji by dictionary in dict_index, from rtk and klc until repopulated",
+        run: |database| {
+            let con = database.collection::<Document>("kanjidic");
+            for dict in ["rtk", "klc"] {
+                let reference = format!("references.{}", dict);
+                con.update_many(
+                    doc! { &reference: { "$exists": true } },
+                    vec![doc! { "$set": { format!("dict_index.{}", dict): format!("${}", reference) } }],
+                    None,
+                )?;
+            }
+            super::mongo::index_dictionaries(&con)
+        },
+    },
+];
+
+/// The recorded schema version, 0 if there is none
+pub fn stored_version(database: &Database) -> mongodb::error::Result<u32> {
+    let stored = database
+        .collection::<SchemaVersion>("meta")
+        .find_one(doc! { "kind": SCHEMA_KIND }, None)?;
+    Ok(stored.map_or(0, |s| s.version))
+}
+
+fn record(database: &Database, version: u32) -> mongodb::error::Result<()> {
+    let record = SchemaVersion {
+        kind: SCHEMA_KIND.into(),
+        version,
+        updated: DateTime::now().try_to_rfc3339_string().unwrap_or_default(),
+    };
+    database.collection::<SchemaVersion>("meta").replace_one(
+        doc! { "kind": SCHEMA_KIND },
+        record,
+        ReplaceOptions::builder().upsert(true).build(),
+    )?;
+    Ok(())
+}
+
+/// The migrations still to run from a stored version
+fn pending(stored: u32) -> impl Iterator<Item = &'static Migration> {
+    MIGRATIONS.iter().filter(move |m| m.version > stored)
+}
+
+/// Run every pending migration, recording the version after each so an
+/// interrupted run continues where it stopped
+pub fn migrate(database: &Database) -> mongodb::error::Result<()> {
+    let stored = stored_version(database)?;
+    assert!(
+        stored <= SCHEMA_VERSION,
+        "schema version {} is newer than {}, update populate",
+        stored,
+        SCHEMA_VERSION
+    );
+
+    for m in pending(stored) {
+        let _span = tracing::info_span!("migrate", version = m.version).entered();
+        println!("migrating to {}: {}", m.version, m.description);
+        (m.run)(database)?;
+        record(database, m.version)?;
+    }
+    println!("schema version {}", SCHEMA_VERSION);
+    Ok(())
+}
+
+/// Make sure a database is at the current schema before writing new
+/// documents into it. An empty database is simply recorded as current.
+pub fn check(database: &Database) -> mongodb::error::Result<()> {
+    if database.list_collection_names(None)?.is_empty() {
+        return record(database, SCHEMA_VERSION);
+    }
+    let stored = stored_version(database)?;
+    assert!(
+        stored == SCHEMA_VERSION,
+        "schema version {} does not match {}, run `populate migrate` first",
+        stored,
+        SCHEMA_VERSION
+    );
+    Ok(())
+}
+
+#[test]
+fn test_migrations() {
+    for (i, m) in MIGRATIONS.iter().enumerate() {
+        assert_eq!(m.version, i as u32 + 1);
+    }
+    assert_eq!(MIGRATIONS.last().map(|m| m.version), Some(SCHEMA_VERSION));
+
+    assert_eq!(pending(0).count(), MIGRATIONS.len());
+    assert_eq!(pending(SCHEMA_VERSION).count(), 0);
+}
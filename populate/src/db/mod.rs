@@ -1,3 +1,52 @@
+use parse::{
+    dtd::{self, SourceResolver},
+    source::Dir,
+    DataSource,
+};
+
+pub mod anki;
+pub mod bin;
+pub mod columns;
+pub mod csv;
+pub mod jmdict;
+pub mod jmnedict;
 pub mod json;
 pub mod kanji;
+pub mod kanjidic2_json;
+pub mod migrations;
 pub mod mongo;
+pub mod shards;
+pub mod similar;
+pub mod sink;
+pub mod words;
+
+/// Read a text file from the data source, panicking with its name on failure
+pub fn read(data: &dyn DataSource, name: &str) -> String {
+    let _span = tracing::info_span!("read", file = name).entered();
+    data.read_to_string(name)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", name, e))
+}
+
+/// Read an XML file from the data source in whatever encoding it declares,
+/// with an external DTD it
+/// references loaded from the data source too when present, and the
+/// parameter entities of the DTD expanded
+pub fn read_xml(data: &dyn DataSource, name: &str) -> String {
+    let text = {
+        let _span = tracing::info_span!("read", file = name).entered();
+        data.read_xml(name)
+            .unwrap_or_else(|e| panic!("failed to read {}: {}", name, e))
+    };
+    let text = dtd::inline_external(&text, &SourceResolver(data))
+        .unwrap_or_else(|e| panic!("failed to read the DTD of {}: {}", name, e));
+    dtd::expand_parameter_entities(&text)
+        .unwrap_or_else(|e| panic!("failed to expand the DTD of {}: {}", name, e))
+        .into_owned()
+}
+
+/// Record the dataset version of a written dump next to it
+pub fn write_version(data: &Dir, name: &str, version: &str) {
+    let file = format!("{}.version", name);
+    data.write(&file, version.as_bytes())
+        .unwrap_or_else(|e| panic!("failed to write {}: {}", file, e));
+}
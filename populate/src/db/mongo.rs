@@ -1,50 +1,271 @@
-use core::panic;
+use std::collections::HashMap;
 
-use backend::data::kanji::Kanji;
+use model::{
+    changelog::Change,
+    entry::Entry,
+    example::{Example, ExampleWord},
+    gloss::{self, Gloss},
+    kana,
+    kanji::Kanji,
+    krad::Decomposition,
+    meta::{KanjidicMeta, KANJIDIC_KIND},
+    name::Name,
+    namespace,
+    provenance::Provenance,
+    strokes::{Stroke, Strokes},
+};
 use mongodb::{
-    bson::doc,
-    sync::{Client, Collection},
+    bson::{doc, to_bson, DateTime, Document},
+    options::{FindOptions, IndexOptions, ReplaceOptions},
+    sync::{Client, Collection, Database},
     IndexModel,
 };
-use parse::util;
+use parse::{kanjivg, tanaka, DataSource, PROGRESS_INTERVAL};
+use serde::Serialize;
 
-use super::kanji::convert;
+use super::{
+    kanji::{load_kanjidic, Converted, Duplicates},
+    migrations, similar, words,
+};
 
-fn connect() -> mongodb::error::Result<Collection<Kanji>> {
+pub fn connect() -> mongodb::error::Result<Database> {
     let url = std::env::var("MONGODB_URL").expect("MONGODB_URL not set");
     let client = Client::with_uri_str(url)?;
 
-    let database = client.database("kanjisho");
-    Ok(database.collection::<Kanji>("kanjidic"))
+    // write into a separate dataset, e.g. a staging snapshot
+    let namespace = std::env::var("NAMESPACE").ok().filter(|ns| !ns.is_empty());
+    if let Some(ns) = &namespace {
+        assert!(namespace::is_valid(ns), "invalid namespace {:?}", ns);
+    }
+
+    Ok(client.database(&namespace::database_name(namespace.as_deref())))
 }
 
-pub fn update_kanjidic() -> mongodb::error::Result<()> {
-    let con = connect()?;
-    // hard reset
-    con.drop(None)?;
+/// How [`update_kanjidic`] writes the new data
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Drop the collections and insert every entry again
+    Reset,
+    /// Only write entries whose content hash changed
+    Incremental,
+}
 
-    let klc = util::index_mapping(&parse::read_file("klc.txt")).expect("something");
+/// The KRADFILEs in the data directory, of JIS X 0208 and JIS X 0212
+const KRAD_FILES: [&str; 2] = ["kradfile", "kradfile2"];
 
-    let jlpt = util::grade_mapping(&[
-        &parse::read_file("n1.txt"),
-        &parse::read_file("n2.txt"),
-        &parse::read_file("n3.txt"),
-        &parse::read_file("n4.txt"),
-        &parse::read_file("n5.txt"),
-    ])
-    .expect("grade mapping");
+/// The RADKFILEs listing the same radicals the other way round
+const RADK_FILES: [&str; 2] = ["radkfile", "radkfile2"];
 
-    let text = parse::read_file("kanjidic2.xml");
+/// The number of documents sent per insert_many
+const BATCH_SIZE: usize = 1000;
 
-    for k in parse::kanjidic::parse(&text)
-        .entries()
-        .map(|k| convert(&k, &jlpt, &klc))
-    {
-        match k {
-            Ok(k) => con.insert_one(k, None)?,
-            Err(e) => panic!("{:?}", e),
+/// What an update did to the entries
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    /// Literals with more than one entry in the file
+    pub duplicates: usize,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} added, {} updated, {} unchanged, {} removed",
+            self.added, self.updated, self.unchanged, self.removed
+        )?;
+        if self.duplicates > 0 {
+            write!(f, ", {} duplicated", self.duplicates)?;
+        }
+        Ok(())
+    }
+}
+
+/// How an entry compares to the stored one with the same literal
+#[derive(Debug, PartialEq)]
+enum Status {
+    Added,
+    Updated,
+    Unchanged,
+}
+
+impl Summary {
+    fn count(&mut self, status: &Status) {
+        match status {
+            Status::Added => self.added += 1,
+            Status::Updated => self.updated += 1,
+            Status::Unchanged => self.unchanged += 1,
+        }
+    }
+}
+
+/// Compare an entry against the stored hashes, removing its literal so
+/// only removed entries are left afterwards. Entries stored before hashes
+/// were recorded have none and always count as updated.
+fn compare(previous: &mut HashMap<char, Option<String>>, literal: char, hash: &str) -> Status {
+    match previous.remove(&literal) {
+        None => Status::Added,
+        Some(Some(h)) if h == hash => Status::Unchanged,
+        Some(_) => Status::Updated,
+    }
+}
+
+/// A hash of the stored form of a document, to tell whether it changed
+fn content_hash<T: Serialize>(value: &T) -> mongodb::error::Result<String> {
+    Ok(model::hash::fnv1a(&mongodb::bson::to_vec(value)?))
+}
+
+/// The document to store for a value, with its content hash
+fn with_hash<T: Serialize>(value: &T, hash: &str) -> mongodb::error::Result<Document> {
+    let mut doc = mongodb::bson::to_document(value)?;
+    doc.insert("hash", hash);
+    Ok(doc)
+}
+
+/// The content hash of every stored document by literal
+fn stored_hashes(
+    con: &Collection<Document>,
+) -> mongodb::error::Result<HashMap<char, Option<String>>> {
+    let options = FindOptions::builder()
+        .projection(doc! { "_id": 0, "literal": 1, "hash": 1 })
+        .build();
+    let mut out = HashMap::new();
+    for d in con.find(None, options)? {
+        let d = d?;
+        if let Some(literal) = d.get_str("literal").ok().and_then(|l| l.chars().next()) {
+            out.insert(literal, d.get_str("hash").ok().map(String::from));
+        }
+    }
+    Ok(out)
+}
+
+fn insert_batched<T: Serialize>(con: &Collection<T>, docs: Vec<T>) -> mongodb::error::Result<()> {
+    for (i, batch) in docs.chunks(BATCH_SIZE).enumerate() {
+        let _span = tracing::info_span!(
+            "insert",
+            collection = con.name(),
+            from = i * BATCH_SIZE,
+            of = docs.len()
+        )
+        .entered();
+        con.insert_many(batch, None)?;
+    }
+    Ok(())
+}
+
+/// Replace the raw source records of a dictionary, which the backend
+/// returns alongside entries for debugging
+fn replace_raw(
+    database: &Database,
+    name: &str,
+    key: &str,
+    docs: Vec<Document>,
+) -> mongodb::error::Result<()> {
+    let _span = tracing::info_span!("replace_raw", name).entered();
+    let con = database.collection::<Document>(name);
+    con.drop(None)?;
+    insert_batched(&con, docs)?;
+    let m = IndexModel::builder().keys(doc! { key: 1 }).build();
+    con.create_index(m, None)?;
+    Ok(())
+}
+
+/// Index the literal uniquely, so an entry can never be inserted twice.
+/// Replaces the plain index created before it was unique.
+pub(super) fn unique_literal<T>(con: &Collection<T>) -> mongodb::error::Result<()> {
+    let m = IndexModel::builder()
+        .keys(doc! { "literal": 1 })
+        .options(IndexOptions::builder().unique(true).build())
+        .build();
+    if con.create_index(m.clone(), None).is_err() {
+        con.drop_index("literal_1", None)?;
+        con.create_index(m, None)?;
+    }
+    Ok(())
+}
+
+/// Index the number of the kanji in each dictionary found in their
+/// `dict_index`, so a dictionary is listed with an indexed sort
+pub(super) fn index_dictionaries<T>(con: &Collection<T>) -> mongodb::error::Result<()> {
+    let pipeline = [
+        doc! { "$project": { "dict": { "$objectToArray": "$dict_index" } } },
+        doc! { "$unwind": "$dict" },
+        doc! { "$group": { "_id": "$dict.k" } },
+    ];
+    for dict in con.aggregate(pipeline, None)? {
+        let dict = dict?;
+        let Ok(dict) = dict.get_str("_id") else {
+            continue;
         };
+        let m = IndexModel::builder()
+            .keys(doc! { format!("dict_index.{}", dict): 1, "literal": 1 })
+            .build();
+        con.create_index(m, None)?;
     }
+    Ok(())
+}
+
+/// Update the kanjidic collections. With `raw`, the entries as parsed are
+/// also stored for debugging.
+pub fn update_kanjidic(
+    data: &dyn DataSource,
+    mode: Mode,
+    duplicates: Duplicates,
+    raw: bool,
+) -> mongodb::error::Result<()> {
+    let _span = tracing::info_span!("update_kanjidic", ?mode).entered();
+    let database = connect()?;
+    migrations::check(&database)?;
+    let con = database.collection::<Kanji>("kanjidic");
+    let provenance = database.collection::<Provenance>("provenance");
+
+    let mut converted = load_kanjidic(data, duplicates).unwrap_or_else(|e| panic!("{}", e));
+    let parsed = std::mem::take(&mut converted.parsed);
+    if raw {
+        let docs = parsed
+            .iter()
+            .map(|k| Ok(doc! { "literal": k.literal.to_string(), "raw": to_bson(k)? }))
+            .collect::<mongodb::error::Result<_>>()?;
+        replace_raw(&database, "kanjidic_raw", "literal", docs)?;
+    }
+    let mut change = Change {
+        version: converted.version.clone(),
+        changed: vec![],
+        removed: vec![],
+    };
+
+    let duplicates = converted.duplicates.len();
+    let header = std::mem::take(&mut converted.header);
+    let mut summary = match mode {
+        Mode::Reset => reset_kanjidic(&database, converted, &mut change)?,
+        Mode::Incremental => diff_kanjidic(&database, converted, &mut change)?,
+    };
+    summary.duplicates = duplicates;
+    println!("kanjidic: {}", summary);
+
+    change.removed.sort();
+    if !change.changed.is_empty() || !change.removed.is_empty() {
+        database
+            .collection::<Change>("changelog")
+            .insert_one(change, None)?;
+    }
+
+    let meta = KanjidicMeta {
+        kind: KANJIDIC_KIND.into(),
+        file_version: header.file_version,
+        database_version: header.database_version,
+        date_of_creation: header.date_of_creation,
+        entries: (summary.added + summary.updated + summary.unchanged) as u64,
+        populated: DateTime::now().try_to_rfc3339_string().unwrap_or_default(),
+    };
+    database.collection::<KanjidicMeta>("meta").replace_one(
+        doc! { "kind": KANJIDIC_KIND },
+        meta,
+        ReplaceOptions::builder().upsert(true).build(),
+    )?;
 
     let m = IndexModel::builder()
         .keys(doc! {
@@ -54,9 +275,11 @@ pub fn update_kanjidic() -> mongodb::error::Result<()> {
     // TODO I should create indexes
     con.create_index(m, None)?;
 
+    unique_literal(&con)?;
+
     let m = IndexModel::builder()
         .keys(doc! {
-            "literal": 1
+            "references": 1
         })
         .build();
     // TODO I should create indexes
@@ -64,11 +287,469 @@ pub fn update_kanjidic() -> mongodb::error::Result<()> {
 
     let m = IndexModel::builder()
         .keys(doc! {
-            "references": 1
+            "variants": 1
+        })
+        .build();
+    con.create_index(m, None)?;
+
+    index_dictionaries(&con)?;
+
+    let m = IndexModel::builder()
+        .keys(doc! {
+            "query.qc_type": 1,
+            "query.code": 1
         })
         .build();
-    // TODO I should create indexes
     con.create_index(m, None)?;
 
+    unique_literal(&provenance)?;
+
     Ok(())
 }
+
+/// Drop the collections and insert every entry again
+fn reset_kanjidic(
+    database: &Database,
+    converted: Converted,
+    change: &mut Change,
+) -> mongodb::error::Result<Summary> {
+    let con = database.collection::<Document>("kanjidic");
+    let provenance = database.collection::<Document>("provenance");
+
+    // keep the previous entries around to record what changed
+    let mut previous = database
+        .collection::<Kanji>("kanjidic")
+        .find(None, None)?
+        .map(|k| k.map(|k| (k.literal, k)))
+        .collect::<mongodb::error::Result<HashMap<_, _>>>()?;
+
+    let mut summary = Summary::default();
+    let mut kanji = vec![];
+    let mut provenances = vec![];
+    for (mut k, p) in converted.entries.into_iter().zip(converted.provenance) {
+        let hash = content_hash(&k)?;
+        let old = previous.remove(&k.literal);
+        // top words come from jmdict and similar kanji from their own
+        // step, keep them until those are populated again
+        if let Some(old) = &old {
+            k.top_words = old.top_words.clone();
+            k.similar = old.similar.clone();
+        }
+        match &old {
+            None => summary.added += 1,
+            Some(old) if *old == k => summary.unchanged += 1,
+            Some(_) => summary.updated += 1,
+        }
+        if old.as_ref() != Some(&k) {
+            change.changed.push(k.literal);
+        }
+        kanji.push(with_hash(&k, &hash)?);
+        provenances.push(with_hash(&p, &content_hash(&p)?)?);
+    }
+    change.removed = previous.into_keys().collect();
+    summary.removed = change.removed.len();
+
+    // hard reset
+    con.drop(None)?;
+    provenance.drop(None)?;
+    insert_batched(&con, kanji)?;
+    insert_batched(&provenance, provenances)?;
+
+    Ok(summary)
+}
+
+/// Write only the entries whose content hash changed, and delete the
+/// removed ones
+fn diff_kanjidic(
+    database: &Database,
+    converted: Converted,
+    change: &mut Change,
+) -> mongodb::error::Result<Summary> {
+    let con = database.collection::<Document>("kanjidic");
+    let provenance = database.collection::<Document>("provenance");
+    let mut previous = stored_hashes(&con)?;
+    let mut previous_provenance = stored_hashes(&provenance)?;
+
+    let mut summary = Summary::default();
+    let mut added = vec![];
+    let mut added_provenance = vec![];
+    let total = converted.entries.len();
+    let entries = converted.entries.into_iter().zip(converted.provenance);
+    for (i, (mut k, p)) in entries.enumerate() {
+        if (i + 1) % PROGRESS_INTERVAL == 0 {
+            tracing::info!(compared = i + 1, of = total, "kanjidic");
+        }
+        let filter = doc! { "literal": k.literal.to_string() };
+
+        let hash = content_hash(&k)?;
+        let status = compare(&mut previous, k.literal, &hash);
+        summary.count(&status);
+        match status {
+            Status::Added => added.push(with_hash(&k, &hash)?),
+            Status::Updated => {
+                // top words and similar kanji are populated separately
+                // and are not part of the hash
+                let old = database
+                    .collection::<Kanji>("kanjidic")
+                    .find_one(filter.clone(), None)?;
+                if let Some(old) = old {
+                    k.top_words = old.top_words;
+                    k.similar = old.similar;
+                }
+                con.replace_one(filter.clone(), with_hash(&k, &hash)?, None)?;
+            }
+            Status::Unchanged => (),
+        }
+        if status != Status::Unchanged {
+            change.changed.push(k.literal);
+        }
+
+        // provenance can change on its own, e.g. with a new source version
+        let hash = content_hash(&p)?;
+        match compare(&mut previous_provenance, p.literal, &hash) {
+            Status::Added => added_provenance.push(with_hash(&p, &hash)?),
+            Status::Updated => {
+                provenance.replace_one(filter, with_hash(&p, &hash)?, None)?;
+            }
+            Status::Unchanged => (),
+        }
+    }
+    insert_batched(&con, added)?;
+    insert_batched(&provenance, added_provenance)?;
+
+    change.removed = previous.into_keys().collect();
+    summary.removed = change.removed.len();
+    let removed: Vec<String> = change.removed.iter().map(|c| c.to_string()).collect();
+    con.delete_many(doc! { "literal": { "$in": removed } }, None)?;
+    let removed_provenance: Vec<String> = previous_provenance
+        .into_keys()
+        .map(|c| c.to_string())
+        .collect();
+    provenance.delete_many(doc! { "literal": { "$in": removed_provenance } }, None)?;
+
+    Ok(summary)
+}
+
+/// Update the jmdict collections. With `raw`, the entries as parsed are
+/// also stored for debugging.
+pub fn update_jmdict(data: &dyn DataSource, raw: bool) -> mongodb::error::Result<()> {
+    let _span = tracing::info_span!("update_jmdict").entered();
+    let database = connect()?;
+    migrations::check(&database)?;
+    let text = super::read_xml(data, "JMdict_e.xml");
+    let parsed: Vec<_> = parse::jmdict::parse(&text).entries().collect();
+    if raw {
+        let docs = parsed
+            .iter()
+            .map(|e| Ok(doc! { "ent_seq": e.ent_seq, "raw": to_bson(e)? }))
+            .collect::<mongodb::error::Result<_>>()?;
+        replace_raw(&database, "jmdict_raw", "ent_seq", docs)?;
+    }
+    let mut entries: Vec<Entry> = {
+        let _span = tracing::info_span!("convert").entered();
+        parsed.into_iter().map(super::jmdict::convert).collect()
+    };
+
+    let size = |entries: &[Entry]| -> usize {
+        entries
+            .iter()
+            .map(|e| {
+                mongodb::bson::to_vec(e)
+                    .expect("failed to encode entry")
+                    .len()
+            })
+            .sum()
+    };
+    let top_words =
+        tracing::info_span!("top_words").in_scope(|| words::top_words(&entries, words::TOP_WORDS));
+
+    let before = size(&entries);
+    let glosses = tracing::info_span!("intern").in_scope(|| gloss::intern(&mut entries));
+    let after = size(&entries);
+    let table: usize = glosses
+        .iter()
+        .map(|g| {
+            mongodb::bson::to_vec(g)
+                .expect("failed to encode gloss")
+                .len()
+        })
+        .sum();
+    println!(
+        "jmdict: {} entries, {} distinct glosses; {} bytes before interning, {} + {} after ({:.1}% smaller)",
+        entries.len(),
+        glosses.len(),
+        before,
+        after,
+        table,
+        100.0 * (1.0 - (after + table) as f64 / before as f64),
+    );
+
+    let con = database.collection::<Entry>("jmdict");
+    let glosses_con = database.collection::<Gloss>("glosses");
+    // hard reset
+    con.drop(None)?;
+    glosses_con.drop(None)?;
+
+    insert_batched(&con, entries)?;
+    insert_batched(&glosses_con, glosses)?;
+
+    for key in [
+        "ent_seq",
+        "kanji.text",
+        "readings.text",
+        "senses.gloss_keys",
+        "script",
+    ] {
+        let m = IndexModel::builder().keys(doc! { key: 1 }).build();
+        con.create_index(m, None)?;
+    }
+
+    let m = IndexModel::builder()
+        .keys(doc! {
+            "key": 1
+        })
+        .build();
+    glosses_con.create_index(m, None)?;
+
+    // precompute the common words of every kanji so lookups stay a
+    // single document read
+    let _span = tracing::info_span!("set_top_words", kanji = top_words.len()).entered();
+    let kanjidic = database.collection::<Kanji>("kanjidic");
+    kanjidic.update_many(doc! {}, doc! { "$unset": { "top_words": "" } }, None)?;
+    for (literal, words) in top_words {
+        kanjidic.update_one(
+            doc! { "literal": literal.to_string() },
+            doc! { "$set": { "top_words": mongodb::bson::to_bson(&words)? } },
+            None,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Read the KRADFILEs and RADKFILEs from the data source, checked
+/// against each other
+fn read_radicals(data: &dyn DataSource) -> kradk::Model {
+    let read = |file: &str| {
+        let input = data
+            .read(file)
+            .unwrap_or_else(|e| panic!("failed to read {}: {}", file, e));
+        kradk::decode(&input).unwrap_or_else(|e| panic!("failed to decode {}: {:?}", file, e))
+    };
+    let krad: Vec<String> = KRAD_FILES.iter().map(|f| read(f)).collect();
+    let radk: Vec<String> = RADK_FILES.iter().map(|f| read(f)).collect();
+    let krad: Vec<&str> = krad.iter().map(String::as_str).collect();
+    let radk: Vec<&str> = radk.iter().map(String::as_str).collect();
+    kradk::Model::parse(&krad, &radk).expect("failed to parse the radical files")
+}
+
+pub fn update_krad(data: &dyn DataSource) -> mongodb::error::Result<()> {
+    let database = connect()?;
+    migrations::check(&database)?;
+    let con = database.collection::<Decomposition>("krad");
+    // hard reset
+    con.drop(None)?;
+
+    let _span = tracing::info_span!("update_krad").entered();
+    let decompositions = read_radicals(data)
+        .decompositions()
+        .map(|(kanji, radicals)| Decomposition {
+            kanji,
+            radicals: radicals.to_vec(),
+        })
+        .collect::<Vec<_>>();
+    con.insert_many(decompositions, None)?;
+
+    let m = IndexModel::builder()
+        .keys(doc! {
+            "radicals": 1
+        })
+        .build();
+    con.create_index(m, None)?;
+
+    Ok(())
+}
+
+/// Replace the proper names with those of JMnedict.xml
+pub fn update_names(data: &dyn DataSource) -> mongodb::error::Result<()> {
+    let _span = tracing::info_span!("update_names").entered();
+    let database = connect()?;
+    migrations::check(&database)?;
+    let con = database.collection::<Name>("names");
+
+    let text = super::read_xml(data, "JMnedict.xml");
+    let names: Vec<Name> = parse::jmnedict::parse(&text)
+        .entries()
+        .map(super::jmnedict::convert)
+        .collect();
+
+    // hard reset
+    con.drop(None)?;
+    insert_batched(&con, names)?;
+
+    let m = IndexModel::builder()
+        .keys(doc! { "ent_seq": 1 })
+        .options(IndexOptions::builder().unique(true).build())
+        .build();
+    con.create_index(m, None)?;
+    for key in ["kanji.text", "readings.text", "translations.details"] {
+        let m = IndexModel::builder().keys(doc! { key: 1 }).build();
+        con.create_index(m, None)?;
+    }
+
+    Ok(())
+}
+
+/// Replace the stroke order diagrams with those of kanjivg.xml
+pub fn update_strokes(data: &dyn DataSource) -> mongodb::error::Result<()> {
+    let _span = tracing::info_span!("update_strokes").entered();
+    let database = connect()?;
+    migrations::check(&database)?;
+    let con = database.collection::<Strokes>("strokes");
+
+    let text = super::read_xml(data, "kanjivg.xml");
+    let strokes: Vec<Strokes> = kanjivg::parse(&text)
+        .unwrap_or_else(|e| panic!("failed to parse kanjivg.xml: {:?}", e))
+        .into_iter()
+        .map(|k| Strokes {
+            literal: k.literal,
+            strokes: k
+                .strokes
+                .into_iter()
+                .map(|s| Stroke {
+                    kind: s.kind,
+                    path: s.path,
+                })
+                .collect(),
+        })
+        .collect();
+
+    // hard reset
+    con.drop(None)?;
+    insert_batched(&con, strokes)?;
+    unique_literal(&con)?;
+
+    Ok(())
+}
+
+/// Convert an example sentence, finding the kanji it uses
+fn example(e: tanaka::Example) -> Example {
+    let mut kanji: Vec<char> = vec![];
+    for c in e.japanese.chars().filter(|c| kana::is_kanji(*c)) {
+        if !kanji.contains(&c) {
+            kanji.push(c);
+        }
+    }
+    Example {
+        id: e.id,
+        japanese: e.japanese,
+        english: e.english,
+        kanji,
+        words: e
+            .words
+            .into_iter()
+            .map(|w| ExampleWord {
+                headword: w.headword,
+                reading: w.reading,
+                sense: w.sense,
+                form: w.form,
+                checked: w.checked,
+            })
+            .collect(),
+    }
+}
+
+/// Replace the example sentences with those of the Tanaka Corpus in
+/// examples.utf
+pub fn update_examples(data: &dyn DataSource) -> mongodb::error::Result<()> {
+    let _span = tracing::info_span!("update_examples").entered();
+    let database = connect()?;
+    migrations::check(&database)?;
+    let con = database.collection::<Example>("examples");
+
+    let text = super::read(data, "examples.utf");
+    let examples: Vec<Example> = tanaka::parse(&text)
+        .unwrap_or_else(|e| panic!("failed to parse examples.utf: {:?}", e))
+        .into_iter()
+        .map(example)
+        .collect();
+
+    // hard reset
+    con.drop(None)?;
+    insert_batched(&con, examples)?;
+    for key in ["kanji", "words.headword"] {
+        let m = IndexModel::builder().keys(doc! { key: 1 }).build();
+        con.create_index(m, None)?;
+    }
+
+    Ok(())
+}
+
+/// Find the kanji which look alike from the stored kanjidic entries and
+/// the radicals of the KRADFILEs, and store them with each kanji
+pub fn update_similar(data: &dyn DataSource) -> mongodb::error::Result<()> {
+    let _span = tracing::info_span!("update_similar").entered();
+    let database = connect()?;
+    migrations::check(&database)?;
+    let kanjidic = database.collection::<Kanji>("kanjidic");
+
+    let radicals: HashMap<char, Vec<char>> = read_radicals(data)
+        .decompositions()
+        .map(|(kanji, radicals)| (kanji, radicals.to_vec()))
+        .collect();
+    let kanji = kanjidic
+        .find(None, None)?
+        .collect::<mongodb::error::Result<Vec<_>>>()?;
+    let similar = tracing::info_span!("similar", kanji = kanji.len())
+        .in_scope(|| similar::similar(&kanji, &radicals, similar::SIMILAR));
+
+    kanjidic.update_many(doc! {}, doc! { "$unset": { "similar": "" } }, None)?;
+    for (literal, similar) in similar {
+        let similar: Vec<String> = similar.iter().map(|c| c.to_string()).collect();
+        kanjidic.update_one(
+            doc! { "literal": literal.to_string() },
+            doc! { "$set": { "similar": similar } },
+            None,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_compare() {
+    let mut previous = HashMap::from([
+        ('一', Some("a".to_owned())),
+        ('二', Some("b".to_owned())),
+        ('三', None),
+        ('四', Some("d".to_owned())),
+    ]);
+    let mut summary = Summary::default();
+    for (literal, hash, expected) in [
+        ('一', "a", Status::Unchanged),
+        ('二', "x", Status::Updated),
+        ('三', "c", Status::Updated),
+        ('五', "e", Status::Added),
+    ] {
+        let s = compare(&mut previous, literal, hash);
+        assert_eq!(s, expected, "{}", literal);
+        summary.count(&s);
+    }
+    assert_eq!(previous.into_keys().collect::<Vec<_>>(), ['四']);
+    assert_eq!(
+        summary.to_string(),
+        "1 added, 2 updated, 1 unchanged, 0 removed"
+    );
+    summary.duplicates = 2;
+    assert!(summary.to_string().ends_with("0 removed, 2 duplicated"));
+
+    let hash = |version: &str| {
+        content_hash(&model::provenance::Source {
+            name: "kanjidic2".into(),
+            version: Some(version.into()),
+        })
+        .unwrap()
+    };
+    assert_eq!(hash("2022-01"), hash("2022-01"));
+    assert_ne!(hash("2022-01"), hash("2022-02"));
+}
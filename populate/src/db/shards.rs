@@ -0,0 +1,189 @@
+//! An export of kanjidic split into shards for clients loading the data
+//! lazily. The shards are concatenated into one bundle so each can be
+//! fetched with a Range request, and a manifest gives their byte ranges.
+
+use std::collections::BTreeMap;
+
+use model::{kanji::Kanji, provenance::Provenance};
+use parse::source::Dir;
+use serde::Serialize;
+
+use super::sink::{Index, Sink, READINGS};
+
+/// The bundle of every shard
+const BUNDLE: &str = "kanjidic.shards";
+/// The manifest describing the bundle
+const MANIFEST: &str = "kanjidic.shards.json";
+
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    /// The KANJIDIC database version
+    pub version: String,
+    /// The file the ranges refer to
+    pub bundle: String,
+    pub shards: Vec<Shard>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Shard {
+    /// What the shard is keyed by, grade or reading
+    pub kind: &'static str,
+    /// The grade, or the first kana of the readings in hiragana. Entries
+    /// without a grade are under "none".
+    pub key: String,
+    /// The byte range of the shard in the bundle
+    pub offset: usize,
+    pub length: usize,
+    /// The number of entries, or of readings in a reading shard
+    pub count: usize,
+}
+
+/// Entries grouped by grade, in literal order
+fn by_grade(entries: &[Kanji]) -> BTreeMap<String, Vec<&Kanji>> {
+    let mut out: BTreeMap<String, Vec<&Kanji>> = BTreeMap::new();
+    for k in entries {
+        let key = k.info.grade.map_or("none".into(), |g| g.to_string());
+        out.entry(key).or_default().push(k);
+    }
+    out
+}
+
+/// The reading index grouped by the first kana of the reading
+fn by_reading(readings: &Index) -> BTreeMap<String, BTreeMap<&str, &[char]>> {
+    let mut out: BTreeMap<String, BTreeMap<&str, &[char]>> = BTreeMap::new();
+    for (reading, literals) in readings {
+        if let Some(first) = reading.chars().next() {
+            out.entry(first.to_string())
+                .or_default()
+                .insert(reading, literals);
+        }
+    }
+    out
+}
+
+/// Encode every shard into one bundle, returning it with the manifest
+fn bundle(
+    version: String,
+    entries: &[Kanji],
+    readings: &Index,
+) -> serde_json::Result<(Vec<u8>, Manifest)> {
+    let mut out = vec![];
+    let mut shards = vec![];
+    let mut push = |kind, key: String, count, json: Vec<u8>| {
+        shards.push(Shard {
+            kind,
+            key,
+            offset: out.len(),
+            length: json.len(),
+            count,
+        });
+        out.extend(json);
+    };
+
+    for (key, group) in by_grade(entries) {
+        push("grade", key, group.len(), serde_json::to_vec(&group)?);
+    }
+    for (key, group) in by_reading(readings) {
+        push("reading", key, group.len(), serde_json::to_vec(&group)?);
+    }
+
+    let manifest = Manifest {
+        version,
+        bundle: BUNDLE.into(),
+        shards,
+    };
+    Ok((out, manifest))
+}
+
+/// The shard bundle and its manifest
+#[derive(Default)]
+pub struct ShardSink {
+    entries: Vec<Kanji>,
+    readings: Index,
+}
+
+impl Sink for ShardSink {
+    fn put_kanji(&mut self, kanji: &Kanji, _provenance: &Provenance) {
+        self.entries.push(kanji.clone());
+    }
+
+    fn put_index(&mut self, name: &str, index: &Index) {
+        if name == READINGS {
+            self.readings = index.clone();
+        }
+    }
+
+    fn finalize(&mut self, data: &Dir, version: &str) {
+        let (out, manifest) =
+            bundle(version.into(), &self.entries, &self.readings).expect("failed to encode shards");
+        println!(
+            "shards: {} shards, {} bytes",
+            manifest.shards.len(),
+            out.len()
+        );
+        data.write(BUNDLE, &out)
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", BUNDLE, e));
+        data.write(MANIFEST, &serde_json::to_vec(&manifest).unwrap())
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", MANIFEST, e));
+
+        for name in [BUNDLE, MANIFEST] {
+            super::write_version(data, name, version);
+        }
+    }
+}
+
+#[cfg(test)]
+use model::kanji::{Info, References};
+
+#[test]
+fn test_bundle() {
+    let kanji = |literal, grade: Option<u32>, on: &[&str], kun: &[&str]| {
+        Kanji::builder(
+            literal,
+            Info::builder(1, 1).grade(grade).build(),
+            References::builder(format!("{:x}", literal as u32)).build(),
+        )
+        .on_readings(on.iter().map(|s| s.to_string()).collect())
+        .kun_readings(kun.iter().map(|s| s.to_string()).collect())
+        .build()
+    };
+    let entries = [
+        kanji('一', Some(1), &["イチ", "イツ"], &["ひと-", "ひと.つ"]),
+        kanji('壱', None, &["イチ"], &[]),
+        kanji('日', Some(1), &["ニチ"], &["ひ", "-び"]),
+    ];
+
+    let readings = super::sink::reading_index(&entries);
+    let (out, manifest) = bundle("2023-01".into(), &entries, &readings).unwrap();
+    let keys: Vec<_> = manifest
+        .shards
+        .iter()
+        .map(|s| (s.kind, s.key.as_str(), s.count))
+        .collect();
+    assert_eq!(
+        keys,
+        [
+            ("grade", "1", 2),
+            ("grade", "none", 1),
+            ("reading", "い", 2),
+            ("reading", "に", 1),
+            ("reading", "ひ", 3),
+            ("reading", "び", 1),
+        ]
+    );
+
+    // every range holds exactly one shard
+    let mut end = 0;
+    for s in &manifest.shards {
+        assert_eq!(s.offset, end);
+        end += s.length;
+        let shard: serde_json::Value = serde_json::from_slice(&out[s.offset..end]).unwrap();
+        assert!(shard.is_array() || shard.is_object());
+    }
+    assert_eq!(end, out.len());
+
+    let s = &manifest.shards[2];
+    let shard: serde_json::Value =
+        serde_json::from_slice(&out[s.offset..s.offset + s.length]).unwrap();
+    assert_eq!(shard["いち"], serde_json::json!(["一", "壱"]));
+}
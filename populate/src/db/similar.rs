@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use model::kanji::Kanji;
+
+/// How many similar kanji are kept per kanji
+pub const SIMILAR: usize = 10;
+
+/// The score a kanji needs to be listed as similar to another
+const MIN_SCORE: i32 = 2;
+
+/// Score how alike two kanji look: three points for each radical they
+/// share less one for each radical only one of them has, two for the
+/// same SKIP pattern with as many strokes in the first part, and one for
+/// equal stroke counts
+fn score(shared: usize, a: &Kanji, a_radicals: usize, b: &Kanji, b_radicals: usize) -> i32 {
+    let skip = |k: &Kanji| k.skip().map(|s| (s.category, s.strokes1));
+    let same_skip = skip(a).is_some() && skip(a) == skip(b);
+    let same_strokes = a.info.stroke_count == b.info.stroke_count;
+
+    let unshared = a_radicals + b_radicals - 2 * shared;
+    3 * shared as i32 - unshared as i32 + 2 * same_skip as i32 + same_strokes as i32
+}
+
+/// The kanji most alike each kanji, at most `limit` each and most alike
+/// first. Candidates are the kanji sharing a radical or the start of the
+/// SKIP code; `radicals` are the decompositions of the KRADFILEs.
+pub fn similar(
+    kanji: &[Kanji],
+    radicals: &HashMap<char, Vec<char>>,
+    limit: usize,
+) -> HashMap<char, Vec<char>> {
+    let no_radicals = vec![];
+    let radicals_of = |k: &Kanji| radicals.get(&k.literal).unwrap_or(&no_radicals);
+
+    let mut by_radical = HashMap::<char, Vec<usize>>::new();
+    let mut by_skip = HashMap::<(u8, u8), Vec<usize>>::new();
+    for (i, k) in kanji.iter().enumerate() {
+        for r in radicals_of(k) {
+            by_radical.entry(*r).or_default().push(i);
+        }
+        if let Some(s) = k.skip() {
+            by_skip.entry((s.category, s.strokes1)).or_default().push(i);
+        }
+    }
+
+    let mut out = HashMap::new();
+    for (i, k) in kanji.iter().enumerate() {
+        // the number of radicals shared with each candidate
+        let mut shared = HashMap::<usize, usize>::new();
+        for r in radicals_of(k) {
+            for j in &by_radical[r] {
+                *shared.entry(*j).or_default() += 1;
+            }
+        }
+        if let Some(s) = k.skip() {
+            for j in &by_skip[&(s.category, s.strokes1)] {
+                shared.entry(*j).or_default();
+            }
+        }
+        shared.remove(&i);
+
+        let mut scored: Vec<_> = shared
+            .into_iter()
+            .map(|(j, n)| {
+                let other = &kanji[j];
+                let score = score(n, k, radicals_of(k).len(), other, radicals_of(other).len());
+                let strokes = k.info.stroke_count.abs_diff(other.info.stroke_count);
+                (score, strokes, other.literal)
+            })
+            .filter(|(score, _, _)| *score >= MIN_SCORE)
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+        scored.truncate(limit);
+
+        if !scored.is_empty() {
+            out.insert(k.literal, scored.into_iter().map(|(_, _, c)| c).collect());
+        }
+    }
+    out
+}
+
+#[test]
+fn test_similar() {
+    use model::kanji::{Info, QueryCode, References};
+
+    let kanji = |literal: char, strokes, skip: &str| {
+        Kanji::builder(
+            literal,
+            Info::builder(1, strokes).build(),
+            References::builder("0").build(),
+        )
+        .query(vec![QueryCode::new("skip", skip)])
+        .build()
+    };
+    let dict = [
+        kanji('未', 5, "4-5-3"),
+        kanji('末', 5, "4-5-1"),
+        kanji('本', 5, "4-5-4"),
+        kanji('木', 4, "4-4-3"),
+        kanji('休', 6, "1-2-4"),
+        kanji('口', 3, "3-3-0"),
+    ];
+    let radicals = HashMap::from([
+        ('未', vec!['一', '木']),
+        ('末', vec!['一', '木']),
+        ('本', vec!['一', '木']),
+        ('木', vec!['木']),
+        ('休', vec!['化', '木']),
+        ('口', vec!['口']),
+    ]);
+
+    let similar = similar(&dict, &radicals, 10);
+    // one shared radical and an extra on each side isn't enough for 休
+    assert_eq!(similar[&'未'], ['末', '本', '木']);
+    assert_eq!(similar[&'木'], ['未', '末', '本', '休']);
+    assert_eq!(similar[&'休'], ['木']);
+    assert!(!similar.contains_key(&'口'));
+}
@@ -0,0 +1,87 @@
+//! The file targets kanjidic is written to. Kanjidic is converted once
+//! and every entry handed to each target, so adding a target only means
+//! saying what it does with them.
+
+use std::collections::BTreeMap;
+
+use model::{kana, kanji::Kanji, provenance::Provenance};
+use parse::source::Dir;
+
+use super::{
+    anki::AnkiSink,
+    bin::StoreSink,
+    columns::Column,
+    csv::CsvSink,
+    json::DumpSink,
+    kanji::{load_kanjidic, Duplicates},
+    shards::ShardSink,
+};
+
+/// Literals by a lookup key, built once over every entry
+pub type Index = BTreeMap<String, Vec<char>>;
+
+/// The name of the index of literals by reading, see [`reading_key`]
+pub const READINGS: &str = "readings";
+
+/// A target the converted entries are written to
+pub trait Sink {
+    /// Take an entry with the provenance of its fields
+    fn put_kanji(&mut self, kanji: &Kanji, provenance: &Provenance);
+
+    /// Take an index built over every entry, which most targets don't use
+    fn put_index(&mut self, _name: &str, _index: &Index) {}
+
+    /// Write out everything taken, for the given KANJIDIC version
+    fn finalize(&mut self, data: &Dir, version: &str);
+}
+
+/// The target with a name as given on the command line. Flat exports
+/// write the given columns, or their own defaults.
+pub fn named(name: &str, columns: Option<&[Column]>) -> Option<Box<dyn Sink>> {
+    match name {
+        "json" => Some(Box::<DumpSink>::default()),
+        "bin" => Some(Box::<StoreSink>::default()),
+        "shards" => Some(Box::<ShardSink>::default()),
+        "anki" => Some(Box::new(AnkiSink::new(columns))),
+        "csv" => Some(Box::new(CsvSink::new(columns))),
+        _ => None,
+    }
+}
+
+/// Normalize a reading for lookup: hiragana, without okurigana separators
+/// or prefix and suffix markers
+pub fn reading_key(reading: &str) -> String {
+    kana::to_hiragana(reading)
+        .chars()
+        .filter(|c| *c != '.' && *c != '-')
+        .collect()
+}
+
+/// The literals of every reading, in entry order
+pub fn reading_index(entries: &[Kanji]) -> Index {
+    let mut out = Index::new();
+    for k in entries {
+        for r in k.on_readings.iter().chain(&k.kun_readings) {
+            let literals = out.entry(reading_key(r)).or_default();
+            if !literals.contains(&k.literal) {
+                literals.push(k.literal);
+            }
+        }
+    }
+    out.remove("");
+    out
+}
+
+/// Convert kanjidic once and write it to every target
+pub fn run(data: &Dir, duplicates: Duplicates, sinks: &mut [Box<dyn Sink>]) {
+    let converted = load_kanjidic(data, duplicates).unwrap_or_else(|e| panic!("{}", e));
+    let readings = reading_index(&converted.entries);
+
+    for sink in sinks {
+        for (k, p) in converted.entries.iter().zip(&converted.provenance) {
+            sink.put_kanji(k, p);
+        }
+        sink.put_index(READINGS, &readings);
+        sink.finalize(data, &converted.version);
+    }
+}
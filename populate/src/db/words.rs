@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use model::{
+    entry::{priority_rank, Entry},
+    kana::is_kanji,
+    kanji::Word,
+};
+
+/// How many words are kept per kanji
+pub const TOP_WORDS: usize = 20;
+
+/// The most common words written with each kanji, at most `limit` each.
+/// Entries must still have their glosses, i.e. not be interned yet.
+pub fn top_words(entries: &[Entry], limit: usize) -> HashMap<char, Vec<Word>> {
+    let mut candidates = HashMap::<char, Vec<(u32, Word)>>::new();
+
+    for e in entries {
+        let gloss = e
+            .senses
+            .iter()
+            .flat_map(|s| &s.glosses)
+            .next()
+            .cloned()
+            .unwrap_or_default();
+
+        let mut seen = vec![];
+        for k in &e.kanji {
+            let Some(rank) = priority_rank(&k.priority) else {
+                continue;
+            };
+            let Some(reading) = e.reading_of(&k.text) else {
+                continue;
+            };
+
+            for c in k.text.chars().filter(|c| is_kanji(*c)) {
+                // only the most common written form of an entry is listed
+                if seen.contains(&c) {
+                    continue;
+                }
+                seen.push(c);
+                candidates.entry(c).or_default().push((
+                    rank,
+                    Word {
+                        seq: e.ent_seq,
+                        word: k.text.clone(),
+                        reading: reading.text.clone(),
+                        gloss: gloss.clone(),
+                    },
+                ));
+            }
+        }
+    }
+
+    candidates
+        .into_iter()
+        .map(|(c, mut words)| {
+            words.sort_by(|(a, x), (b, y)| {
+                a.cmp(b)
+                    .then(x.word.chars().count().cmp(&y.word.chars().count()))
+                    .then(x.seq.cmp(&y.seq))
+            });
+            words.truncate(limit);
+            (c, words.into_iter().map(|(_, w)| w).collect())
+        })
+        .collect()
+}
+
+#[test]
+fn test_top_words() {
+    use model::entry::{Kanji, Reading, Sense};
+
+    let entry = |ent_seq, text: &str, reading: &str, priority: &[&str]| {
+        Entry::builder(ent_seq)
+            .kanji(vec![Kanji {
+                text: text.into(),
+                info: vec![],
+                priority: priority.iter().map(|p| p.to_string()).collect(),
+            }])
+            .readings(vec![Reading {
+                text: reading.into(),
+                no_kanji: false,
+                restrictions: vec![],
+                info: vec![],
+                priority: vec![],
+            }])
+            .senses(vec![Sense {
+                glosses: vec![format!("gloss {}", ent_seq)],
+                ..Default::default()
+            }])
+            .build()
+    };
+
+    let entries = [
+        entry(1, "水曜日", "すいようび", &["ichi1"]),
+        entry(2, "水", "みず", &["ichi1", "nf01"]),
+        entry(3, "水道", "すいどう", &["news1", "nf12"]),
+        entry(4, "水母", "くらげ", &[]),
+    ];
+    let top = top_words(&entries, 2);
+
+    let seqs = |c| top[&c].iter().map(|w| w.seq).collect::<Vec<_>>();
+    assert_eq!(seqs('水'), [2, 3]);
+    assert_eq!(seqs('曜'), [1]);
+    assert!(!top.contains_key(&'母'));
+    assert_eq!(top[&'道'][0].reading, "すいどう");
+    assert_eq!(top[&'道'][0].gloss, "gloss 3");
+}
@@ -1,6 +1,99 @@
+use db::{json::Format, kanji::Duplicates, mongo::Mode};
+use parse::source::Dir;
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+
+mod check;
 mod db;
 
 fn main() {
-    // db::mongo::update_kanjidic().expect("failed to update kanjidic");
-    db::json::update_kanjidic()
+    // report each step with its timing as it finishes, RUST_LOG=debug
+    // adds progress within each file
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
+
+    let data = Dir::from_env();
+
+    // `--raw` also stores the entries as parsed, for debugging
+    let flags: Vec<String> = std::env::args().skip(2).collect();
+    let raw = flags.iter().any(|f| f == "--raw");
+    // `--duplicates=error|keep-first|merge` resolves entries sharing a
+    // literal, keeping the first by default
+    let duplicates = flags
+        .iter()
+        .find_map(|f| f.strip_prefix("--duplicates="))
+        .map(|p| Duplicates::parse(p).unwrap_or_else(|| panic!("invalid policy {:?}", p)))
+        .unwrap_or_default();
+    // `--format=dump|kanjidic2-json` selects what the json command writes
+    let format = flags
+        .iter()
+        .find_map(|f| f.strip_prefix("--format="))
+        .map(|f| Format::parse(f).unwrap_or_else(|| panic!("invalid format {:?}", f)))
+        .unwrap_or_default();
+    // `--fields=` chooses the columns of the anki and csv exports, see
+    // `db::columns`
+    let columns = flags
+        .iter()
+        .find_map(|f| f.strip_prefix("--fields="))
+        .map(|f| db::columns::parse(f).unwrap_or_else(|| panic!("invalid fields {:?}", f)));
+
+    // a running backend with a cache keeps serving what it cached before
+    // until CACHE_TTL passes, unless busted with POST /admin/cache/bust
+    match std::env::args().nth(1).as_deref() {
+        Some("mongo") => {
+            // `mongo --incremental` only writes the entries which changed
+            let mode = match flags.iter().any(|f| f == "--incremental") {
+                true => Mode::Incremental,
+                false => Mode::Reset,
+            };
+            db::mongo::update_kanjidic(&data, mode, duplicates, raw)
+                .expect("failed to update kanjidic")
+        }
+        Some("jmdict") => db::mongo::update_jmdict(&data, raw).expect("failed to update jmdict"),
+        Some("jmdict-json") => db::json::dump_jmdict(&data, raw),
+        Some("names") => db::mongo::update_names(&data).expect("failed to update names"),
+        Some("krad") => db::mongo::update_krad(&data).expect("failed to update krad"),
+        Some("examples") => db::mongo::update_examples(&data).expect("failed to update examples"),
+        Some("strokes") => db::mongo::update_strokes(&data).expect("failed to update strokes"),
+        Some("similar") => {
+            db::mongo::update_similar(&data).expect("failed to update similar kanji")
+        }
+        // `check [file...]` checks downloaded files are well formed before
+        // populating from them, kanjidic2.xml and JMdict_e.xml by default
+        Some("check") => {
+            let files: Vec<&str> = flags
+                .iter()
+                .filter(|f| !f.starts_with("--"))
+                .map(String::as_str)
+                .collect();
+            let files = if files.is_empty() {
+                check::FILES
+            } else {
+                &files
+            };
+            if !check::check(&data, files) {
+                std::process::exit(1);
+            }
+        }
+        Some("migrate") => db::mongo::connect()
+            .and_then(|database| db::migrations::migrate(&database))
+            .expect("failed to migrate"),
+        None | Some("json") if format == Format::Kanjidic2Json => {
+            db::json::export_kanjidic2(&data, duplicates)
+        }
+        // json, bin, shards, anki and csv, or several at once such as
+        // json,bin to convert only once
+        targets => {
+            let mut sinks: Vec<_> = targets
+                .unwrap_or("json")
+                .split(',')
+                .map(|t| {
+                    db::sink::named(t, columns.as_deref())
+                        .unwrap_or_else(|| panic!("unknown target {:?}", t))
+                })
+                .collect();
+            db::sink::run(&data, duplicates, &mut sinks)
+        }
+    }
 }